@@ -0,0 +1,70 @@
+use bellperson::groth16::generate_parameters;
+use bellperson::{Circuit, ConstraintSystem, SynthesisError};
+use blstrs::{Bls12, G1Projective, G2Projective, Scalar as Fr};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use ff::{Field, PrimeField};
+use group::Group;
+use rand::SeedableRng;
+
+/// Repeated squaring of a public input, one constraint per squaring.
+#[derive(Clone)]
+struct Squarings {
+    num_constraints: usize,
+}
+
+impl<Scalar: PrimeField> Circuit<Scalar> for Squarings {
+    fn synthesize<CS: ConstraintSystem<Scalar>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+        let mut x = cs.alloc_input(|| "x", || Ok(Scalar::one()))?;
+        for i in 0..self.num_constraints {
+            let y = cs.alloc(|| format!("y {}", i), || Ok(Scalar::one()))?;
+            cs.enforce(
+                || format!("x * x = y {}", i),
+                |lc| lc + x,
+                |lc| lc + x,
+                |lc| lc + y,
+            );
+            x = y;
+        }
+        Ok(())
+    }
+}
+
+fn generator_benchmark(c: &mut Criterion) {
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0);
+    let g1 = G1Projective::random(&mut rng);
+    let g2 = G2Projective::random(&mut rng);
+    let toxic: Vec<Fr> = (0..5).map(|_| Fr::random(&mut rng)).collect();
+
+    let mut group = c.benchmark_group("generate_parameters");
+    group.sample_size(10);
+    for log_size in [10, 12].iter() {
+        let circuit = Squarings {
+            num_constraints: (1 << log_size) - 2,
+        };
+        group.bench_with_input(
+            BenchmarkId::from_parameter(log_size),
+            &circuit,
+            |b, circuit| {
+                b.iter(|| {
+                    black_box(
+                        generate_parameters::<Bls12, _>(
+                            circuit.clone(),
+                            g1,
+                            g2,
+                            toxic[0],
+                            toxic[1],
+                            toxic[2],
+                            toxic[3],
+                            toxic[4],
+                        )
+                        .unwrap(),
+                    )
+                });
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, generator_benchmark);
+criterion_main!(benches);
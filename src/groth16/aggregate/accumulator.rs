@@ -1,9 +1,10 @@
+use crate::parallel::prelude::*;
+#[cfg(feature = "multicore")]
 use crossbeam_channel::{bounded, Receiver, Sender};
 use ff::Field;
 use group::{Curve, Group};
 use pairing::{Engine, MillerLoopResult, MultiMillerLoop};
 use rand_core::RngCore;
-use rayon::prelude::*;
 
 use crate::SynthesisError;
 use std::ops::Mul;
@@ -11,6 +12,7 @@ use std::sync::{
     atomic::{AtomicBool, Ordering::SeqCst},
     Arc, Mutex,
 };
+#[cfg(feature = "multicore")]
 use std::thread;
 
 /// Holds the logic for merging multiple pairing checks of the form
@@ -21,6 +23,9 @@ use std::thread;
 ///
 /// Into a compressed form where only one final exponentiation is required. All
 /// checks but up to one will be randomized.
+///
+/// With the `multicore` feature the checks are merged on a separate thread as
+/// they come in, otherwise they are merged directly by the caller.
 #[derive(Debug)]
 pub struct PairingChecks<E, R>
 where
@@ -29,8 +34,13 @@ where
 {
     /// Circuit breaker to allow canceling all checks and marking the whole check as failed.
     valid: Arc<AtomicBool>,
+    #[cfg(feature = "multicore")]
     merge_send: Sender<Result<PairingCheck<E>, SynthesisError>>,
+    #[cfg(feature = "multicore")]
     valid_recv: Receiver<Result<bool, SynthesisError>>,
+    /// The merged checks, or the first reported error.
+    #[cfg(not(feature = "multicore"))]
+    acc: Mutex<Result<PairingCheck<E>, SynthesisError>>,
     /// Random number generator used for generating the random coefficients.
     rng: Mutex<R>,
     /// Ensures that the non randomized check is only added exactly once.
@@ -42,6 +52,7 @@ where
     E: MultiMillerLoop,
     R: RngCore + Send,
 {
+    #[cfg(feature = "multicore")]
     #[allow(clippy::type_complexity)]
     pub fn new(rng: R) -> Self {
         let (merge_send, merge_recv): (
@@ -89,17 +100,37 @@ where
         }
     }
 
+    #[cfg(not(feature = "multicore"))]
+    pub fn new(rng: R) -> Self {
+        PairingChecks {
+            valid: Arc::new(AtomicBool::new(true)),
+            acc: Mutex::new(Ok(PairingCheck::new())),
+            rng: Mutex::new(rng),
+            non_random_check_done: AtomicBool::new(false),
+        }
+    }
+
     /// Fails the whole check.
     pub fn invalidate(&self) {
         self.valid.store(false, SeqCst);
     }
 
+    #[cfg(feature = "multicore")]
     pub fn report_err(&self, e: SynthesisError) {
         self.merge_send
             .send(Err(e))
             .expect("expect to send on channel");
     }
 
+    #[cfg(not(feature = "multicore"))]
+    pub fn report_err(&self, e: SynthesisError) {
+        self.valid.store(false, SeqCst);
+        let mut acc = self.acc.lock().unwrap();
+        if acc.is_ok() {
+            *acc = Err(e);
+        }
+    }
+
     fn merge_pair(
         &self,
         result: <E as MultiMillerLoop>::Result,
@@ -166,17 +197,28 @@ where
             self.non_random_check_done.store(true, SeqCst);
         };
 
-        // This send is "best effort". If the verification in `verify_tipp_mipp()` identifies
-        // an invalid aggregation, the `self.valid` is set to `false`. That terminates the thread
-        // that receives those messages, hence also the receiving channel is closed.
-        // This means that if the aggrigation is invalid, it is expected that the message cannot
-        // be sent.
-        let sent = self.merge_send.send(Ok(check));
-        if sent.is_err() && self.valid.load(SeqCst) {
-            panic!("Channel was closed although it is still valid.")
+        #[cfg(not(feature = "multicore"))]
+        if self.valid.load(SeqCst) {
+            if let Ok(acc) = &mut *self.acc.lock().unwrap() {
+                acc.merge(&check);
+            }
+        }
+
+        #[cfg(feature = "multicore")]
+        {
+            // This send is "best effort". If the verification in `verify_tipp_mipp()` identifies
+            // an invalid aggregation, the `self.valid` is set to `false`. That terminates the thread
+            // that receives those messages, hence also the receiving channel is closed.
+            // This means that if the aggrigation is invalid, it is expected that the message cannot
+            // be sent.
+            let sent = self.merge_send.send(Ok(check));
+            if sent.is_err() && self.valid.load(SeqCst) {
+                panic!("Channel was closed although it is still valid.")
+            }
         }
     }
 
+    #[cfg(feature = "multicore")]
     pub fn verify(self) -> Result<bool, SynthesisError> {
         let Self {
             valid,
@@ -192,6 +234,12 @@ where
         }
         valid_recv.recv().unwrap()
     }
+
+    #[cfg(not(feature = "multicore"))]
+    pub fn verify(self) -> Result<bool, SynthesisError> {
+        let acc = self.acc.into_inner().unwrap()?;
+        Ok(self.valid.load(SeqCst) && acc.verify())
+    }
 }
 
 /// PairingCheck represents a check of the form e(A,B)e(C,D)... = T. Checks can
@@ -305,6 +353,11 @@ where
 
     fn verify(&self) -> bool {
         let left = self.left.final_exponentiation();
+        // the right side is still the placeholder for one when only checks
+        // against one have been merged
+        if self.right == <E as Engine>::Gt::generator() {
+            return left == <E as Engine>::Gt::identity();
+        }
         left == self.right
     }
 }
@@ -363,4 +416,31 @@ mod test {
             });
         assert!(final_tuple.verify());
     }
+
+    #[test]
+    fn test_pairing_check_one() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let g1r = G1Projective::random(&mut rng).to_affine();
+        let g2r = G2Projective::random(&mut rng).to_affine();
+        let ng1r = -g1r;
+        let one = <Bls12 as Engine>::Gt::generator();
+
+        // e(A,B)e(-A,B) = 1
+        let mut acc = PairingCheck::<Bls12>::new();
+        let coeff = derive_non_zero::<Bls12, _>(&mut rng);
+        acc.merge(&PairingCheck::new_random_from_miller_inputs(
+            coeff,
+            &[(&g1r, &g2r), (&ng1r, &g2r)],
+            &one,
+        ));
+        assert!(acc.verify());
+
+        let coeff = derive_non_zero::<Bls12, _>(&mut rng);
+        acc.merge(&PairingCheck::new_random_from_miller_inputs(
+            coeff,
+            &[(&g1r, &g2r)],
+            &one,
+        ));
+        assert!(!acc.verify());
+    }
 }
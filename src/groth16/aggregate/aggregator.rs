@@ -0,0 +1,258 @@
+use std::marker::PhantomData;
+
+use crate::parallel::prelude::*;
+use blstrs::Compress;
+use ff::PrimeField;
+use pairing::{Engine, MillerLoopResult, MultiMillerLoop};
+use serde::Serialize;
+
+use super::{
+    commit, pad_to_power_of_two,
+    prove::{aggregate_from_commitments, FirstRoundCommitments},
+    transcript::{Sha256Transcript, TranscriptProtocol},
+    AggregateProof, ProverSRS,
+};
+use crate::groth16::Proof;
+use crate::SynthesisError;
+
+/// Miller loops of the two parts of a commitment, the final exponentiation is
+/// run once all the proofs are added.
+type MillerOutput<E> = (
+    <E as MultiMillerLoop>::Result,
+    <E as MultiMillerLoop>::Result,
+);
+
+/// Aggregator aggregates a given number of proofs received one at a time. The
+/// commitments to A, B and C and the commitments of the first GIPA round only
+/// depend on each proof and its position, so they are accumulated as proofs
+/// are added. `finalize` then only runs the parts of the aggregation depending
+/// on the challenges derived from all the proofs, and returns the same proof
+/// as `aggregate_proofs` would with the proofs in the order they were added.
+/// Padding proofs are only known once the last proof is added, so a number of
+/// proofs which is a power of two makes `finalize` the fastest.
+pub struct Aggregator<'a, E, P = Sha256Transcript>
+where
+    E: MultiMillerLoop,
+{
+    srs: &'a ProverSRS<E>,
+    /// number of proofs to aggregate, before padding
+    nproofs: usize,
+    proofs: Vec<Proof<E>>,
+    com_ab: MillerOutput<E>,
+    com_c: MillerOutput<E>,
+    /// commitments of the first GIPA round, see `FirstRoundCommitments`
+    tab_l: (MillerOutput<E>, MillerOutput<E>),
+    tab_r: (MillerOutput<E>, MillerOutput<E>),
+    tuc_l: MillerOutput<E>,
+    tuc_r: MillerOutput<E>,
+    _protocol: PhantomData<P>,
+}
+
+impl<'a, E> Aggregator<'a, E>
+where
+    E: MultiMillerLoop,
+{
+    /// Returns an aggregator of `nproofs` proofs, with the same requirements
+    /// on `nproofs` and the SRS as `aggregate_proofs`.
+    pub fn new(srs: &'a ProverSRS<E>, nproofs: usize) -> Result<Self, SynthesisError> {
+        Self::with_transcript(srs, nproofs)
+    }
+}
+
+impl<'a, E, P> Aggregator<'a, E, P>
+where
+    E: MultiMillerLoop,
+    P: TranscriptProtocol,
+{
+    /// Returns an aggregator like `new` deriving the Fiat–Shamir challenges
+    /// with the transcript protocol `P`, see
+    /// `aggregate_proofs_with_transcript`.
+    pub fn with_transcript(srs: &'a ProverSRS<E>, nproofs: usize) -> Result<Self, SynthesisError> {
+        if nproofs < 2 {
+            return Err(SynthesisError::MalformedProofs(
+                "aggregating less than 2 proofs is not allowed".to_string(),
+            ));
+        }
+        if !srs.has_correct_len(nproofs.next_power_of_two()) {
+            return Err(SynthesisError::MalformedSrs);
+        }
+        let one = (E::Result::default(), E::Result::default());
+        Ok(Aggregator {
+            srs,
+            nproofs,
+            proofs: Vec::with_capacity(nproofs),
+            com_ab: one,
+            com_c: one,
+            tab_l: (one, one),
+            tab_r: (one, one),
+            tuc_l: one,
+            tuc_r: one,
+            _protocol: PhantomData,
+        })
+    }
+
+    /// Returns the number of proofs to aggregate.
+    pub fn nproofs(&self) -> usize {
+        self.nproofs
+    }
+
+    /// Returns the number of proofs added so far.
+    pub fn len(&self) -> usize {
+        self.proofs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.proofs.is_empty()
+    }
+
+    /// Returns true once all the proofs to aggregate are added.
+    pub fn is_complete(&self) -> bool {
+        self.proofs.len() == self.nproofs
+    }
+
+    /// Adds the next proof to aggregate, proofs are aggregated in the order
+    /// they are added.
+    pub fn add(&mut self, proof: Proof<E>) -> Result<(), SynthesisError> {
+        if self.is_complete() {
+            return Err(SynthesisError::MalformedProofs(
+                "all the proofs to aggregate are already added".to_string(),
+            ));
+        }
+        self.accumulate(self.proofs.len(), &proof);
+        self.proofs.push(proof);
+        Ok(())
+    }
+
+    /// Accumulates the Miller loops of the commitments with the proof at
+    /// position `i`.
+    fn accumulate(&mut self, i: usize, proof: &Proof<E>) {
+        let (vkey, wkey) = (&self.srs.vkey, &self.srs.wkey);
+        // the first GIPA round commits to each half with the keys of the
+        // other half
+        let split = vkey.a.len() / 2;
+        let j = if i < split { i + split } else { i - split };
+
+        let prepared = [proof.b, vkey.a[i], vkey.b[i], vkey.a[j], vkey.b[j]]
+            .iter()
+            .map(|&p| p.into())
+            .collect::<Vec<E::G2Prepared>>();
+        let (b, v1, v2) = (&prepared[0], &prepared[1], &prepared[2]);
+        let (v1_j, v2_j) = (&prepared[3], &prepared[4]);
+        let (w1, w2) = (&wkey.a[i], &wkey.b[i]);
+        let (w1_j, w2_j) = (&wkey.a[j], &wkey.b[j]);
+        let (a, c) = (&proof.a, &proof.c);
+        par! {
+            let com_ab = (
+                E::multi_miller_loop(&[(a, v1), (w1, b)]),
+                E::multi_miller_loop(&[(a, v2), (w2, b)])
+            ),
+            let com_c = (
+                E::multi_miller_loop(&[(c, v1)]),
+                E::multi_miller_loop(&[(c, v2)])
+            ),
+            let a_v = (
+                E::multi_miller_loop(&[(a, v1_j)]),
+                E::multi_miller_loop(&[(a, v2_j)])
+            ),
+            let w_b = (
+                E::multi_miller_loop(&[(w1_j, b)]),
+                E::multi_miller_loop(&[(w2_j, b)])
+            ),
+            let c_v = (
+                E::multi_miller_loop(&[(c, v1_j)]),
+                E::multi_miller_loop(&[(c, v2_j)])
+            )
+        };
+
+        add_assign::<E>(&mut self.com_ab, com_ab);
+        add_assign::<E>(&mut self.com_c, com_c);
+        if i < split {
+            add_assign::<E>(&mut self.tab_r.0, a_v);
+            add_assign::<E>(&mut self.tab_l.1, w_b);
+            add_assign::<E>(&mut self.tuc_r, c_v);
+        } else {
+            add_assign::<E>(&mut self.tab_l.0, a_v);
+            add_assign::<E>(&mut self.tab_r.1, w_b);
+            add_assign::<E>(&mut self.tuc_l, c_v);
+        }
+    }
+}
+
+impl<'a, E, P> Aggregator<'a, E, P>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    <E::Fr as PrimeField>::Repr: Send + Sync,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+{
+    /// Aggregates the proofs added, which must be all the proofs to
+    /// aggregate. The same WARNING about `transcript_include` as
+    /// `aggregate_proofs` applies.
+    pub fn finalize(
+        mut self,
+        transcript_include: &[u8],
+    ) -> Result<AggregateProof<E>, SynthesisError> {
+        if !self.is_complete() {
+            return Err(SynthesisError::MalformedProofs(
+                "not all the proofs to aggregate are added".to_string(),
+            ));
+        }
+        // padding proofs repeat the last proof
+        let last = self.proofs[self.nproofs - 1].clone();
+        for i in self.nproofs..self.srs.vkey.a.len() {
+            self.accumulate(i, &last);
+        }
+
+        let padded = pad_to_power_of_two(&self.proofs);
+        let proofs = &padded[..];
+        par! {
+            let a = proofs.iter().map(|proof| proof.a).collect::<Vec<_>>(),
+            let b = proofs.iter().map(|proof| proof.b).collect::<Vec<_>>(),
+            let c = proofs.iter().map(|proof| proof.c).collect::<Vec<_>>()
+        };
+
+        let outputs = [
+            self.com_ab,
+            self.com_c,
+            self.tab_l.0,
+            self.tab_l.1,
+            self.tab_r.0,
+            self.tab_r.1,
+            self.tuc_l,
+            self.tuc_r,
+        ]
+        .par_iter()
+        .map(|(x, y)| (x.final_exponentiation(), y.final_exponentiation()))
+        .collect::<Vec<commit::Output<E>>>();
+        let first_round = FirstRoundCommitments {
+            tab_l: (outputs[2], outputs[3]),
+            tab_r: (outputs[4], outputs[5]),
+            tuc_l: outputs[6],
+            tuc_r: outputs[7],
+        };
+
+        let (proof, _, _, _) = aggregate_from_commitments::<E, P>(
+            self.srs,
+            transcript_include,
+            self.nproofs,
+            &a,
+            &b,
+            &c,
+            outputs[0],
+            outputs[1],
+            Some(first_round),
+            &[],
+            None,
+        )?;
+        Ok(proof)
+    }
+}
+
+fn add_assign<E: MultiMillerLoop>(acc: &mut MillerOutput<E>, (x, y): MillerOutput<E>) {
+    acc.0 += x;
+    acc.1 += y;
+}
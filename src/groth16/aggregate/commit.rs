@@ -27,8 +27,8 @@
 /// one commitment.
 use std::ops::AddAssign;
 
+use crate::parallel::prelude::*;
 use group::{prime::PrimeCurveAffine, Curve};
-use rayon::prelude::*;
 
 use crate::groth16::aggregate::inner_product;
 use crate::SynthesisError;
@@ -1,7 +1,7 @@
+use crate::parallel::prelude::*;
 use ff::PrimeField;
 use group::prime::PrimeCurveAffine;
 use pairing::{MillerLoopResult, MultiMillerLoop};
-use rayon::prelude::*;
 
 use crate::groth16::multiscalar::*;
 use crate::SynthesisError;
@@ -0,0 +1,173 @@
+//! Keccak-256 as used by Ethereum, i.e. with the original Keccak padding and
+//! not the one of the standardized SHA3-256.
+
+/// Round constants of the iota step.
+const ROUND_CONSTANTS: [u64; 24] = [
+    0x0000_0000_0000_0001,
+    0x0000_0000_0000_8082,
+    0x8000_0000_0000_808A,
+    0x8000_0000_8000_8000,
+    0x0000_0000_0000_808B,
+    0x0000_0000_8000_0001,
+    0x8000_0000_8000_8081,
+    0x8000_0000_0000_8009,
+    0x0000_0000_0000_008A,
+    0x0000_0000_0000_0088,
+    0x0000_0000_8000_8009,
+    0x0000_0000_8000_000A,
+    0x0000_0000_8000_808B,
+    0x8000_0000_0000_008B,
+    0x8000_0000_0000_8089,
+    0x8000_0000_0000_8003,
+    0x8000_0000_0000_8002,
+    0x8000_0000_0000_0080,
+    0x0000_0000_0000_800A,
+    0x8000_0000_8000_000A,
+    0x8000_0000_8000_8081,
+    0x8000_0000_0000_8080,
+    0x0000_0000_8000_0001,
+    0x8000_0000_8000_8008,
+];
+
+/// Rotation offsets of the rho step, indexed by `x + 5 * y`.
+const ROTATIONS: [u32; 25] = [
+    0, 1, 62, 28, 27, 36, 44, 6, 55, 20, 3, 10, 43, 25, 39, 41, 45, 15, 21, 8, 18, 2, 61, 56, 14,
+];
+
+/// Number of bytes absorbed per permutation: 1600 bits minus twice the output
+/// size.
+const RATE: usize = 136;
+
+/// The Keccak-f[1600] permutation, lanes are indexed by `x + 5 * y`.
+fn keccak_f(a: &mut [u64; 25]) {
+    for rc in ROUND_CONSTANTS.iter() {
+        // theta
+        let mut c = [0u64; 5];
+        for (x, cx) in c.iter_mut().enumerate() {
+            *cx = a[x] ^ a[x + 5] ^ a[x + 10] ^ a[x + 15] ^ a[x + 20];
+        }
+        for x in 0..5 {
+            let d = c[(x + 4) % 5] ^ c[(x + 1) % 5].rotate_left(1);
+            for y in 0..5 {
+                a[x + 5 * y] ^= d;
+            }
+        }
+
+        // rho and pi
+        let mut b = [0u64; 25];
+        for x in 0..5 {
+            for y in 0..5 {
+                b[y + 5 * ((2 * x + 3 * y) % 5)] = a[x + 5 * y].rotate_left(ROTATIONS[x + 5 * y]);
+            }
+        }
+
+        // chi
+        for x in 0..5 {
+            for y in 0..5 {
+                a[x + 5 * y] = b[x + 5 * y] ^ (!b[(x + 1) % 5 + 5 * y] & b[(x + 2) % 5 + 5 * y]);
+            }
+        }
+
+        // iota
+        a[0] ^= rc;
+    }
+}
+
+/// Incremental Keccak-256 hasher.
+#[derive(Clone, Debug)]
+pub struct Keccak256 {
+    state: [u64; 25],
+    /// bytes of the block being filled
+    buffer: [u8; RATE],
+    buffer_len: usize,
+}
+
+impl Default for Keccak256 {
+    fn default() -> Self {
+        Keccak256 {
+            state: [0; 25],
+            buffer: [0; RATE],
+            buffer_len: 0,
+        }
+    }
+}
+
+impl Keccak256 {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() {
+            let take = std::cmp::min(RATE - self.buffer_len, data.len());
+            self.buffer[self.buffer_len..self.buffer_len + take].copy_from_slice(&data[..take]);
+            self.buffer_len += take;
+            data = &data[take..];
+            if self.buffer_len == RATE {
+                self.absorb_block();
+            }
+        }
+    }
+
+    pub fn finalize(mut self) -> [u8; 32] {
+        // pad10*1 with the Keccak domain bit
+        for b in &mut self.buffer[self.buffer_len..] {
+            *b = 0;
+        }
+        self.buffer[self.buffer_len] ^= 0x01;
+        self.buffer[RATE - 1] ^= 0x80;
+        self.absorb_block();
+
+        let mut out = [0u8; 32];
+        for (chunk, lane) in out.chunks_mut(8).zip(self.state.iter()) {
+            chunk.copy_from_slice(&lane.to_le_bytes());
+        }
+        out
+    }
+
+    fn absorb_block(&mut self) {
+        for (lane, chunk) in self.state.iter_mut().zip(self.buffer.chunks(8)) {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(chunk);
+            *lane ^= u64::from_le_bytes(bytes);
+        }
+        keccak_f(&mut self.state);
+        self.buffer_len = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keccak256(data: &[u8]) -> [u8; 32] {
+        let mut hasher = Keccak256::new();
+        hasher.update(data);
+        hasher.finalize()
+    }
+
+    #[test]
+    fn test_keccak256() {
+        assert_eq!(
+            keccak256(b"")[..],
+            [
+                0xc5, 0xd2, 0x46, 0x01, 0x86, 0xf7, 0x23, 0x3c, 0x92, 0x7e, 0x7d, 0xb2, 0xdc, 0xc7,
+                0x03, 0xc0, 0xe5, 0x00, 0xb6, 0x53, 0xca, 0x82, 0x27, 0x3b, 0x7b, 0xfa, 0xd8, 0x04,
+                0x5d, 0x85, 0xa4, 0x70
+            ][..]
+        );
+        // ERC-20 transfer selector
+        assert_eq!(
+            keccak256(b"transfer(address,uint256)")[..4],
+            [0xa9, 0x05, 0x9c, 0xbb][..]
+        );
+
+        // absorbing across block boundaries does not change the digest
+        let data = (0..400).map(|i| i as u8).collect::<Vec<_>>();
+        let mut hasher = Keccak256::new();
+        for chunk in data.chunks(7) {
+            hasher.update(chunk);
+        }
+        assert_eq!(hasher.finalize(), keccak256(&data));
+    }
+}
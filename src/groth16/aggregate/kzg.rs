@@ -0,0 +1,368 @@
+//! KZG polynomial commitments over the powers of a `GenericSRS`, independent
+//! of the commitment keys of the aggregation.
+//!
+//! Polynomials are committed in G1 with the powers $\{g^{a^i}\}$ and an
+//! opening proves the evaluations of a polynomial at one or several points
+//! with a single G1 element. Openings are verified through `PairingChecks`,
+//! so any number of them, possibly alongside other pairing checks, is verified
+//! with a single final exponentiation.
+use crate::groth16::multiscalar::{
+    par_multiscalar, precompute_fixed_window, MultiscalarPrecomp, MultiscalarPrecompOwned,
+    ScalarList, WINDOW_SIZE,
+};
+use crate::parallel::prelude::*;
+use crate::SynthesisError;
+use ff::{Field, PrimeField};
+use group::{prime::PrimeCurveAffine, Curve, Group};
+use pairing::{Engine, MultiMillerLoop};
+use rand_core::RngCore;
+
+use super::{accumulator::PairingChecks, poly::DensePolynomial, GenericSRS};
+
+/// The key to commit to polynomials of degree at most `max_degree` and to
+/// open them.
+#[derive(Clone, Debug)]
+pub struct CommitterKey<E: Engine> {
+    /// $\{g^{a^i}\}_{i=0}^{max\_degree}$
+    powers_table: MultiscalarPrecompOwned<E::G1Affine>,
+}
+
+/// The key to verify openings at up to `max_points` points.
+#[derive(Clone, Debug)]
+pub struct VerifierKey<E: Engine> {
+    /// $\{g^{a^i}\}_{i=0}^{max\_points-1}$ to commit to the interpolation of
+    /// the evaluations
+    pub g_powers: Vec<E::G1Affine>,
+    /// $\{h^{a^i}\}_{i=0}^{max\_points}$ to commit to the vanishing polynomial
+    /// of the points
+    pub h_powers: Vec<E::G2Affine>,
+}
+
+/// The evaluations of a committed polynomial at some points, and the proof
+/// they are correct.
+#[derive(Clone, Debug)]
+pub struct Opening<E: Engine> {
+    /// the evaluations, in the order of the points
+    pub evals: Vec<E::Fr>,
+    /// $g^{q(a)}$ where $q = (f - I) / Z$ with $I$ the interpolation of the
+    /// evaluations and $Z$ the vanishing polynomial of the points
+    pub proof: E::G1Affine,
+}
+
+impl<E: Engine> PartialEq for Opening<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.evals == other.evals && self.proof == other.proof
+    }
+}
+
+/// An opening to verify with `batch_verify`.
+pub struct BatchItem<'a, E: Engine> {
+    pub commitment: &'a E::G1Affine,
+    pub points: &'a [E::Fr],
+    pub opening: &'a Opening<E>,
+}
+
+/// Derives the keys to commit to polynomials of degree at most `max_degree`
+/// and to verify openings at up to `max_points` points at once. The SRS must
+/// contain at least `max_degree + 1` powers in G1 and `max_points + 1` in G2,
+/// otherwise `MalformedSrs` is returned.
+pub fn setup<E: Engine>(
+    srs: &GenericSRS<E>,
+    max_degree: usize,
+    max_points: usize,
+) -> Result<(CommitterKey<E>, VerifierKey<E>), SynthesisError> {
+    let good_len = max_points > 0
+        && srs.g_alpha_powers.len() > max_degree
+        && srs.g_alpha_powers.len() >= max_points
+        && srs.h_alpha_powers.len() > max_points;
+    if !good_len {
+        return Err(SynthesisError::MalformedSrs);
+    }
+    let powers_table = precompute_fixed_window(&srs.g_alpha_powers[..=max_degree], WINDOW_SIZE);
+    Ok((
+        CommitterKey { powers_table },
+        VerifierKey {
+            g_powers: srs.g_alpha_powers[..max_points].to_vec(),
+            h_powers: srs.h_alpha_powers[..=max_points].to_vec(),
+        },
+    ))
+}
+
+impl<E: Engine> CommitterKey<E> {
+    /// Returns the maximum degree of the polynomials this key commits to.
+    pub fn max_degree(&self) -> usize {
+        self.powers_table.tables().len() - 1
+    }
+}
+
+impl<E: Engine> VerifierKey<E> {
+    /// Returns the maximum number of points of an opening this key verifies.
+    pub fn max_points(&self) -> usize {
+        self.g_powers.len()
+    }
+}
+
+/// Commits to `poly`, its degree must be at most the maximum degree of the
+/// key.
+pub fn commit<E>(
+    ck: &CommitterKey<E>,
+    poly: &DensePolynomial<E::Fr>,
+) -> Result<E::G1Affine, SynthesisError>
+where
+    E: Engine,
+    <E::Fr as PrimeField>::Repr: Send + Sync,
+{
+    let coeffs = poly.coeffs();
+    if coeffs.len() > ck.powers_table.tables().len() {
+        return Err(SynthesisError::PolynomialDegreeTooLarge);
+    }
+    let getter = |i: usize| coeffs[i].to_repr();
+    Ok(par_multiscalar::<_, E::G1Affine>(
+        &ScalarList::Getter(getter, coeffs.len()),
+        &ck.powers_table,
+        std::mem::size_of::<<E::Fr as PrimeField>::Repr>() * 8,
+    )
+    .to_affine())
+}
+
+/// Opens `poly` at `points`, which must be distinct. A single point is the
+/// regular KZG opening.
+pub fn open<E>(
+    ck: &CommitterKey<E>,
+    poly: &DensePolynomial<E::Fr>,
+    points: &[E::Fr],
+) -> Result<Opening<E>, SynthesisError>
+where
+    E: Engine,
+    <E::Fr as PrimeField>::Repr: Send + Sync,
+{
+    if points.is_empty() {
+        return Err(SynthesisError::IncompatibleLengthVector(
+            "no point to open at".to_string(),
+        ));
+    }
+    let evals = points
+        .par_iter()
+        .map(|x| poly.evaluate(x))
+        .collect::<Vec<_>>();
+    let interpolation = interpolate(points, &evals).ok_or(SynthesisError::DivisionByZero)?;
+    // f - I vanishes on the points, the division is exact
+    let quotient = &(poly - &interpolation) / &vanishing_polynomial(points);
+    let proof = commit(ck, &quotient)?;
+    Ok(Opening { evals, proof })
+}
+
+/// Merges the check of `opening` of `commitment` at `points` into
+/// `pairing_checks`:
+///
+/// $$
+/// e(C \cdot g^{-I(a)}, h) = e(\pi, h^{Z(a)})
+/// $$
+///
+/// A malformed opening is reported to `pairing_checks`.
+pub fn verify<E, R>(
+    vk: &VerifierKey<E>,
+    commitment: &E::G1Affine,
+    points: &[E::Fr],
+    opening: &Opening<E>,
+    pairing_checks: &PairingChecks<E, R>,
+) where
+    E: MultiMillerLoop,
+    R: RngCore + Send,
+{
+    if points.is_empty() || points.len() != opening.evals.len() {
+        pairing_checks.report_err(SynthesisError::IncompatibleLengthVector(
+            "points and evaluations do not match".to_string(),
+        ));
+        return;
+    }
+    if points.len() > vk.max_points() {
+        pairing_checks.report_err(SynthesisError::IncompatibleLengthVector(
+            "more points than the verifier key supports".to_string(),
+        ));
+        return;
+    }
+    let interpolation = match interpolate(points, &opening.evals) {
+        Some(interpolation) => interpolation,
+        None => {
+            pairing_checks.report_err(SynthesisError::DivisionByZero);
+            return;
+        }
+    };
+    let vanishing = vanishing_polynomial(points);
+
+    par! {
+        // g^{I(a)}
+        let i_a = evaluate_in_exponent(&vk.g_powers, interpolation.coeffs()),
+        // h^{Z(a)}
+        let z_a = evaluate_in_exponent(&vk.h_powers, vanishing.coeffs())
+    };
+
+    // e(C g^{-I(a)}, -h) e(\pi, h^{Z(a)}) = 1
+    let a = (commitment.to_curve() - i_a).to_affine();
+    let nh = (-vk.h_powers[0].to_curve()).to_affine();
+    let z_a = z_a.to_affine();
+    pairing_checks.merge_miller_inputs(
+        &[(&a, &nh), (&opening.proof, &z_a)],
+        &<E as Engine>::Gt::generator(),
+    );
+}
+
+/// Verifies all `items` with a single final exponentiation. The verification
+/// fails if any opening is malformed.
+pub fn batch_verify<E, R>(
+    vk: &VerifierKey<E>,
+    rng: R,
+    items: &[BatchItem<'_, E>],
+) -> Result<bool, SynthesisError>
+where
+    E: MultiMillerLoop,
+    R: RngCore + Send,
+{
+    let pairing_checks = PairingChecks::new(rng);
+    items.par_iter().for_each(|item| {
+        verify(
+            vk,
+            item.commitment,
+            item.points,
+            item.opening,
+            &pairing_checks,
+        )
+    });
+    pairing_checks.verify()
+}
+
+/// Returns $\prod_i (X - x_i)$.
+fn vanishing_polynomial<F: Field>(points: &[F]) -> DensePolynomial<F> {
+    let mut coeffs = vec![F::one()];
+    for x in points {
+        // multiply by X - x
+        coeffs.insert(0, F::zero());
+        for i in 0..coeffs.len() - 1 {
+            let c = coeffs[i + 1] * x;
+            coeffs[i] -= c;
+        }
+    }
+    DensePolynomial::from_coeffs(coeffs)
+}
+
+/// Returns the polynomial of degree less than the number of points which
+/// evaluates to `evals[i]` at `points[i]`, or `None` if two points are equal.
+fn interpolate<F: Field>(points: &[F], evals: &[F]) -> Option<DensePolynomial<F>> {
+    let vanishing = vanishing_polynomial(points);
+    let mut coeffs = vec![F::zero(); points.len()];
+    for (x, y) in points.iter().zip(evals.iter()) {
+        // Z(X) / (X - x) by synthetic division
+        let z = vanishing.coeffs();
+        let mut basis = vec![F::zero(); points.len()];
+        let mut carry = F::zero();
+        for i in (0..points.len()).rev() {
+            carry = z[i + 1] + carry * x;
+            basis[i] = carry;
+        }
+        // the basis polynomial evaluated at x is \prod_{x_j != x} (x - x_j)
+        let denominator = DensePolynomial::from_coeffs(basis.clone()).evaluate(x);
+        let scale = Option::<F>::from(denominator.invert())? * y;
+        for (c, b) in coeffs.iter_mut().zip(basis.iter()) {
+            *c += *b * scale;
+        }
+    }
+    Some(DensePolynomial::from_coeffs(coeffs))
+}
+
+/// Returns $\prod_i bases_i^{coeffs_i}$, `coeffs` is at most as long as
+/// `bases`.
+fn evaluate_in_exponent<G: PrimeCurveAffine>(bases: &[G], coeffs: &[G::Scalar]) -> G::Curve {
+    bases
+        .iter()
+        .zip(coeffs.iter())
+        .fold(G::Curve::identity(), |acc, (b, c)| acc + *b * c)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::groth16::aggregate::setup_fake_srs;
+    use blstrs::{Bls12, Scalar as Fr};
+    use rand_core::SeedableRng;
+
+    fn random_poly(degree: usize, rng: &mut impl RngCore) -> DensePolynomial<Fr> {
+        DensePolynomial::from_coeffs((0..=degree).map(|_| Fr::random(&mut *rng)).collect())
+    }
+
+    #[test]
+    fn test_interpolate() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let poly = random_poly(4, &mut rng);
+        let points = (0..5).map(|_| Fr::random(&mut rng)).collect::<Vec<_>>();
+        let evals = points.iter().map(|x| poly.evaluate(x)).collect::<Vec<_>>();
+        assert_eq!(
+            interpolate(&points, &evals).unwrap().coeffs(),
+            poly.coeffs()
+        );
+        for x in &points {
+            assert!(bool::from(
+                vanishing_polynomial(&points).evaluate(x).is_zero()
+            ));
+        }
+        assert!(interpolate(&[points[0], points[0]], &evals[..2]).is_none());
+    }
+
+    #[test]
+    fn test_kzg() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        // 16 powers in each group
+        let srs = setup_fake_srs::<Bls12, _>(&mut rng, 8);
+        let (ck, vk) = setup(&srs, 15, 3).unwrap();
+        assert_eq!(ck.max_degree(), 15);
+        assert_eq!(vk.max_points(), 3);
+        assert!(setup(&srs, 16, 3).is_err());
+
+        let polys = (0..3)
+            .map(|i| random_poly(15 - i, &mut rng))
+            .collect::<Vec<_>>();
+        let commitments = polys
+            .iter()
+            .map(|p| commit(&ck, p).unwrap())
+            .collect::<Vec<_>>();
+        let points = (0..3)
+            .map(|i| (0..=i).map(|_| Fr::random(&mut rng)).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let openings = polys
+            .iter()
+            .zip(points.iter())
+            .map(|(p, x)| open(&ck, p, x).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(openings[0].evals, vec![polys[0].evaluate(&points[0][0])]);
+
+        let check = |openings: &[Opening<Bls12>], rng: &mut rand_chacha::ChaChaRng| {
+            let items = commitments
+                .iter()
+                .zip(points.iter())
+                .zip(openings.iter())
+                .map(|((commitment, points), opening)| BatchItem {
+                    commitment,
+                    points,
+                    opening,
+                })
+                .collect::<Vec<_>>();
+            batch_verify(&vk, rng, &items)
+        };
+        assert!(check(&openings, &mut rng).unwrap());
+
+        // a wrong evaluation or proof is rejected
+        let mut invalid = openings.clone();
+        invalid[2].evals[1] += Fr::one();
+        assert!(!check(&invalid, &mut rng).unwrap());
+        let mut invalid = openings.clone();
+        invalid[0].proof = openings[1].proof;
+        assert!(!check(&invalid, &mut rng).unwrap());
+
+        // too large polynomials and malformed openings are errors
+        assert!(commit(&ck, &random_poly(16, &mut rng)).is_err());
+        assert!(open(&ck, &polys[0], &[points[0][0], points[0][0]]).is_err());
+        let mut invalid = openings.clone();
+        invalid[1].evals.pop();
+        assert!(!matches!(check(&invalid, &mut rng), Ok(true)));
+    }
+}
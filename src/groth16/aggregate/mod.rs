@@ -1,27 +1,45 @@
+use std::borrow::Cow;
 use std::ops::AddAssign;
 
+use crate::parallel::prelude::*;
+use blstrs::Compress;
 use ff::Field;
 use group::{prime::PrimeCurveAffine, Curve};
-use rayon::prelude::*;
+use pairing::Engine;
+use serde::Serialize;
+
+use self::transcript::{Challenge, Transcript};
 
 #[macro_use]
 mod macros;
 
 mod accumulator;
+mod aggregator;
 mod commit;
 mod inner_product;
+mod keccak;
+pub mod kzg;
 mod msm;
 mod poly;
 mod proof;
 mod prove;
 mod srs;
+mod srs_cache;
 mod transcript;
 mod verify;
 
+pub use self::accumulator::PairingChecks;
+pub use self::aggregator::Aggregator;
 pub use self::commit::*;
+pub use self::poly::DensePolynomial;
 pub use self::proof::*;
 pub use self::prove::*;
 pub use self::srs::*;
+pub use self::srs_cache::{SharedSpecializedSrs, SrsCache};
+pub use self::transcript::{
+    Blake2s, Blake2sTranscript, CanonicalTranscript, Keccak256Transcript, Sha256Transcript,
+    TranscriptHash, TranscriptProtocol,
+};
 pub use self::verify::*;
 
 /// Returns the vector used for the linear combination fo the inner pairing product
@@ -36,6 +54,106 @@ fn structured_scalar_power<F: Field>(num: usize, s: &F) -> Vec<F> {
     powers
 }
 
+/// Pads `items` to the next power of two by repeating the last element. Proofs
+/// and their public inputs are padded the same way, so every padding proof is
+/// valid for its padding public inputs. `items` must not be empty.
+fn pad_to_power_of_two<T: Clone>(items: &[T]) -> Cow<'_, [T]> {
+    let n = items.len();
+    if n.is_power_of_two() {
+        return Cow::Borrowed(items);
+    }
+    let mut padded = items.to_vec();
+    padded.resize(n.next_power_of_two(), items[n - 1].clone());
+    Cow::Owned(padded)
+}
+
+/// Derives the challenge binding the commitments to the proofs, and to their
+/// public inputs when they are committed to, to the rest of the transcript.
+fn commitments_challenge<E, P>(
+    com_ab: &commit::Output<E>,
+    com_c: &commit::Output<E>,
+    input_commitment: Option<&InputCommitment<E>>,
+) -> Challenge<E>
+where
+    E: Engine,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    E::Gt: Compress + Serialize,
+    E::G2Affine: Serialize,
+{
+    let transcript = Transcript::<E, P>::new("hcom")
+        .write_gt(&com_ab.0)
+        .write_gt(&com_ab.1)
+        .write_gt(&com_c.0)
+        .write_gt(&com_c.1);
+    match input_commitment {
+        Some(inputs) => transcript
+            .write_u32(inputs.nproofs)
+            .write_list(&inputs.coms, |t, (v1, v2)| {
+                t.write_point(v1).write_point(v2)
+            }),
+        None => transcript,
+    }
+    .into_challenge()
+}
+
+/// Derives the challenge used to combine the openings of the committed public
+/// inputs at `r`, once their values `input_evals` are known.
+fn input_combination_challenge<E, P>(r: &Challenge<E>, input_evals: &[E::Fr]) -> Challenge<E>
+where
+    E: Engine,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+{
+    Transcript::<E, P>::new("random-inputs")
+        .write_scalar(r)
+        .write_list(input_evals, |t, x| t.write_scalar(x))
+        .into_challenge()
+}
+
+/// Derives the challenge used for the random linear combination of the proofs.
+/// When the proofs are padded, their actual number is bound to the transcript
+/// so the aggregate can not be verified against the padded public inputs, the
+/// transcript of power of two aggregations is unchanged. The verifying key
+/// indices of a heterogeneous aggregation are bound as well, they are empty
+/// otherwise.
+fn random_linear_combination_challenge<E, P>(
+    hcom: &Challenge<E>,
+    transcript_include: &[u8],
+    nproofs: usize,
+    key_indices: &[u32],
+) -> Challenge<E>
+where
+    E: Engine,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+{
+    let mut transcript = Transcript::<E, P>::new("random-r")
+        .write_scalar(hcom)
+        .write_bytes(transcript_include);
+    if !nproofs.is_power_of_two() {
+        transcript = transcript.write_u64(nproofs as u64);
+    }
+    if !key_indices.is_empty() {
+        transcript = transcript.write_list(key_indices, |t, k| t.write_u32(*k));
+    }
+    transcript.into_challenge()
+}
+
+/// Returns, for each verifying key, the vector `r` where the entries of the
+/// proofs using another key are set to zero. `key_indices` must be padded like
+/// the proofs.
+fn scalars_per_key<F: Field>(r: &[F], key_indices: &[u32], num_keys: usize) -> Vec<Vec<F>> {
+    (0..num_keys)
+        .map(|k| {
+            r.iter()
+                .zip(key_indices.iter())
+                .map(|(ri, &key)| if key as usize == k { *ri } else { F::zero() })
+                .collect()
+        })
+        .collect()
+}
+
 /// compress is similar to commit::{V,W}KEY::compress: it modifies the `vec`
 /// vector by setting the value at index $i:0 -> split$  $vec[i] = vec[i] +
 /// vec[i+split]^scaler$. The `vec` vector is half of its size after this call.
@@ -4,7 +4,7 @@ use group::{
     Curve,
 };
 
-use rayon::prelude::*;
+use crate::parallel::prelude::*;
 
 pub fn get_mul_window_size(num_scalars: usize) -> usize {
     if num_scalars < 32 {
@@ -67,6 +67,14 @@ impl<F: Field> DensePolynomial<F> {
         self.coeffs.is_empty() || self.coeffs.iter().all(|coeff| coeff.is_zero().into())
     }
 
+    /// Evaluates the polynomial at `point`.
+    pub fn evaluate(&self, point: &F) -> F {
+        self.coeffs
+            .iter()
+            .rev()
+            .fold(F::zero(), |acc, coeff| acc * point + coeff)
+    }
+
     pub fn zero() -> Self {
         Self { coeffs: Vec::new() }
     }
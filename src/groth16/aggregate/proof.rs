@@ -1,17 +1,99 @@
+use std::fmt;
 use std::io::{Read, Write};
+use std::marker::PhantomData;
 
 use blstrs::Compress;
-use group::{prime::PrimeCurveAffine, Curve, GroupEncoding};
+use ff::PrimeField;
+use group::{prime::PrimeCurveAffine, Curve, Group, GroupEncoding};
 use pairing::{Engine, MultiMillerLoop};
-use serde::{Deserialize, Serialize};
+use serde::de::{self, MapAccess, Visitor};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
-use crate::groth16::aggregate::{commit, srs};
+use crate::groth16::aggregate::{commit, srs, TranscriptProtocol};
+use crate::groth16::serde_impl;
+use crate::parallel::prelude::*;
 use crate::SynthesisError;
 
+const AGGREGATE_PROOF_MAGIC: [u8; 4] = *b"BPAG";
+const AGGREGATE_PROOF_VERSION: u32 = 2;
+
+/// AggregateProofHeader is the prefix of the v2 encoding of an aggregate proof.
+/// It records the transcript protocol the proof was created with and the hash
+/// of the generic SRS its proving key was specialized from, so a verifier can
+/// reject a proof it would not be able to verify before looking at it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AggregateProofHeader {
+    /// `TranscriptProtocol::PROTOCOL_ID` of the transcript used to prove
+    pub protocol_id: String,
+    /// `GenericSRS::hash` of the SRS used to prove
+    pub srs_hash: [u8; 32],
+}
+
+impl AggregateProofHeader {
+    /// Returns the header of a proof created with the transcript `P` from the
+    /// SRS with the given hash.
+    pub fn new<P: TranscriptProtocol>(srs_hash: &[u8]) -> std::io::Result<Self> {
+        if srs_hash.len() != 32 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "srs hash must be 32 bytes",
+            ));
+        }
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(srs_hash);
+        Ok(AggregateProofHeader {
+            protocol_id: P::PROTOCOL_ID.to_string(),
+            srs_hash: hash,
+        })
+    }
+
+    /// Returns true if the proof was created with the transcript `P` from the
+    /// SRS with the given hash.
+    pub fn matches<P: TranscriptProtocol>(&self, srs_hash: &[u8]) -> bool {
+        self.protocol_id == P::PROTOCOL_ID && self.srs_hash[..] == *srs_hash
+    }
+
+    /// Writes the magic bytes and the version followed by the protocol id,
+    /// prefixed by its length, and the SRS hash.
+    fn write(&self, mut out: impl Write) -> std::io::Result<()> {
+        let id = self.protocol_id.as_bytes();
+        if id.len() > u8::MAX as usize {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "protocol id is too long",
+            ));
+        }
+        out.write_all(&AGGREGATE_PROOF_MAGIC)?;
+        out.write_all(&AGGREGATE_PROOF_VERSION.to_be_bytes())?;
+        out.write_all(&[id.len() as u8])?;
+        out.write_all(id)?;
+        out.write_all(&self.srs_hash)
+    }
+
+    /// Reads the header following the magic bytes and the version.
+    fn read_after_version(mut source: impl Read) -> std::io::Result<Self> {
+        let mut len = [0u8; 1];
+        source.read_exact(&mut len)?;
+        let mut id = vec![0u8; len[0] as usize];
+        source.read_exact(&mut id)?;
+        let protocol_id = String::from_utf8(id).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid protocol id")
+        })?;
+
+        let mut srs_hash = [0u8; 32];
+        source.read_exact(&mut srs_hash)?;
+        Ok(AggregateProofHeader {
+            protocol_id,
+            srs_hash,
+        })
+    }
+}
+
 /// AggregateProof contains all elements to verify n aggregated Groth16 proofs
 /// using inner pairing product arguments. This proof can be created by any
 /// party in possession of valid Groth16 proofs.
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Debug)]
 pub struct AggregateProof<E>
 where
     E: MultiMillerLoop,
@@ -19,32 +101,125 @@ where
 {
     /// commitment to A and B using the pair commitment scheme needed to verify
     /// TIPP relation.
-    #[serde(bound(
-        serialize = "<E as pairing::Engine>::Gt: Serialize",
-        deserialize = "<E as pairing::Engine>::Gt: Deserialize<'de>",
-    ))]
     pub com_ab: commit::Output<E>,
     /// commit to C separate since we use it only in MIPP
-    #[serde(bound(
-        serialize = "<E as pairing::Engine>::Gt: Serialize",
-        deserialize = "<E as pairing::Engine>::Gt: Deserialize<'de>",
-    ))]
     pub com_c: commit::Output<E>,
     /// $A^r * B = Z$ is the left value on the aggregated Groth16 equation
-    #[serde(bound(
-        serialize = "<E as pairing::Engine>::Gt: Serialize",
-        deserialize = "<E as pairing::Engine>::Gt: Deserialize<'de>",
-    ))]
     pub ip_ab: <E as Engine>::Gt,
     /// $C^r$ is used on the right side of the aggregated Groth16 equation
     pub agg_c: E::G1,
-    #[serde(bound(
-        serialize = "TippMippProof<E>: Serialize",
-        deserialize = "TippMippProof<E>: Deserialize<'de>",
-    ))]
     pub tmipp: TippMippProof<E>,
 }
 
+/// Binary formats (e.g. bincode) get the fields of the proof, which is how
+/// aggregate proofs have always been serialized with them. Human readable
+/// formats (e.g. JSON) get the bytes of `AggregateProof::write` as a hex
+/// string, and also accept the fields when deserializing.
+impl<E> Serialize for AggregateProof<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+{
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        if s.is_human_readable() {
+            return serde_impl::serialize(s, |w| self.write(w));
+        }
+        let mut fields = s.serialize_struct("AggregateProof", 5)?;
+        fields.serialize_field("com_ab", &self.com_ab)?;
+        fields.serialize_field("com_c", &self.com_c)?;
+        fields.serialize_field("ip_ab", &self.ip_ab)?;
+        fields.serialize_field("agg_c", &self.agg_c)?;
+        fields.serialize_field("tmipp", &self.tmipp)?;
+        fields.end()
+    }
+}
+
+impl<'de, E> Deserialize<'de> for AggregateProof<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress + Deserialize<'de>,
+    E::G1: Deserialize<'de>,
+    E::G1Affine: Deserialize<'de>,
+    E::G2Affine: Deserialize<'de>,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        struct ProofVisitor<E>(PhantomData<E>);
+
+        impl<'de, E> Visitor<'de> for ProofVisitor<E>
+        where
+            E: MultiMillerLoop,
+            <E as Engine>::Gt: Compress + Deserialize<'de>,
+            E::G1: Deserialize<'de>,
+            E::G1Affine: Deserialize<'de>,
+            E::G2Affine: Deserialize<'de>,
+        {
+            type Value = AggregateProof<E>;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                write!(f, "aggregate proof as a hex string or its fields")
+            }
+
+            fn visit_str<Err: de::Error>(self, v: &str) -> Result<Self::Value, Err> {
+                let bytes = serde_impl::from_hex(v)
+                    .ok_or_else(|| Err::invalid_value(de::Unexpected::Str(v), &self))?;
+                serde_impl::read_all(&bytes, "aggregate proof", |r| AggregateProof::read(r))
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+                AggregateProofFields::deserialize(de::value::MapAccessDeserializer::new(map))?
+                    .into_proof()
+            }
+        }
+
+        if d.is_human_readable() {
+            d.deserialize_any(ProofVisitor(PhantomData))
+        } else {
+            AggregateProofFields::deserialize(d)?.into_proof()
+        }
+    }
+}
+
+/// Fields of an `AggregateProof`, as serialized by binary formats.
+#[derive(Deserialize)]
+#[serde(rename = "AggregateProof")]
+struct AggregateProofFields<E: MultiMillerLoop> {
+    #[serde(bound(deserialize = "<E as pairing::Engine>::Gt: Deserialize<'de>"))]
+    com_ab: commit::Output<E>,
+    #[serde(bound(deserialize = "<E as pairing::Engine>::Gt: Deserialize<'de>"))]
+    com_c: commit::Output<E>,
+    #[serde(bound(deserialize = "<E as pairing::Engine>::Gt: Deserialize<'de>"))]
+    ip_ab: <E as Engine>::Gt,
+    #[serde(bound(deserialize = "E::G1: Deserialize<'de>"))]
+    agg_c: E::G1,
+    #[serde(bound(deserialize = "TippMippProof<E>: Deserialize<'de>"))]
+    tmipp: TippMippProof<E>,
+}
+
+impl<E> AggregateProofFields<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    /// Returns the proof, once it passes `AggregateProof::parsing_check` like
+    /// the proofs read with `AggregateProof::read`.
+    fn into_proof<Err: de::Error>(self) -> Result<AggregateProof<E>, Err> {
+        let proof = AggregateProof {
+            com_ab: self.com_ab,
+            com_c: self.com_c,
+            ip_ab: self.ip_ab,
+            agg_c: self.agg_c,
+            tmipp: self.tmipp,
+        };
+        proof
+            .parsing_check()
+            .map_err(|err| Err::custom(format_args!("invalid aggregate proof: {}", err)))?;
+        Ok(proof)
+    }
+}
+
 impl<E> PartialEq for AggregateProof<E>
 where
     E: MultiMillerLoop,
@@ -65,7 +240,8 @@ where
     <E as Engine>::Gt: Compress,
 {
     /// Performs some high level checks on the length of vectors and others to
-    /// make sure all items in the proofs are consistent with each other.
+    /// make sure all items in the proofs are consistent with each other, and
+    /// checks all group elements are in the prime order subgroups.
     pub fn parsing_check(&self) -> Result<(), SynthesisError> {
         let gipa = &self.tmipp.gipa;
         // 1. Check length of the proofs
@@ -74,13 +250,7 @@ where
                 "invalid nproofs field".to_string(),
             ));
         }
-        // 2. Check if it's a power of two
-        if !gipa.nproofs.is_power_of_two() {
-            return Err(SynthesisError::MalformedProofs(
-                "number of proofs is not a power of two".to_string(),
-            ));
-        }
-        // 3. Check all vectors are of the same length and of the correct length
+        // 2. Check all vectors are of the same length and of the correct length
         let ref_len = gipa.comms_ab.len();
         let good_len = ref_len == (gipa.nproofs as f32).log2().ceil() as usize;
         if !good_len {
@@ -97,8 +267,52 @@ where
                 "proofs vectors don't have the same size".to_string(),
             ));
         }
+        // 3. Check all group elements are in the prime order subgroups
+        if !self.in_subgroups() {
+            return Err(SynthesisError::MalformedProofs(
+                "group element out of the prime order subgroup".to_string(),
+            ));
+        }
         Ok(())
     }
+
+    fn in_subgroups(&self) -> bool {
+        let gipa = &self.tmipp.gipa;
+        let mut gts = vec![self.com_ab.0, self.com_ab.1, self.com_c.0, self.com_c.1];
+        gts.push(self.ip_ab);
+        for (x, y) in gipa.comms_ab.iter().chain(gipa.comms_c.iter()) {
+            gts.extend_from_slice(&[x.0, x.1, y.0, y.1]);
+        }
+        for (x, y) in &gipa.z_ab {
+            gts.extend_from_slice(&[*x, *y]);
+        }
+
+        let mut g1s = vec![self.agg_c];
+        for (x, y) in &gipa.z_c {
+            g1s.extend_from_slice(&[*x, *y]);
+        }
+        let g1_affines = [
+            gipa.final_a,
+            gipa.final_c,
+            gipa.final_wkey.0,
+            gipa.final_wkey.1,
+            self.tmipp.wkey_opening.0,
+            self.tmipp.wkey_opening.1,
+        ];
+        let g2_affines = [
+            gipa.final_b,
+            gipa.final_vkey.0,
+            gipa.final_vkey.1,
+            self.tmipp.vkey_opening.0,
+            self.tmipp.vkey_opening.1,
+        ];
+
+        g1s.par_iter().all(point_in_subgroup)
+            && g1_affines.iter().all(point_in_subgroup)
+            && g2_affines.iter().all(point_in_subgroup)
+            && gts.par_iter().all(gt_in_subgroup)
+    }
+
     /// Writes the agggregated proof into the provided buffer.
     pub fn write(&self, mut out: impl Write) -> std::io::Result<()> {
         // com_ab
@@ -131,7 +345,65 @@ where
         out.len()
     }
 
-    pub fn read(mut source: impl Read) -> std::io::Result<Self> {
+    /// Writes the aggregated proof in the v2 encoding: an
+    /// `AggregateProofHeader` recording the transcript `P` the proof was
+    /// created with and the hash of the generic SRS, followed by the same
+    /// bytes as `write`.
+    pub fn write_v2<P: TranscriptProtocol>(
+        &self,
+        mut out: impl Write,
+        srs_hash: &[u8],
+    ) -> std::io::Result<()> {
+        AggregateProofHeader::new::<P>(srs_hash)?.write(&mut out)?;
+        self.write(&mut out)
+    }
+
+    /// Reads a proof written by either `write` or `write_v2`.
+    pub fn read(source: impl Read) -> std::io::Result<Self> {
+        Self::read_with_header(source).map(|(proof, _)| proof)
+    }
+
+    /// Reads a proof written by either `write` or `write_v2`, returning the
+    /// header of the latter. The v2 encoding is decoded strictly: the proof
+    /// must be the unique encoding of its elements and of a supported size.
+    pub fn read_with_header(
+        mut source: impl Read,
+    ) -> std::io::Result<(Self, Option<AggregateProofHeader>)> {
+        // a v1 proof starts with a Gt element and is longer than the prefix,
+        // it is taken for a v2 one with negligible probability
+        let mut prefix = [0u8; 8];
+        source.read_exact(&mut prefix)?;
+        if prefix[..4] != AGGREGATE_PROOF_MAGIC
+            || prefix[4..] != AGGREGATE_PROOF_VERSION.to_be_bytes()
+        {
+            let proof = Self::read_v1((&prefix[..]).chain(source))?;
+            return Ok((proof, None));
+        }
+
+        let header = AggregateProofHeader::read_after_version(&mut source)?;
+        let mut recorder = RecordingReader {
+            source,
+            bytes: Vec::new(),
+        };
+        let proof = Self::read_v1(&mut recorder)?;
+        if proof.tmipp.gipa.nproofs as usize > srs::MAX_SRS_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "number of proofs is too large",
+            ));
+        }
+        let mut canonical = Vec::with_capacity(recorder.bytes.len());
+        proof.write(&mut canonical)?;
+        if canonical != recorder.bytes {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "non canonical proof encoding",
+            ));
+        }
+        Ok((proof, Some(header)))
+    }
+
+    fn read_v1(mut source: impl Read) -> std::io::Result<Self> {
         let com_ab = (
             <<E as Engine>::Gt as Compress>::read_compressed(&mut source)?,
             <<E as Engine>::Gt as Compress>::read_compressed(&mut source)?,
@@ -157,6 +429,393 @@ where
     }
 }
 
+/// HeterogeneousAggregateProof aggregates Groth16 proofs of different circuits,
+/// each proof being verified with its own verifying key. On top of the regular
+/// aggregate proof, it records the key used by each proof and proves the MIPP
+/// relation of C for the proofs of each key, as each of these parts is paired
+/// with the delta of its key in the aggregated Groth16 equation.
+#[derive(Debug)]
+pub struct HeterogeneousAggregateProof<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    pub proof: AggregateProof<E>,
+    /// index of the verifying key of each proof, before padding
+    pub key_indices: Vec<u32>,
+    /// $C^{r_k}$ for each verifying key $k$, where $r_k$ is $r$ restricted to
+    /// the proofs of that key
+    pub agg_c_keys: Vec<E::G1>,
+    /// MIPP values of each GIPA round, one pair per verifying key
+    pub z_c_keys: Vec<Vec<(E::G1, E::G1)>>,
+}
+
+/// Serializes to the same bytes as `HeterogeneousAggregateProof::write`.
+impl<E> Serialize for HeterogeneousAggregateProof<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serde_impl::serialize(s, |w| self.write(w))
+    }
+}
+
+impl<'de, E> Deserialize<'de> for HeterogeneousAggregateProof<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        serde_impl::deserialize(d, "heterogeneous aggregate proof", |r| Self::read(r))
+    }
+}
+
+impl<E> PartialEq for HeterogeneousAggregateProof<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.proof == other.proof
+            && self.key_indices == other.key_indices
+            && self.agg_c_keys == other.agg_c_keys
+            && self.z_c_keys == other.z_c_keys
+    }
+}
+
+impl<E> HeterogeneousAggregateProof<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    /// Returns the number of verifying keys the proof must be verified with.
+    pub fn num_keys(&self) -> usize {
+        self.agg_c_keys.len()
+    }
+
+    /// Performs the checks of `AggregateProof::parsing_check` as well as
+    /// checking the key indices and the per key values are consistent and in
+    /// the prime order subgroup.
+    pub fn parsing_check(&self) -> Result<(), SynthesisError> {
+        self.proof.parsing_check()?;
+        let gipa = &self.proof.tmipp.gipa;
+        if self.key_indices.len() != gipa.nproofs as usize {
+            return Err(SynthesisError::MalformedProofs(
+                "key indices length does not match nproofs".to_string(),
+            ));
+        }
+        let num_keys = self.num_keys();
+        if num_keys == 0 || num_keys > self.key_indices.len() {
+            return Err(SynthesisError::MalformedProofs(
+                "invalid number of verifying keys".to_string(),
+            ));
+        }
+        if self.key_indices.iter().any(|&k| k as usize >= num_keys) {
+            return Err(SynthesisError::MalformedProofs(
+                "key index out of range".to_string(),
+            ));
+        }
+        let good_len = self.z_c_keys.len() == gipa.z_c.len()
+            && self.z_c_keys.iter().all(|zs| zs.len() == num_keys);
+        if !good_len {
+            return Err(SynthesisError::MalformedProofs(
+                "per key vectors have not indicated size".to_string(),
+            ));
+        }
+        let in_subgroups = self.agg_c_keys.par_iter().all(point_in_subgroup)
+            && self
+                .z_c_keys
+                .par_iter()
+                .flatten()
+                .all(|(x, y)| point_in_subgroup(x) && point_in_subgroup(y));
+        if !in_subgroups {
+            return Err(SynthesisError::MalformedProofs(
+                "group element out of the prime order subgroup".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Writes the aggregated proof into the provided buffer: the regular
+    /// aggregate proof followed by the number of keys, the key index of each
+    /// proof and the per key values.
+    pub fn write(&self, mut out: impl Write) -> std::io::Result<()> {
+        self.proof.write(&mut out)?;
+
+        let nproofs = self.proof.tmipp.gipa.nproofs as usize;
+        assert_eq!(self.key_indices.len(), nproofs);
+        out.write_all(&(self.num_keys() as u32).to_le_bytes()[..])?;
+        for k in &self.key_indices {
+            out.write_all(&k.to_le_bytes()[..])?;
+        }
+
+        // agg_c_keys
+        for x in &self.agg_c_keys {
+            out.write_all(x.to_affine().to_bytes().as_ref())?;
+        }
+
+        assert_eq!(self.z_c_keys.len(), GipaProof::<E>::log_proofs(nproofs));
+        // z_c_keys
+        for zs in &self.z_c_keys {
+            assert_eq!(zs.len(), self.num_keys());
+            for (x, y) in zs {
+                out.write_all(x.to_affine().to_bytes().as_ref())?;
+                out.write_all(y.to_affine().to_bytes().as_ref())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn read(mut source: impl Read) -> std::io::Result<Self> {
+        let proof = AggregateProof::read(&mut source)?;
+        let nproofs = proof.tmipp.gipa.nproofs as usize;
+
+        let mut buffer = 0u32.to_le_bytes();
+        source.read_exact(&mut buffer)?;
+        let num_keys = u32::from_le_bytes(buffer) as usize;
+        if num_keys == 0 || num_keys > nproofs {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "number of verifying keys is invalid",
+            ));
+        }
+
+        let mut key_indices = Vec::with_capacity(nproofs);
+        for _ in 0..nproofs {
+            source.read_exact(&mut buffer)?;
+            let k = u32::from_le_bytes(buffer);
+            if k as usize >= num_keys {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    "key index out of range",
+                ));
+            }
+            key_indices.push(k);
+        }
+
+        let mut agg_c_keys = Vec::with_capacity(num_keys);
+        for _ in 0..num_keys {
+            agg_c_keys.push(read_affine::<E::G1Affine, _>(&mut source)?.to_curve());
+        }
+
+        let log_proofs = GipaProof::<E>::log_proofs(nproofs);
+        let mut z_c_keys = Vec::with_capacity(log_proofs);
+        for _ in 0..log_proofs {
+            let mut zs = Vec::with_capacity(num_keys);
+            for _ in 0..num_keys {
+                zs.push((
+                    read_affine::<E::G1Affine, _>(&mut source)?.to_curve(),
+                    read_affine::<E::G1Affine, _>(&mut source)?.to_curve(),
+                ));
+            }
+            z_c_keys.push(zs);
+        }
+
+        Ok(HeterogeneousAggregateProof {
+            proof,
+            key_indices,
+            agg_c_keys,
+            z_c_keys,
+        })
+    }
+}
+
+/// InputCommitment is a succinct commitment to the public inputs of aggregated
+/// proofs. The i-th public input of all the proofs, padded like the proofs,
+/// are the coefficients of a polynomial $f_i$ which is committed to with the v
+/// commitment key: $(h^{f_i(a)}, h^{f_i(b)})$. It is computed with
+/// `commit_public_inputs` by anyone knowing the public inputs.
+#[derive(Debug)]
+pub struct InputCommitment<E: Engine> {
+    /// number of proofs before padding
+    pub nproofs: u32,
+    /// commitment to each public input
+    pub coms: Vec<(E::G2Affine, E::G2Affine)>,
+}
+
+impl<E: Engine> Clone for InputCommitment<E> {
+    fn clone(&self) -> Self {
+        InputCommitment {
+            nproofs: self.nproofs,
+            coms: self.coms.clone(),
+        }
+    }
+}
+
+impl<E: Engine> PartialEq for InputCommitment<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.nproofs == other.nproofs && self.coms == other.coms
+    }
+}
+
+/// Serializes to the same bytes as `InputCommitment::write`.
+impl<E: Engine> Serialize for InputCommitment<E> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serde_impl::serialize(s, |w| self.write(w))
+    }
+}
+
+impl<'de, E: Engine> Deserialize<'de> for InputCommitment<E> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        serde_impl::deserialize(d, "input commitment", |r| Self::read(r))
+    }
+}
+
+impl<E: Engine> InputCommitment<E> {
+    /// Returns the number of public inputs of each proof.
+    pub fn num_inputs(&self) -> usize {
+        self.coms.len()
+    }
+
+    /// Writes the commitment into the provided buffer: the number of proofs
+    /// and of public inputs followed by the commitment of each input.
+    pub fn write(&self, mut out: impl Write) -> std::io::Result<()> {
+        out.write_all(&self.nproofs.to_le_bytes()[..])?;
+        out.write_all(&(self.num_inputs() as u32).to_le_bytes()[..])?;
+        for (x, y) in &self.coms {
+            out.write_all(x.to_bytes().as_ref())?;
+            out.write_all(y.to_bytes().as_ref())?;
+        }
+        Ok(())
+    }
+
+    pub fn read(mut source: impl Read) -> std::io::Result<Self> {
+        let mut buffer = 0u32.to_le_bytes();
+        source.read_exact(&mut buffer)?;
+        let nproofs = u32::from_le_bytes(buffer);
+        if nproofs < 2 || nproofs as usize > srs::MAX_SRS_SIZE {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "invalid number of proofs",
+            ));
+        }
+
+        source.read_exact(&mut buffer)?;
+        let num_inputs = u32::from_le_bytes(buffer) as usize;
+        // do not trust the length for the allocation
+        let mut coms = Vec::new();
+        for _ in 0..num_inputs {
+            coms.push((read_affine(&mut source)?, read_affine(&mut source)?));
+        }
+
+        Ok(InputCommitment { nproofs, coms })
+    }
+}
+
+/// CommittedAggregateProof is an aggregate proof verified against an
+/// `InputCommitment` instead of the public inputs of every proof. On top of
+/// the regular aggregate proof, it contains the combination of each public
+/// input of the proofs by $r$, i.e. $f_i(r)$, which is all the aggregated
+/// Groth16 equation needs, and a KZG opening proving these values.
+#[derive(Debug)]
+pub struct CommittedAggregateProof<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    pub proof: AggregateProof<E>,
+    /// $f_i(r)$ for each public input $i$
+    pub input_evals: Vec<E::Fr>,
+    /// opening at $r$ of the combination of the $f_i$ by a challenge
+    pub input_opening: KZGOpening<E::G2Affine>,
+}
+
+/// Serializes to the same bytes as `CommittedAggregateProof::write`.
+impl<E> Serialize for CommittedAggregateProof<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serde_impl::serialize(s, |w| self.write(w))
+    }
+}
+
+impl<'de, E> Deserialize<'de> for CommittedAggregateProof<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        serde_impl::deserialize(d, "committed aggregate proof", |r| Self::read(r))
+    }
+}
+
+impl<E> PartialEq for CommittedAggregateProof<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.proof == other.proof
+            && self.input_evals == other.input_evals
+            && self.input_opening == other.input_opening
+    }
+}
+
+impl<E> CommittedAggregateProof<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    /// Performs the checks of `AggregateProof::parsing_check` as well as
+    /// checking there is a value for each public input and the opening is in
+    /// the prime order subgroup.
+    pub fn parsing_check(&self) -> Result<(), SynthesisError> {
+        self.proof.parsing_check()?;
+        if self.input_evals.is_empty() {
+            return Err(SynthesisError::MalformedProofs(
+                "no public input values".to_string(),
+            ));
+        }
+        if !point_in_subgroup(&self.input_opening.0) || !point_in_subgroup(&self.input_opening.1) {
+            return Err(SynthesisError::MalformedProofs(
+                "group element out of the prime order subgroup".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Writes the aggregated proof into the provided buffer: the regular
+    /// aggregate proof followed by the number of public inputs, their values
+    /// and the opening.
+    pub fn write(&self, mut out: impl Write) -> std::io::Result<()> {
+        self.proof.write(&mut out)?;
+
+        out.write_all(&(self.input_evals.len() as u32).to_le_bytes()[..])?;
+        for x in &self.input_evals {
+            out.write_all(x.to_repr().as_ref())?;
+        }
+
+        out.write_all(self.input_opening.0.to_bytes().as_ref())?;
+        out.write_all(self.input_opening.1.to_bytes().as_ref())?;
+        Ok(())
+    }
+
+    pub fn read(mut source: impl Read) -> std::io::Result<Self> {
+        let proof = AggregateProof::read(&mut source)?;
+
+        let mut buffer = 0u32.to_le_bytes();
+        source.read_exact(&mut buffer)?;
+        let num_inputs = u32::from_le_bytes(buffer) as usize;
+        // do not trust the length for the allocation
+        let mut input_evals = Vec::new();
+        for _ in 0..num_inputs {
+            input_evals.push(read_scalar::<E::Fr, _>(&mut source)?);
+        }
+
+        let input_opening = (read_affine(&mut source)?, read_affine(&mut source)?);
+
+        Ok(CommittedAggregateProof {
+            proof,
+            input_evals,
+            input_opening,
+        })
+    }
+}
+
 /// It contains all elements derived in the GIPA loop for both TIPP and MIPP at
 /// the same time.
 #[derive(Serialize, Deserialize, Debug)]
@@ -467,13 +1126,53 @@ fn read_affine<G: PrimeCurveAffine, R: std::io::Read>(mut source: R) -> std::io:
     Ok(affine)
 }
 
+/// Returns true if the point is on the curve and in the prime order subgroup,
+/// i.e. if decoding its encoding gives it back.
+fn point_in_subgroup<G: GroupEncoding + PartialEq>(p: &G) -> bool {
+    let decoded: Option<G> = G::from_bytes(&p.to_bytes()).into();
+    decoded.as_ref() == Some(p)
+}
+
+/// Returns true if the target group element is in the prime order subgroup,
+/// i.e. if decompressing its compressed form gives it back.
+fn gt_in_subgroup<T: Group + Compress>(x: &T) -> bool {
+    // the compression divides by the part of x its conjugate negates, which
+    // is zero only for the identity in the subgroup
+    if *x == -*x {
+        return x.is_identity().into();
+    }
+    let mut bytes = Vec::new();
+    x.write_compressed(&mut bytes).is_ok() && T::read_compressed(&bytes[..]).ok() == Some(*x)
+}
+
+/// Reader keeping a copy of the bytes read from its source.
+struct RecordingReader<R> {
+    source: R,
+    bytes: Vec<u8>,
+}
+
+impl<R: Read> Read for RecordingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.source.read(buf)?;
+        self.bytes.extend_from_slice(&buf[..n]);
+        Ok(n)
+    }
+}
+
+fn read_scalar<F: PrimeField, R: std::io::Read>(mut source: R) -> std::io::Result<F> {
+    let mut repr = F::Repr::default();
+    source.read_exact(repr.as_mut())?;
+    let opt: Option<_> = F::from_repr(repr).into();
+
+    opt.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "invalid scalar"))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use group::Group;
-
-    use blstrs::{Bls12, G1Affine, G1Projective, G2Affine, G2Projective};
+    use crate::groth16::aggregate::{Keccak256Transcript, Sha256Transcript};
+    use blstrs::{Bls12, G1Affine, G1Projective, G2Affine, G2Projective, Gt, Scalar};
 
     fn fake_proof() -> AggregateProof<Bls12> {
         // create pairing, as pairing results can be compressed
@@ -521,6 +1220,140 @@ mod tests {
         let ser_proof = bincode::serialize(&proof).unwrap();
         let des_proof: AggregateProof<Bls12> = bincode::deserialize(&ser_proof).unwrap();
         assert_eq!(des_proof, proof);
+        assert!(bincode::deserialize::<AggregateProof<Bls12>>(&ser_proof[..100]).is_err());
+
+        let json = serde_json::to_string(&proof).unwrap();
+        assert_eq!(json, format!("\"{}\"", serde_impl::to_hex(&buffer)));
+        let des_proof: AggregateProof<Bls12> = serde_json::from_str(&json).unwrap();
+        assert_eq!(des_proof, proof);
+    }
+
+    #[test]
+    fn test_proof_io_v2() {
+        let proof = fake_proof();
+        let srs_hash = [7u8; 32];
+        let mut buffer = Vec::new();
+        proof
+            .write_v2::<Sha256Transcript>(&mut buffer, &srs_hash)
+            .unwrap();
+        let header_len = 4 + 4 + 1 + "snarkpack-v1".len() + 32;
+        assert_eq!(buffer.len(), header_len + 8_212);
+        assert!(proof
+            .write_v2::<Sha256Transcript>(Vec::new(), &srs_hash[1..])
+            .is_err());
+
+        let (out, header) =
+            AggregateProof::<Bls12>::read_with_header(std::io::Cursor::new(&buffer)).unwrap();
+        assert_eq!(proof, out);
+        let header = header.expect("v2 proof has a header");
+        assert_eq!(header.protocol_id, "snarkpack-v1");
+        assert!(header.matches::<Sha256Transcript>(&srs_hash));
+        assert!(!header.matches::<Keccak256Transcript>(&srs_hash));
+        assert!(!header.matches::<Sha256Transcript>(&[8u8; 32]));
+        let out = AggregateProof::<Bls12>::read(std::io::Cursor::new(&buffer)).unwrap();
+        assert_eq!(proof, out);
+
+        // v1 bytes are still accepted, without a header
+        let (out, header) =
+            AggregateProof::<Bls12>::read_with_header(std::io::Cursor::new(&buffer[header_len..]))
+                .unwrap();
+        assert_eq!(proof, out);
+        assert!(header.is_none());
+
+        assert!(
+            AggregateProof::<Bls12>::read(std::io::Cursor::new(&buffer[..buffer.len() - 1]))
+                .is_err()
+        );
+        // the point at infinity with a non zero coordinate for agg_c
+        let mut bad = buffer.clone();
+        let agg_c = header_len + 5 * 288;
+        bad[agg_c] = 0xc0;
+        for b in &mut bad[agg_c + 1..agg_c + 48] {
+            *b = 0;
+        }
+        bad[agg_c + 47] = 1;
+        assert!(AggregateProof::<Bls12>::read(std::io::Cursor::new(&bad)).is_err());
+        // an unknown version is not taken for a v2 proof
+        let mut bad = buffer.clone();
+        bad[7] = 3;
+        assert!(AggregateProof::<Bls12>::read(std::io::Cursor::new(&bad)).is_err());
+    }
+
+    #[test]
+    fn test_heterogeneous_proof_io() {
+        let g = G1Projective::generator();
+        let mut proof = HeterogeneousAggregateProof::<Bls12> {
+            proof: fake_proof(),
+            key_indices: vec![0, 1, 1, 0],
+            agg_c_keys: vec![g, g],
+            z_c_keys: vec![vec![(g, g), (g, g)], vec![(g, g), (g, g)]],
+        };
+        proof.parsing_check().expect("proof should be valid");
+
+        let mut buffer = Vec::new();
+        proof.write(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), 8_212 + 4 + 4 * 4 + 2 * 48 + 2 * 2 * 2 * 48);
+        let out =
+            HeterogeneousAggregateProof::<Bls12>::read(std::io::Cursor::new(&buffer)).unwrap();
+        assert_eq!(proof, out);
+
+        let ser_proof = bincode::serialize(&proof).unwrap();
+        let des_proof: HeterogeneousAggregateProof<Bls12> =
+            bincode::deserialize(&ser_proof).unwrap();
+        assert_eq!(des_proof, proof);
+
+        // a key index past the number of keys is rejected
+        buffer[8_212 + 4] = 2;
+        assert!(HeterogeneousAggregateProof::<Bls12>::read(std::io::Cursor::new(&buffer)).is_err());
+
+        proof.key_indices[1] = 2;
+        proof.parsing_check().expect_err("proof should be invalid");
+        proof.key_indices[1] = 1;
+        proof.z_c_keys[1].pop();
+        proof.parsing_check().expect_err("proof should be invalid");
+    }
+
+    #[test]
+    fn test_committed_proof_io() {
+        let h = G2Projective::generator().to_affine();
+        let com = InputCommitment::<Bls12> {
+            nproofs: 3,
+            coms: vec![(h, h), (h, h)],
+        };
+        let mut buffer = Vec::new();
+        com.write(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), 4 + 4 + 2 * 2 * 96);
+        let out = InputCommitment::<Bls12>::read(std::io::Cursor::new(&buffer)).unwrap();
+        assert_eq!(com, out);
+        let des_com: InputCommitment<Bls12> =
+            bincode::deserialize(&bincode::serialize(&com).unwrap()).unwrap();
+        assert_eq!(des_com, com);
+
+        let mut proof = CommittedAggregateProof::<Bls12> {
+            proof: fake_proof(),
+            input_evals: vec![Scalar::from(2u64), Scalar::from(3u64)],
+            input_opening: (h, h),
+        };
+        proof.parsing_check().expect("proof should be valid");
+
+        let mut buffer = Vec::new();
+        proof.write(&mut buffer).unwrap();
+        assert_eq!(buffer.len(), 8_212 + 4 + 2 * 32 + 2 * 96);
+        let out = CommittedAggregateProof::<Bls12>::read(std::io::Cursor::new(&buffer)).unwrap();
+        assert_eq!(proof, out);
+
+        let ser_proof = bincode::serialize(&proof).unwrap();
+        let des_proof: CommittedAggregateProof<Bls12> = bincode::deserialize(&ser_proof).unwrap();
+        assert_eq!(des_proof, proof);
+
+        // a value out of the field is rejected
+        for b in &mut buffer[8_212 + 4..8_212 + 4 + 32] {
+            *b = 0xff;
+        }
+        assert!(CommittedAggregateProof::<Bls12>::read(std::io::Cursor::new(&buffer)).is_err());
+
+        proof.input_evals.clear();
+        proof.parsing_check().expect_err("proof should be invalid");
     }
 
     #[test]
@@ -535,6 +1368,9 @@ mod tests {
         let oldn = proof.tmipp.gipa.nproofs;
         proof.tmipp.gipa.nproofs = 14;
         proof.parsing_check().expect_err("proof should be invalid");
+        // padded to the same number of rounds
+        proof.tmipp.gipa.nproofs = 3;
+        proof.parsing_check().expect("proof should be valid");
         proof.tmipp.gipa.nproofs = oldn;
 
         proof
@@ -543,5 +1379,58 @@ mod tests {
             .comms_ab
             .append(&mut vec![((a, a), (a, a))]);
         proof.parsing_check().expect_err("Proof should be invalid");
+        proof.tmipp.gipa.comms_ab.pop();
+        proof.parsing_check().expect("proof should be valid");
+
+        // elements of the target group out of the subgroup
+        // serde does not check the subgroup, the first coordinate is the
+        // lowest limb first
+        let with_first_coordinate = |x: Gt, limbs: &[u64]| -> Gt {
+            let mut bytes = bincode::serialize(&x).unwrap();
+            for (i, limb) in limbs.iter().enumerate() {
+                bytes[8 * i..8 * (i + 1)].copy_from_slice(&limb.to_le_bytes());
+            }
+            bincode::deserialize(&bytes).unwrap()
+        };
+        let mut first = [0u8; 8];
+        first.copy_from_slice(&bincode::serialize(&a).unwrap()[..8]);
+        let not_unitary = with_first_coordinate(a, &[u64::from_le_bytes(first) ^ 1]);
+        let p_minus_one = [
+            0xb9fe_ffff_ffff_aaaa,
+            0x1eab_fffe_b153_ffff,
+            0x6730_d2a0_f6b0_f624,
+            0x6477_4b84_f385_12bf,
+            0x4b1b_a7b6_434b_acd7,
+            0x1a01_11ea_397f_e69a,
+        ];
+        let minus_one = with_first_coordinate(Gt::identity(), &p_minus_one);
+        assert_eq!(minus_one, -minus_one);
+        for &x in &[not_unitary, minus_one] {
+            let old = proof.ip_ab;
+            proof.ip_ab = x;
+            proof.parsing_check().expect_err("proof should be invalid");
+            proof.ip_ab = old;
+            proof.tmipp.gipa.z_ab[1].0 = x;
+            proof.parsing_check().expect_err("proof should be invalid");
+            proof.tmipp.gipa.z_ab[1].0 = old;
+        }
+
+        // a point on the curve out of the subgroup
+        let mut bytes = [0u8; 48];
+        bytes[0] = 0x80;
+        let bad_point = (1..)
+            .find_map(|i| {
+                bytes[47] = i;
+                Option::<G1Affine>::from(G1Affine::from_compressed_unchecked(&bytes))
+            })
+            .unwrap();
+        assert!(bool::from(G1Affine::from_compressed(&bytes).is_none()));
+        proof.tmipp.gipa.final_c = bad_point;
+        proof.parsing_check().expect_err("proof should be invalid");
+        proof.tmipp.gipa.final_c = G1Affine::generator();
+        proof.tmipp.gipa.z_c[0].1 = bad_point.to_curve();
+        proof.parsing_check().expect_err("proof should be invalid");
+        proof.tmipp.gipa.z_c[0].1 = G1Projective::generator();
+        proof.parsing_check().expect("proof should be valid");
     }
 }
@@ -1,25 +1,29 @@
 use std::ops::{AddAssign, MulAssign};
 
+use crate::parallel::prelude::*;
 use blstrs::Compress;
 use ff::{Field, PrimeField};
 use group::{prime::PrimeCurveAffine, Curve};
-use rayon::prelude::*;
 use serde::Serialize;
 
 use super::{
     commit,
     commit::{VKey, WKey},
-    compress, inner_product,
+    commitments_challenge, compress, inner_product, input_combination_challenge,
+    pad_to_power_of_two,
     poly::DensePolynomial,
-    structured_scalar_power,
-    transcript::Transcript,
-    AggregateProof, GipaProof, KZGOpening, ProverSRS, TippMippProof,
+    random_linear_combination_challenge, scalars_per_key, structured_scalar_power,
+    transcript::{Challenge, Sha256Transcript, Transcript, TranscriptProtocol},
+    AggregateProof, CommittedAggregateProof, GipaProof, HeterogeneousAggregateProof,
+    InputCommitment, KZGOpening, ProverSRS, TippMippProof,
 };
 use crate::groth16::{multiscalar::*, Proof};
 use crate::SynthesisError;
 use pairing::{Engine, MultiMillerLoop};
 
-/// Aggregate `n` zkSnark proofs, where `n` must be a power of two.
+/// Aggregate `n` zkSnark proofs, where `n` must be at least two. If `n` is not a power of two,
+/// the proofs are padded to the next power of two by repeating the last proof, the SRS must be
+/// specialized for that size. `verify_aggregate_proof` pads the public inputs the same way.
 /// WARNING: transcript_include represents everything that should be included in
 /// the transcript from outside the boundary of this function. This is especially
 /// relevant for ALL public inputs of ALL individual proofs. In the regular case,
@@ -43,15 +47,137 @@ where
     E::G1: Serialize,
     E::G1Affine: Serialize,
     E::G2Affine: Serialize,
+{
+    aggregate_proofs_with_transcript::<E, Sha256Transcript>(srs, transcript_include, proofs)
+}
+
+/// Aggregates proofs like `aggregate_proofs`, deriving the Fiat–Shamir
+/// challenges with the transcript protocol `P`. The aggregate proof must be
+/// verified with `verify_aggregate_proof_with_transcript` and the same
+/// protocol.
+pub fn aggregate_proofs_with_transcript<E, P>(
+    srs: &ProverSRS<E>,
+    transcript_include: &[u8],
+    proofs: &[Proof<E>],
+) -> Result<AggregateProof<E>, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    <E::Fr as PrimeField>::Repr: Send + Sync,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+{
+    let (proof, _, _, _) =
+        aggregate_proofs_with_keys::<E, P>(srs, transcript_include, proofs, &[], None)?;
+    Ok(proof)
+}
+
+/// Aggregate `n` zkSnark proofs of different circuits, where `key_indices[i]`
+/// is the index of the verifying key of `proofs[i]` in the list of keys given
+/// to `verify_aggregate_proof_heterogeneous`. Keys are numbered from zero and
+/// the list of keys is as long as the largest index plus one. The same
+/// requirements and WARNING as `aggregate_proofs` apply.
+pub fn aggregate_proofs_heterogeneous<E>(
+    srs: &ProverSRS<E>,
+    transcript_include: &[u8],
+    proofs: &[Proof<E>],
+    key_indices: &[u32],
+) -> Result<HeterogeneousAggregateProof<E>, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    E::Fr: Serialize,
+    <E::Fr as PrimeField>::Repr: Send + Sync,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+{
+    aggregate_proofs_heterogeneous_with_transcript::<E, Sha256Transcript>(
+        srs,
+        transcript_include,
+        proofs,
+        key_indices,
+    )
+}
+
+/// Aggregates proofs like `aggregate_proofs_heterogeneous`, deriving the
+/// Fiat–Shamir challenges with the transcript protocol `P`. The aggregate
+/// proof must be verified with
+/// `verify_aggregate_proof_heterogeneous_with_transcript` and the same
+/// protocol.
+pub fn aggregate_proofs_heterogeneous_with_transcript<E, P>(
+    srs: &ProverSRS<E>,
+    transcript_include: &[u8],
+    proofs: &[Proof<E>],
+    key_indices: &[u32],
+) -> Result<HeterogeneousAggregateProof<E>, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    <E::Fr as PrimeField>::Repr: Send + Sync,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+{
+    if key_indices.len() != proofs.len() {
+        return Err(SynthesisError::MalformedProofs(
+            "key indices length does not match the number of proofs".to_string(),
+        ));
+    }
+    let (proof, agg_c_keys, z_c_keys, _) =
+        aggregate_proofs_with_keys::<E, P>(srs, transcript_include, proofs, key_indices, None)?;
+    Ok(HeterogeneousAggregateProof {
+        proof,
+        key_indices: key_indices.to_vec(),
+        agg_c_keys,
+        z_c_keys,
+    })
+}
+
+/// Aggregates the proofs and, when `key_indices` is not empty, proves the MIPP
+/// relation of C for the proofs of each verifying key alongside the regular
+/// one. It returns the aggregate proof together with the $C^{r_k}$ values, the
+/// MIPP values of each GIPA round per key and $r$. The commitment to the
+/// public inputs, if any, is bound to the transcript.
+#[allow(clippy::type_complexity)]
+fn aggregate_proofs_with_keys<E, P>(
+    srs: &ProverSRS<E>,
+    transcript_include: &[u8],
+    proofs: &[Proof<E>],
+    key_indices: &[u32],
+    input_commitment: Option<&InputCommitment<E>>,
+) -> Result<
+    (
+        AggregateProof<E>,
+        Vec<E::G1>,
+        Vec<Vec<(E::G1, E::G1)>>,
+        Challenge<E>,
+    ),
+    SynthesisError,
+>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    <E::Fr as PrimeField>::Repr: Send + Sync,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
 {
     if proofs.len() < 2 {
         return Err(SynthesisError::MalformedProofs(
             "aggregating less than 2 proofs is not allowed".to_string(),
         ));
     }
-    if !proofs.len().is_power_of_two() {
-        return Err(SynthesisError::NonPowerOfTwo);
-    }
+    let nproofs = proofs.len();
+    let padded = pad_to_power_of_two(proofs);
+    let proofs = &padded[..];
 
     if !srs.has_correct_len(proofs.len()) {
         return Err(SynthesisError::MalformedSrs);
@@ -75,19 +201,84 @@ where
         let com_c = commit::single_g1::<E>(&srs.vkey, refc)
     };
 
-    let hcom = Transcript::<E>::new("hcom")
-        .write(&com_ab)
-        .write(&com_c)
-        .into_challenge();
+    aggregate_from_commitments::<E, P>(
+        srs,
+        transcript_include,
+        nproofs,
+        &a,
+        &b,
+        &c,
+        com_ab,
+        com_c,
+        None,
+        key_indices,
+        input_commitment,
+    )
+}
+
+/// Commitments of the first GIPA round accumulated as proofs are added to an
+/// `Aggregator`, before $r$ is known. The cross commitments of A and B are
+/// kept in two parts: pairing A with v, and pairing the unscaled w with the
+/// unscaled B. Scaling w by $r^{-i}$ and B by $r^i$ raises the second part to
+/// the power $r^{-n/2}$ on the left and $r^{n/2}$ on the right.
+pub(super) struct FirstRoundCommitments<E: Engine> {
+    /// $e(A_{right}, v_{left})$ and $e(w_{right}, B_{left})$
+    pub tab_l: (commit::Output<E>, commit::Output<E>),
+    /// $e(A_{left}, v_{right})$ and $e(w_{left}, B_{right})$
+    pub tab_r: (commit::Output<E>, commit::Output<E>),
+    /// $e(C_{right}, v_{left})$
+    pub tuc_l: commit::Output<E>,
+    /// $e(C_{left}, v_{right})$
+    pub tuc_r: commit::Output<E>,
+}
+
+/// Aggregates the proofs made of `a`, `b` and `c`, padded to a power of two,
+/// given their commitments, see `aggregate_proofs_with_keys`. The
+/// commitments of the first GIPA round are computed unless given.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+pub(super) fn aggregate_from_commitments<E, P>(
+    srs: &ProverSRS<E>,
+    transcript_include: &[u8],
+    nproofs: usize,
+    a: &[E::G1Affine],
+    b: &[E::G2Affine],
+    c: &[E::G1Affine],
+    com_ab: commit::Output<E>,
+    com_c: commit::Output<E>,
+    first_round: Option<FirstRoundCommitments<E>>,
+    key_indices: &[u32],
+    input_commitment: Option<&InputCommitment<E>>,
+) -> Result<
+    (
+        AggregateProof<E>,
+        Vec<E::G1>,
+        Vec<Vec<(E::G1, E::G1)>>,
+        Challenge<E>,
+    ),
+    SynthesisError,
+>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    <E::Fr as PrimeField>::Repr: Send + Sync,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+{
+    let hcom = commitments_challenge::<E, P>(&com_ab, &com_c, input_commitment);
 
     // Derive a random scalar to perform a linear combination of proofs
-    let r = Transcript::<E>::new("random-r")
-        .write(&hcom)
-        .write(&transcript_include)
-        .into_challenge();
+    let r = random_linear_combination_challenge::<E, P>(
+        &hcom,
+        transcript_include,
+        nproofs,
+        key_indices,
+    );
 
     // 1,r, r^2, r^3, r^4 ...
-    let r_vec: Vec<E::Fr> = structured_scalar_power(proofs.len(), &*r);
+    let r_vec: Vec<E::Fr> = structured_scalar_power(a.len(), &*r);
     // 1,r^-1, r^-2, r^-3
     let r_inv = r_vec
         .par_iter()
@@ -104,37 +295,248 @@ where
     let refr_vec = &r_vec;
     try_par! {
         // compute A * B^r for the verifier
-        let ip_ab = inner_product::pairing::<E>(&refa, &refb_r),
+        let ip_ab = inner_product::pairing::<E>(a, &refb_r),
         // compute C^r for the verifier
-        let agg_c = inner_product::multiexponentiation::<E::G1Affine>(&refc, &refr_vec)
+        let agg_c = inner_product::multiexponentiation::<E::G1Affine>(c, &refr_vec)
     };
 
     // w^{r^{-1}}
     let wkey_r_inv = srs.wkey.scale(&r_inv)?;
 
+    // r restricted to the proofs of each verifying key, and C^{r_k} which
+    // goes in the aggregated Groth16 equation of key k
+    let r_keys = if key_indices.is_empty() {
+        Vec::new()
+    } else {
+        let num_keys = key_indices.iter().max().map_or(0, |&k| k as usize + 1);
+        scalars_per_key(&r_vec, &pad_to_power_of_two(key_indices), num_keys)
+    };
+    let agg_c_keys = r_keys
+        .iter()
+        .map(|r_key| inner_product::multiexponentiation::<E::G1Affine>(c, r_key))
+        .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+    // the pairings of w with B are scaled by r^{-i} r^j where i and j are
+    // half the number of proofs apart
+    let split = a.len() / 2;
+    let first_round = first_round.map(|round| {
+        let scale = |(x, y): commit::Output<E>, (wx, wy): commit::Output<E>, s: &E::Fr| {
+            (x + wx * s, y + wy * s)
+        };
+        (
+            scale(round.tab_l.0, round.tab_l.1, &r_inv[split]),
+            scale(round.tab_r.0, round.tab_r.1, &r_vec[split]),
+            round.tuc_l,
+            round.tuc_r,
+        )
+    });
+
     // we prove tipp and mipp using the same recursive loop
-    let tmipp = prove_tipp_mipp::<E>(
+    let (mut tmipp, z_c_keys) = prove_tipp_mipp::<E, P>(
         &srs,
-        &a,
+        a,
         &b_r,
-        &c,
+        c,
         &wkey_r_inv,
         &r_vec,
         &ip_ab,
         &agg_c,
         &hcom,
+        r_keys,
+        &agg_c_keys,
+        first_round,
     )?;
+    // the proof records the number of proofs before padding
+    tmipp.gipa.nproofs = nproofs as u32;
     debug_assert!({
-        let computed_com_ab = commit::pair::<E>(&srs.vkey, &wkey_r_inv, &a, &b_r).unwrap();
+        let computed_com_ab = commit::pair::<E>(&srs.vkey, &wkey_r_inv, a, &b_r).unwrap();
         com_ab == computed_com_ab
     });
 
-    Ok(AggregateProof {
-        com_ab,
-        com_c,
-        ip_ab,
-        agg_c,
-        tmipp,
+    Ok((
+        AggregateProof {
+            com_ab,
+            com_c,
+            ip_ab,
+            agg_c,
+            tmipp,
+        },
+        agg_c_keys,
+        z_c_keys,
+        r,
+    ))
+}
+
+/// Commits to the public inputs of `n` proofs, where `public_inputs[i]` are
+/// the public inputs of the i-th proof, with the same requirements on `n` and
+/// the SRS as `aggregate_proofs`. Proofs aggregated with
+/// `aggregate_proofs_committed_inputs` can be verified against this
+/// commitment instead of their public inputs.
+pub fn commit_public_inputs<E>(
+    srs: &ProverSRS<E>,
+    public_inputs: &[Vec<E::Fr>],
+) -> Result<InputCommitment<E>, SynthesisError>
+where
+    E: Engine,
+    <E::Fr as PrimeField>::Repr: Send + Sync,
+{
+    if public_inputs.len() < 2 {
+        return Err(SynthesisError::MalformedProofs(
+            "aggregating less than 2 proofs is not allowed".to_string(),
+        ));
+    }
+    let num_inputs = public_inputs[0].len();
+    if num_inputs == 0 || public_inputs.iter().any(|p| p.len() != num_inputs) {
+        return Err(SynthesisError::MalformedProofs(
+            "proofs must have the same non zero number of public inputs".to_string(),
+        ));
+    }
+    let nproofs = public_inputs.len();
+    let public_inputs = pad_to_power_of_two(public_inputs);
+    if srs.n != public_inputs.len() {
+        return Err(SynthesisError::MalformedSrs);
+    }
+
+    // the i-th public input of each proof are the coefficients of f_i
+    let coms = (0..num_inputs)
+        .into_par_iter()
+        .map(|i| {
+            let getter = |j: usize| public_inputs[j][i].to_repr();
+            let commit = |table: &dyn MultiscalarPrecomp<E::G2Affine>| {
+                par_multiscalar::<_, E::G2Affine>(
+                    &ScalarList::Getter(getter, public_inputs.len()),
+                    table,
+                    std::mem::size_of::<<E::Fr as PrimeField>::Repr>() * 8,
+                )
+                .to_affine()
+            };
+            (
+                commit(&srs.h_alpha_powers_table),
+                commit(&srs.h_beta_powers_table),
+            )
+        })
+        .collect();
+
+    Ok(InputCommitment {
+        nproofs: nproofs as u32,
+        coms,
+    })
+}
+
+/// Aggregates `n` zkSnark proofs like `aggregate_proofs` and proves the values
+/// their public inputs contribute to the aggregated Groth16 equation, so that
+/// it can be verified against `input_commitment` alone with
+/// `verify_aggregate_proof_committed_inputs`. `input_commitment` must be the
+/// commitment to `public_inputs` from `commit_public_inputs`, it is bound to
+/// the transcript. The same WARNING as `aggregate_proofs` applies.
+pub fn aggregate_proofs_committed_inputs<E>(
+    srs: &ProverSRS<E>,
+    transcript_include: &[u8],
+    proofs: &[Proof<E>],
+    public_inputs: &[Vec<E::Fr>],
+    input_commitment: &InputCommitment<E>,
+) -> Result<CommittedAggregateProof<E>, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    E::Fr: Serialize,
+    <E::Fr as PrimeField>::Repr: Send + Sync,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+{
+    aggregate_proofs_committed_inputs_with_transcript::<E, Sha256Transcript>(
+        srs,
+        transcript_include,
+        proofs,
+        public_inputs,
+        input_commitment,
+    )
+}
+
+/// Aggregates proofs like `aggregate_proofs_committed_inputs`, deriving the
+/// Fiat–Shamir challenges with the transcript protocol `P`. The aggregate
+/// proof must be verified with
+/// `verify_aggregate_proof_committed_inputs_with_transcript` and the same
+/// protocol.
+pub fn aggregate_proofs_committed_inputs_with_transcript<E, P>(
+    srs: &ProverSRS<E>,
+    transcript_include: &[u8],
+    proofs: &[Proof<E>],
+    public_inputs: &[Vec<E::Fr>],
+    input_commitment: &InputCommitment<E>,
+) -> Result<CommittedAggregateProof<E>, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    <E::Fr as PrimeField>::Repr: Send + Sync,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+{
+    if public_inputs.len() != proofs.len() || input_commitment.nproofs as usize != proofs.len() {
+        return Err(SynthesisError::MalformedProofs(
+            "public inputs length does not match the number of proofs".to_string(),
+        ));
+    }
+    let num_inputs = input_commitment.num_inputs();
+    if public_inputs.iter().any(|p| p.len() != num_inputs) {
+        return Err(SynthesisError::MalformedProofs(
+            "public inputs do not match the input commitment".to_string(),
+        ));
+    }
+    let (proof, _, _, r) = aggregate_proofs_with_keys::<E, P>(
+        srs,
+        transcript_include,
+        proofs,
+        &[],
+        Some(input_commitment),
+    )?;
+
+    // the public inputs are padded like the proofs
+    let public_inputs = pad_to_power_of_two(public_inputs);
+    let r_vec = structured_scalar_power(public_inputs.len(), &*r);
+    // f_i(r) for each public input
+    let input_evals = (0..num_inputs)
+        .into_par_iter()
+        .map(|i| {
+            public_inputs
+                .iter()
+                .zip(r_vec.iter())
+                .fold(E::Fr::zero(), |acc, (p, rj)| acc + p[i] * rj)
+        })
+        .collect::<Vec<_>>();
+
+    // a single opening at r of \sum_i gamma^i f_i
+    let gamma = input_combination_challenge::<E, P>(&r, &input_evals);
+    let gammas = structured_scalar_power(num_inputs, &*gamma);
+    let coeffs = public_inputs
+        .par_iter()
+        .map(|p| {
+            p.iter()
+                .zip(gammas.iter())
+                .fold(E::Fr::zero(), |acc, (x, g)| acc + *x * g)
+        })
+        .collect::<Vec<_>>();
+    let eval = input_evals
+        .iter()
+        .zip(gammas.iter())
+        .fold(E::Fr::zero(), |acc, (x, g)| acc + *x * g);
+    let input_opening = create_kzg_opening(
+        &srs.h_alpha_powers_table,
+        &srs.h_beta_powers_table,
+        srs.n,
+        DensePolynomial::from_coeffs(coeffs),
+        eval,
+        &r,
+    )?;
+
+    Ok(CommittedAggregateProof {
+        proof,
+        input_evals,
+        input_opening,
     })
 }
 
@@ -144,8 +546,12 @@ where
 /// commitment key v is used to commit to A and C recursively in GIPA such that
 /// only one KZG proof is needed for v. In the original paper version, since the
 /// challenges of GIPA would be different, two KZG proofs would be needed.
-#[allow(clippy::too_many_arguments)]
-fn prove_tipp_mipp<E>(
+/// The MIPP relation is also proven for each vector of `r_keys`, with the same
+/// challenges, and the MIPP values of each round are returned per key.
+/// `first_round` are the commitments of the first GIPA round, if computed
+/// beforehand.
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn prove_tipp_mipp<E, P>(
     srs: &ProverSRS<E>,
     a: &[E::G1Affine],
     b: &[E::G2Affine],
@@ -155,9 +561,13 @@ fn prove_tipp_mipp<E>(
     ip_ab: &<E as Engine>::Gt,
     agg_c: &E::G1,
     hcom: &E::Fr,
-) -> Result<TippMippProof<E>, SynthesisError>
+    r_keys: Vec<Vec<E::Fr>>,
+    agg_c_keys: &[E::G1],
+    first_round: Option<GipaRound<E>>,
+) -> Result<(TippMippProof<E>, Vec<Vec<(E::G1, E::G1)>>), SynthesisError>
 where
     E: MultiMillerLoop,
+    P: TranscriptProtocol,
     E::Fr: Serialize,
     <E::Fr as PrimeField>::Repr: Send + Sync,
     <E as Engine>::Gt: Compress + Serialize,
@@ -167,8 +577,20 @@ where
 {
     let r_shift = r_vec[1];
     // Run GIPA
-    let (proof, mut challenges, mut challenges_inv) =
-        gipa_tipp_mipp::<E>(a, b, c, &srs.vkey, &wkey, r_vec, ip_ab, agg_c, hcom)?;
+    let (proof, mut challenges, mut challenges_inv, z_c_keys) = gipa_tipp_mipp::<E, P>(
+        a,
+        b,
+        c,
+        &srs.vkey,
+        &wkey,
+        r_vec,
+        ip_ab,
+        agg_c,
+        hcom,
+        r_keys,
+        agg_c_keys,
+        first_round,
+    )?;
 
     // Prove final commitment keys are wellformed
     // we reverse the transcript so the polynomial in kzg opening is constructed
@@ -179,12 +601,12 @@ where
     let r_inverse = r_shift.invert().unwrap();
 
     // KZG challenge point
-    let z = Transcript::<E>::new("random-z")
-        .write(&challenges[0])
-        .write(&proof.final_vkey.0)
-        .write(&proof.final_vkey.1)
-        .write(&proof.final_wkey.0)
-        .write(&proof.final_wkey.1)
+    let z = Transcript::<E, P>::new("random-z")
+        .write_scalar(&challenges[0])
+        .write_point(&proof.final_vkey.0)
+        .write_point(&proof.final_vkey.1)
+        .write_point(&proof.final_wkey.0)
+        .write_point(&proof.final_wkey.1)
         .into_challenge();
 
     // Complete KZG proofs
@@ -206,23 +628,37 @@ where
         )
     };
 
-    Ok(TippMippProof {
-        gipa: proof,
-        vkey_opening: vkey_opening?,
-        wkey_opening: wkey_opening?,
-    })
+    Ok((
+        TippMippProof {
+            gipa: proof,
+            vkey_opening: vkey_opening?,
+            wkey_opening: wkey_opening?,
+        },
+        z_c_keys,
+    ))
 }
 
+/// Commitments of a GIPA round: `tab_l`, `tab_r`, `tuc_l` and `tuc_r`.
+type GipaRound<E> = (
+    commit::Output<E>,
+    commit::Output<E>,
+    commit::Output<E>,
+    commit::Output<E>,
+);
+
 /// gipa_tipp_mipp peforms the recursion of the GIPA protocol for TIPP and MIPP.
 /// It returns a proof containing all intermdiate committed values, as well as
 /// the challenges generated necessary to do the polynomial commitment proof
-/// later in TIPP.
+/// later in TIPP. The vectors of `r_keys` are folded like `r` and their MIPP
+/// values with C are returned for each round, they are bound to the
+/// challenges. The commitments of the first round are not computed again if
+/// given.
 #[allow(
     clippy::many_single_char_names,
     clippy::type_complexity,
     clippy::too_many_arguments
 )]
-fn gipa_tipp_mipp<E>(
+fn gipa_tipp_mipp<E, P>(
     a: &[E::G1Affine],
     b: &[E::G2Affine],
     c: &[E::G1Affine],
@@ -232,13 +668,26 @@ fn gipa_tipp_mipp<E>(
     ip_ab: &<E as Engine>::Gt,
     agg_c: &E::G1,
     hcom: &E::Fr,
-) -> Result<(GipaProof<E>, Vec<E::Fr>, Vec<E::Fr>), SynthesisError>
+    mut r_keys: Vec<Vec<E::Fr>>,
+    agg_c_keys: &[E::G1],
+    mut first_round: Option<GipaRound<E>>,
+) -> Result<
+    (
+        GipaProof<E>,
+        Vec<E::Fr>,
+        Vec<E::Fr>,
+        Vec<Vec<(E::G1, E::G1)>>,
+    ),
+    SynthesisError,
+>
 where
     E: MultiMillerLoop,
+    P: TranscriptProtocol,
     E::Fr: Serialize,
     <E::Fr as PrimeField>::Repr: Sync,
-    <E as Engine>::Gt: Serialize,
+    <E as Engine>::Gt: Compress + Serialize,
     E::G1: Serialize,
+    E::G1Affine: Serialize,
 {
     // the values of vectors A and B rescaled at each step of the loop
     let (mut m_a, mut m_b) = (a.to_vec(), b.to_vec());
@@ -252,14 +701,15 @@ where
     let mut comms_c = Vec::new();
     let mut z_ab = Vec::new();
     let mut z_c = Vec::new();
+    let mut z_c_keys = Vec::new();
     let mut challenges: Vec<E::Fr> = Vec::new();
     let mut challenges_inv: Vec<E::Fr> = Vec::new();
 
-    let mut c_inv: E::Fr = *Transcript::<E>::new("gipa-0")
-        .write(hcom)
-        .write(ip_ab)
-        .write(agg_c)
-        .write(&r[1])
+    let mut c_inv: E::Fr = *Transcript::<E, P>::new("gipa-0")
+        .write_scalar(hcom)
+        .write_gt(ip_ab)
+        .write_g1(agg_c)
+        .write_scalar(&r[1])
         .into_challenge();
     let mut c = c_inv.invert().unwrap();
 
@@ -290,11 +740,18 @@ where
         let (rb_left, rb_right) = (&b_left, &b_right);
         let (rc_left, rc_right) = (&c_left, &c_right);
         let (rr_left, rr_right) = (&r_left, &r_right);
+        let round = &first_round.take();
         // See section 3.3 for paper version with equivalent names
         try_par! {
             // TIPP part
-            let tab_l = commit::pair::<E>(&rvk_left, &rwk_right, &ra_right, &rb_left),
-            let tab_r = commit::pair::<E>(&rvk_right, &rwk_left, &ra_left, &rb_right),
+            let tab_l = match round {
+                Some((tab_l, _, _, _)) => Ok(*tab_l),
+                None => commit::pair::<E>(&rvk_left, &rwk_right, &ra_right, &rb_left),
+            },
+            let tab_r = match round {
+                Some((_, tab_r, _, _)) => Ok(*tab_r),
+                None => commit::pair::<E>(&rvk_right, &rwk_left, &ra_left, &rb_right),
+            },
             // \prod e(A_right,B_left)
             let zab_l = inner_product::pairing::<E>(&ra_right, &rb_left),
             let zab_r = inner_product::pairing::<E>(&ra_left, &rb_right),
@@ -305,30 +762,48 @@ where
             // Z_r = c[:n'] ^ r[n':]
             let zc_r = inner_product::multiexponentiation::<E::G1Affine>(rc_left, rr_right),
             // u_l = c[n':] * v[:n']
-            let tuc_l = commit::single_g1::<E>(&rvk_left, rc_right),
+            let tuc_l = match round {
+                Some((_, _, tuc_l, _)) => Ok(*tuc_l),
+                None => commit::single_g1::<E>(&rvk_left, rc_right),
+            },
             // u_r = c[:n'] * v[n':]
-            let tuc_r = commit::single_g1::<E>(&rvk_right, rc_left)
+            let tuc_r = match round {
+                Some((_, _, _, tuc_r)) => Ok(*tuc_r),
+                None => commit::single_g1::<E>(&rvk_right, rc_left),
+            }
         };
 
+        // MIPP for the proofs of each verifying key
+        let zc_keys = r_keys
+            .iter()
+            .map(|r_key| {
+                let (rk_left, rk_right) = r_key.split_at(split);
+                Ok((
+                    inner_product::multiexponentiation::<E::G1Affine>(rc_right, rk_left)?,
+                    inner_product::multiexponentiation::<E::G1Affine>(rc_left, rk_right)?,
+                ))
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+
         // Fiat-Shamir challenge
         // combine both TIPP and MIPP transcript
         if i == 0 {
             // already generated c_inv and c outside of the loop
         } else {
-            c_inv = *Transcript::<E>::new(&format!("gipa-{}", i))
-                .write(&c_inv)
-                .write(&zab_l)
-                .write(&zab_r)
-                .write(&zc_l)
-                .write(&zc_r)
-                .write(&tab_l.0)
-                .write(&tab_l.1)
-                .write(&tab_r.0)
-                .write(&tab_r.1)
-                .write(&tuc_l.0)
-                .write(&tuc_l.1)
-                .write(&tuc_r.0)
-                .write(&tuc_r.1)
+            c_inv = *Transcript::<E, P>::new(&format!("gipa-{}", i))
+                .write_scalar(&c_inv)
+                .write_gt(&zab_l)
+                .write_gt(&zab_r)
+                .write_g1(&zc_l)
+                .write_g1(&zc_r)
+                .write_gt(&tab_l.0)
+                .write_gt(&tab_l.1)
+                .write_gt(&tab_r.0)
+                .write_gt(&tab_r.1)
+                .write_gt(&tuc_l.0)
+                .write_gt(&tuc_l.1)
+                .write_gt(&tuc_r.0)
+                .write_gt(&tuc_r.1)
                 .into_challenge();
 
             // Optimization for multiexponentiation to rescale G2 elements with
@@ -336,6 +811,16 @@ where
             // of c_inv
             c = c_inv.invert().unwrap();
         }
+        if !zc_keys.is_empty() {
+            let mut transcript = Transcript::<E, P>::new(&format!("gipa-keys-{}", i))
+                .write_scalar(&c_inv)
+                .write_list(&zc_keys, |t, (l, r)| t.write_g1(l).write_g1(r));
+            if i == 0 {
+                transcript = transcript.write_list(agg_c_keys, |t, c| t.write_g1(c));
+            }
+            c_inv = *transcript.into_challenge();
+            c = c_inv.invert().unwrap();
+        }
 
         // Set up values for next step of recursion
         // A[:n'] + A[n':] ^ x
@@ -355,6 +840,14 @@ where
             });
         let len = r_left.len();
         m_r.resize(len, E::Fr::zero()); // shrink to new size
+        for r_key in r_keys.iter_mut() {
+            let (rk_left, rk_right) = r_key.split_at_mut(split);
+            rk_left
+                .par_iter_mut()
+                .zip(rk_right.par_iter())
+                .for_each(|(r_l, r_r)| r_l.add_assign(*r_r * c_inv));
+            r_key.truncate(split);
+        }
 
         // v_left + v_right^x^-1
         vkey = vk_left.compress(&vk_right, &c_inv)?;
@@ -365,6 +858,7 @@ where
         comms_c.push((tuc_l, tuc_r));
         z_ab.push((zab_l, zab_r));
         z_c.push((zc_l, zc_r));
+        z_c_keys.push(zc_keys);
         challenges.push(c);
         challenges_inv.push(c_inv);
 
@@ -393,6 +887,7 @@ where
         },
         challenges,
         challenges_inv,
+        z_c_keys,
     ))
 }
 
@@ -485,7 +980,8 @@ where
 {
     let neg_kzg_challenge = -*kzg_challenge;
 
-    if poly.coeffs().len() != srs_powers_len {
+    // the leading zero coefficients of the polynomial are trimmed
+    if poly.coeffs().len() > srs_powers_len {
         return Err(SynthesisError::MalformedSrs);
     }
 
@@ -508,7 +1004,7 @@ where
     // on the curve we are on). that's the extra cost of the commitment scheme
     // used which is compatible with Groth16 CRS insteaf of the original paper
     // of Bunz'19
-    Ok(rayon::join(
+    Ok(crate::parallel::join(
         || {
             par_multiscalar::<_, G>(
                 &ScalarList::Getter(getter, srs_powers_len),
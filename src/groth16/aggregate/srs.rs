@@ -1,19 +1,27 @@
-use super::msm;
+use super::{accumulator::PairingChecks, inner_product, msm};
 use crate::groth16::aggregate::commit::*;
-use crate::groth16::multiscalar::{precompute_fixed_window, MultiscalarPrecompOwned, WINDOW_SIZE};
+use crate::groth16::multiscalar::{
+    precompute_fixed_window, MultiscalarPrecomp, MultiscalarPrecompOwned, WINDOW_SIZE,
+};
+use crate::groth16::serde_impl;
+use crate::parallel::prelude::*;
+use crate::SynthesisError;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use digest::Digest;
 use ff::{Field, PrimeField, PrimeFieldBits};
 use group::{
     prime::{PrimeCurve, PrimeCurveAffine},
-    Curve, Group, GroupEncoding,
+    Curve, Group, GroupEncoding, UncompressedEncoding,
 };
+#[cfg(feature = "memmap")]
 use memmap::Mmap;
-use pairing::Engine;
-use rayon::prelude::*;
+use pairing::{Engine, MultiMillerLoop};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use sha2::Sha256;
 use std::convert::TryFrom;
-use std::io::{self, Error, ErrorKind, Read, Write};
+use std::io::{self, Error, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::marker::PhantomData;
+#[cfg(feature = "memmap")]
 use std::mem::size_of;
 use std::ops::MulAssign;
 
@@ -29,7 +37,8 @@ pub const MAX_SRS_SIZE: usize = (2 << 19) + 1;
 /// precomputed tables that drastically increase prover's performance.
 /// This GenericSRS is usually formed from the transcript of two distinct power of taus ceremony
 /// ,in other words from two distinct Groth16 CRS.
-/// See [there](https://github.com/nikkolasg/taupipp) a way on how to generate this GenesisSRS.
+/// `GenericSRS::from_powers_of_tau` assembles it from the transcripts of two such ceremonies, see
+/// [there](https://github.com/nikkolasg/taupipp) for the original tool doing it.
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Debug)]
 pub struct GenericSRS<E: Engine> {
@@ -45,8 +54,7 @@ pub struct GenericSRS<E: Engine> {
 
 /// ProverSRS is the specialized SRS version for the prover for a specific number of proofs to
 /// aggregate. It contains as well the commitment keys for this specific size.
-/// Note the size must be a power of two for the moment - if it is not, padding must be
-/// applied.
+/// The size is always a power of two, `aggregate_proofs` pads the proofs to it.
 #[allow(clippy::upper_case_acronyms)]
 #[derive(Clone, Debug)]
 pub struct ProverSRS<E: Engine> {
@@ -115,6 +123,271 @@ impl<E: Engine> ProverSRS<E> {
     }
 }
 
+/// Precomputed tables of the powers of a `ProverSRS` for `n` proofs: the
+/// first $2n$ powers in G1 and the first $n$ powers in G2. The tables for a
+/// number of proofs are prefixes of the tables for any larger number.
+pub(super) struct PowersTables<E: Engine> {
+    pub n: usize,
+    pub g_alpha: MultiscalarPrecompOwned<E::G1Affine>,
+    pub g_beta: MultiscalarPrecompOwned<E::G1Affine>,
+    pub h_alpha: MultiscalarPrecompOwned<E::G2Affine>,
+    pub h_beta: MultiscalarPrecompOwned<E::G2Affine>,
+}
+
+impl<E: Engine> PowersTables<E> {
+    /// Precomputes the tables for `n` proofs, the SRS must be long enough.
+    pub fn new(srs: &GenericSRS<E>, n: usize) -> Self {
+        // when doing the KZG opening we need _all_ coefficients from 0
+        // to 2n-1 because the polynomial is of degree 2n-1.
+        PowersTables {
+            n,
+            g_alpha: precompute_fixed_window(&srs.g_alpha_powers[..2 * n], WINDOW_SIZE),
+            g_beta: precompute_fixed_window(&srs.g_beta_powers[..2 * n], WINDOW_SIZE),
+            h_alpha: precompute_fixed_window(&srs.h_alpha_powers[..n], WINDOW_SIZE),
+            h_beta: precompute_fixed_window(&srs.h_beta_powers[..n], WINDOW_SIZE),
+        }
+    }
+
+    /// Returns the tables for `n` proofs, at most the number of proofs of
+    /// these tables, sharing them.
+    pub fn prefix(&self, n: usize) -> Self {
+        assert!(n <= self.n, "tables are too short");
+        PowersTables {
+            n,
+            g_alpha: self.g_alpha.prefix(2 * n).unwrap(),
+            g_beta: self.g_beta.prefix(2 * n).unwrap(),
+            h_alpha: self.h_alpha.prefix(n).unwrap(),
+            h_beta: self.h_beta.prefix(n).unwrap(),
+        }
+    }
+
+    /// Returns the tables for `n` proofs, at least the number of proofs of
+    /// these tables, only precomputing the tables of the missing powers.
+    pub fn extend(self, srs: &GenericSRS<E>, n: usize) -> Self {
+        assert!(n >= self.n, "tables are too long");
+        let m = self.n;
+        PowersTables {
+            n,
+            g_alpha: self.g_alpha.extend(&srs.g_alpha_powers[2 * m..2 * n]),
+            g_beta: self.g_beta.extend(&srs.g_beta_powers[2 * m..2 * n]),
+            h_alpha: self.h_alpha.extend(&srs.h_alpha_powers[m..n]),
+            h_beta: self.h_beta.extend(&srs.h_beta_powers[m..n]),
+        }
+    }
+
+    /// Returns the tables of `srs`, sharing them.
+    pub fn of(srs: &ProverSRS<E>) -> Self {
+        PowersTables {
+            n: srs.n,
+            g_alpha: srs.g_alpha_powers_table.clone(),
+            g_beta: srs.g_beta_powers_table.clone(),
+            h_alpha: srs.h_alpha_powers_table.clone(),
+            h_beta: srs.h_beta_powers_table.clone(),
+        }
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.g_alpha.size_in_bytes()
+            + self.g_beta.size_in_bytes()
+            + self.h_alpha.size_in_bytes()
+            + self.h_beta.size_in_bytes()
+    }
+}
+
+/// Magic bytes and version at the start of a specialized SRS written to disk.
+const PROVER_SRS_MAGIC: [u8; 4] = *b"BPPS";
+const VERIFIER_SRS_MAGIC: [u8; 4] = *b"BPVS";
+const SPECIALIZED_SRS_VERSION: u32 = 1;
+
+impl<E> ProverSRS<E>
+where
+    E: Engine,
+    E::G1Affine: UncompressedEncoding,
+    E::G2Affine: UncompressedEncoding,
+{
+    /// Writes the prover SRS, including the precomputed tables, so that it can
+    /// be loaded without calling `GenericSRS::specialize` again. `srs_hash` is
+    /// the `GenericSRS::hash()` of the SRS it was specialized from, it is
+    /// recorded in the file along with a digest of the whole content.
+    pub fn write<W: Write>(&self, writer: W, srs_hash: &[u8]) -> io::Result<()> {
+        let mut writer = DigestWriter::new(writer);
+        write_header(&mut writer, PROVER_SRS_MAGIC, srs_hash)?;
+        writer.write_u32::<BigEndian>(self.n as u32)?;
+        self.g_alpha_powers_table.write(&mut writer)?;
+        self.g_beta_powers_table.write(&mut writer)?;
+        self.h_alpha_powers_table.write(&mut writer)?;
+        self.h_beta_powers_table.write(&mut writer)?;
+        writer.finish()
+    }
+
+    /// Reads a prover SRS written by `write`. It fails if the SRS was not
+    /// specialized from the generic SRS with hash `srs_hash` or if the
+    /// content does not match its digest. The precomputed tables are not
+    /// validated otherwise, the file is trusted.
+    pub fn read<R: Read>(reader: R, srs_hash: &[u8]) -> io::Result<Self> {
+        let mut reader = DigestReader::new(reader);
+        read_header(&mut reader, PROVER_SRS_MAGIC, srs_hash)?;
+        let n = read_specialized_len(&mut reader)?;
+        let g_alpha_powers_table = MultiscalarPrecompOwned::read(&mut reader, false)?;
+        let g_beta_powers_table = MultiscalarPrecompOwned::read(&mut reader, false)?;
+        let h_alpha_powers_table = MultiscalarPrecompOwned::read(&mut reader, false)?;
+        let h_beta_powers_table = MultiscalarPrecompOwned::read(&mut reader, false)?;
+        reader.finish()?;
+
+        Self::from_tables(
+            n,
+            g_alpha_powers_table,
+            g_beta_powers_table,
+            h_alpha_powers_table,
+            h_beta_powers_table,
+        )
+    }
+
+    /// Same as `read`, but reads from a memory map starting at `offset`, the
+    /// precomputed tables are decoded in parallel. The offset is advanced past
+    /// the prover SRS.
+    #[cfg(feature = "memmap")]
+    pub fn read_mmap(mmap: &Mmap, offset: &mut usize, srs_hash: &[u8]) -> io::Result<Self> {
+        let start = *offset;
+        let mut data = mmap
+            .get(start..)
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "prover SRS is truncated"))?;
+        let available = data.len();
+        read_header(&mut data, PROVER_SRS_MAGIC, srs_hash)?;
+        let n = read_specialized_len(&mut data)?;
+        *offset += available - data.len();
+
+        let g_alpha_powers_table = MultiscalarPrecompOwned::read_mmap(mmap, offset, false)?;
+        let g_beta_powers_table = MultiscalarPrecompOwned::read_mmap(mmap, offset, false)?;
+        let h_alpha_powers_table = MultiscalarPrecompOwned::read_mmap(mmap, offset, false)?;
+        let h_beta_powers_table = MultiscalarPrecompOwned::read_mmap(mmap, offset, false)?;
+        check_mmap_digest(mmap, start, offset)?;
+
+        Self::from_tables(
+            n,
+            g_alpha_powers_table,
+            g_beta_powers_table,
+            h_alpha_powers_table,
+            h_beta_powers_table,
+        )
+    }
+
+    /// Rebuilds the commitment keys from the tables: the first entry of the
+    /// table of each point is the point itself.
+    fn from_tables(
+        n: usize,
+        g_alpha_powers_table: MultiscalarPrecompOwned<E::G1Affine>,
+        g_beta_powers_table: MultiscalarPrecompOwned<E::G1Affine>,
+        h_alpha_powers_table: MultiscalarPrecompOwned<E::G2Affine>,
+        h_beta_powers_table: MultiscalarPrecompOwned<E::G2Affine>,
+    ) -> io::Result<Self> {
+        let good_len = g_alpha_powers_table.tables().len() == 2 * n
+            && g_beta_powers_table.tables().len() == 2 * n
+            && h_alpha_powers_table.tables().len() == n
+            && h_beta_powers_table.tables().len() == n;
+        if !good_len {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "precomputed tables do not match the number of proofs",
+            ));
+        }
+        fn bases<G: PrimeCurveAffine>(table: &MultiscalarPrecompOwned<G>, from: usize) -> Vec<G> {
+            table.tables()[from..].iter().map(|t| t[0]).collect()
+        }
+        let vkey = VKey::<E> {
+            a: bases(&h_alpha_powers_table, 0),
+            b: bases(&h_beta_powers_table, 0),
+        };
+        let wkey = WKey::<E> {
+            a: bases(&g_alpha_powers_table, n),
+            b: bases(&g_beta_powers_table, n),
+        };
+
+        Ok(ProverSRS {
+            n,
+            g_alpha_powers_table,
+            h_alpha_powers_table,
+            g_beta_powers_table,
+            h_beta_powers_table,
+            vkey,
+            wkey,
+        })
+    }
+}
+
+impl<E: Engine> VerifierSRS<E> {
+    /// Writes the verifier SRS the same way as `write`, bound to the generic
+    /// SRS it was specialized from, see `ProverSRS::write`.
+    pub fn write_bound<W: Write>(&self, writer: W, srs_hash: &[u8]) -> io::Result<()> {
+        let mut writer = DigestWriter::new(writer);
+        write_header(&mut writer, VERIFIER_SRS_MAGIC, srs_hash)?;
+        self.write(&mut writer)?;
+        writer.finish()
+    }
+
+    /// Reads a verifier SRS written by `write_bound`. It fails if the SRS was
+    /// not specialized from the generic SRS with hash `srs_hash`.
+    pub fn read_bound<R: Read>(reader: R, srs_hash: &[u8]) -> io::Result<Self> {
+        let mut reader = DigestReader::new(reader);
+        read_header(&mut reader, VERIFIER_SRS_MAGIC, srs_hash)?;
+        let vk = Self::read(&mut reader)?;
+        reader.finish()?;
+        Ok(vk)
+    }
+
+    /// Same as `read_bound`, but reads from a memory map starting at `offset`.
+    /// The offset is advanced past the verifier SRS.
+    #[cfg(feature = "memmap")]
+    pub fn read_mmap_bound(mmap: &Mmap, offset: &mut usize, srs_hash: &[u8]) -> io::Result<Self> {
+        let start = *offset;
+        let mut data = mmap
+            .get(start..)
+            .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "verifier SRS is truncated"))?;
+        let available = data.len();
+        read_header(&mut data, VERIFIER_SRS_MAGIC, srs_hash)?;
+        let vk = Self::read(&mut data)?;
+        *offset += available - data.len();
+        check_mmap_digest(mmap, start, offset)?;
+        Ok(vk)
+    }
+
+    /// Writes the number of proofs followed by the compressed points.
+    pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_u32::<BigEndian>(u32::try_from(self.n).map_err(|_| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid number of proofs > u32: {}", self.n),
+            )
+        })?)?;
+        write_point(writer, &self.g.to_affine())?;
+        write_point(writer, &self.h.to_affine())?;
+        write_point(writer, &self.g_alpha.to_affine())?;
+        write_point(writer, &self.g_beta.to_affine())?;
+        write_point(writer, &self.h_alpha.to_affine())?;
+        write_point(writer, &self.h_beta.to_affine())?;
+        Ok(())
+    }
+
+    pub fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
+        let n = reader.read_u32::<BigEndian>()? as usize;
+        if n > MAX_SRS_SIZE {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("invalid number of proofs {}", n),
+            ));
+        }
+        Ok(VerifierSRS {
+            n,
+            g: read_point::<E::G1Affine, _>(reader)?.to_curve(),
+            h: read_point::<E::G2Affine, _>(reader)?.to_curve(),
+            g_alpha: read_point::<E::G1Affine, _>(reader)?.to_curve(),
+            g_beta: read_point::<E::G1Affine, _>(reader)?.to_curve(),
+            h_alpha: read_point::<E::G2Affine, _>(reader)?.to_curve(),
+            h_beta: read_point::<E::G2Affine, _>(reader)?.to_curve(),
+        })
+    }
+}
+
 impl<E> GenericSRS<E>
 where
     E: Engine,
@@ -122,51 +395,64 @@ where
     <E::G2Affine as GroupEncoding>::Repr: Sync,
 {
     /// specializes returns the prover and verifier SRS for a specific number of
-    /// proofs to aggregate. The number of proofs MUST BE a power of two, it
-    /// panics otherwise. The number of proofs must be inferior to half of the
-    /// size of the generic srs otherwise it panics.
-    pub fn specialize(&self, num_proofs: usize) -> (ProverSRS<E>, VerifierSRS<E>) {
-        assert!(num_proofs.is_power_of_two());
+    /// proofs to aggregate. If the number of proofs is not a power of two, the
+    /// SRS is specialized for the next power of two, which is what the proofs
+    /// are padded to. That padded number of proofs must be at most half of
+    /// the size of the generic srs, otherwise `MalformedSrs` is returned.
+    pub fn specialize(
+        &self,
+        num_proofs: usize,
+    ) -> Result<(ProverSRS<E>, VerifierSRS<E>), SynthesisError> {
+        let n = self.specialized_len(num_proofs)?;
+        let tables = PowersTables::new(self, n);
+        Ok((self.prover_srs(tables), self.verifier_srs(n)))
+    }
+
+    /// Returns the padded number of proofs the SRS is specialized for when
+    /// aggregating `num_proofs` proofs, see `specialize`.
+    pub(super) fn specialized_len(&self, num_proofs: usize) -> Result<usize, SynthesisError> {
+        if num_proofs == 0 {
+            return Err(SynthesisError::MalformedSrs);
+        }
+        let num_proofs = num_proofs
+            .checked_next_power_of_two()
+            .ok_or(SynthesisError::MalformedSrs)?;
         let tn = 2 * num_proofs; // size of the CRS we need
-        assert!(self.g_alpha_powers.len() >= tn);
-        assert!(self.h_alpha_powers.len() >= tn);
-        assert!(self.g_beta_powers.len() >= tn);
-        assert!(self.h_beta_powers.len() >= tn);
-        let n = num_proofs;
-        // when doing the KZG opening we need _all_ coefficients from 0
-        // to 2n-1 because the polynomial is of degree 2n-1.
-        let g_low = 0;
-        let g_up = tn;
-        let h_low = 0;
-        let h_up = h_low + n;
-        let g_alpha_powers_table =
-            precompute_fixed_window(&self.g_alpha_powers[g_low..g_up], WINDOW_SIZE);
-        let g_beta_powers_table =
-            precompute_fixed_window(&self.g_beta_powers[g_low..g_up], WINDOW_SIZE);
-        let h_alpha_powers_table =
-            precompute_fixed_window(&self.h_alpha_powers[h_low..h_up], WINDOW_SIZE);
-        let h_beta_powers_table =
-            precompute_fixed_window(&self.h_beta_powers[h_low..h_up], WINDOW_SIZE);
-        let v1 = self.h_alpha_powers[h_low..h_up].to_vec();
-        let v2 = self.h_beta_powers[h_low..h_up].to_vec();
+        let good_len = self.g_alpha_powers.len() >= tn
+            && self.h_alpha_powers.len() >= tn
+            && self.g_beta_powers.len() >= tn
+            && self.h_beta_powers.len() >= tn;
+        if !good_len {
+            return Err(SynthesisError::MalformedSrs);
+        }
+        Ok(num_proofs)
+    }
+
+    /// Returns the prover SRS for the number of proofs of the tables.
+    pub(super) fn prover_srs(&self, tables: PowersTables<E>) -> ProverSRS<E> {
+        let n = tables.n;
+        let v1 = self.h_alpha_powers[..n].to_vec();
+        let v2 = self.h_beta_powers[..n].to_vec();
         let vkey = VKey::<E> { a: v1, b: v2 };
-        assert!(vkey.has_correct_len(n));
         // however, here we only need the "right" shifted bases for the
         // commitment scheme.
-        let w1 = self.g_alpha_powers[n..g_up].to_vec();
-        let w2 = self.g_beta_powers[n..g_up].to_vec();
+        let w1 = self.g_alpha_powers[n..2 * n].to_vec();
+        let w2 = self.g_beta_powers[n..2 * n].to_vec();
         let wkey = WKey::<E> { a: w1, b: w2 };
-        assert!(wkey.has_correct_len(n));
-        let pk = ProverSRS::<E> {
-            g_alpha_powers_table,
-            g_beta_powers_table,
-            h_alpha_powers_table,
-            h_beta_powers_table,
+        ProverSRS::<E> {
+            g_alpha_powers_table: tables.g_alpha,
+            g_beta_powers_table: tables.g_beta,
+            h_alpha_powers_table: tables.h_alpha,
+            h_beta_powers_table: tables.h_beta,
             vkey,
             wkey,
             n,
-        };
-        let vk = VerifierSRS::<E> {
+        }
+    }
+
+    /// Returns the verifier SRS for `n` proofs.
+    pub(super) fn verifier_srs(&self, n: usize) -> VerifierSRS<E> {
+        VerifierSRS::<E> {
             n,
             g: self.g_alpha_powers[0].to_curve(),
             h: self.h_alpha_powers[0].to_curve(),
@@ -174,8 +460,7 @@ where
             g_beta: self.g_beta_powers[1].to_curve(),
             h_alpha: self.h_alpha_powers[1].to_curve(),
             h_beta: self.h_beta_powers[1].to_curve(),
-        };
-        (pk, vk)
+        }
     }
 
     pub fn write<W: Write>(&self, writer: &mut W) -> io::Result<()> {
@@ -193,6 +478,8 @@ where
         Sha256::digest(&v).to_vec()
     }
 
+    /// Reads a generic SRS written by `write`. The points are only checked to
+    /// be valid group elements, `verify` checks that they form a valid SRS.
     pub fn read<R: Read>(reader: &mut R) -> io::Result<Self> {
         let g_alpha_powers = read_vec(reader)?;
         let g_beta_powers = read_vec(reader)?;
@@ -206,6 +493,60 @@ where
         })
     }
 
+    /// Assembles the generic SRS from the transcripts of two powers of tau
+    /// ceremonies, such as the Filecoin and Zcash ones: the alpha powers are
+    /// the tau powers of `reader_a` and the beta powers the ones of
+    /// `reader_b`. Each vector holds the first `max_len` powers, or fewer if
+    /// a ceremony is smaller.
+    ///
+    /// Both challenge and response files of the `powersoftau` accumulator are
+    /// accepted, the format and size of the ceremony are derived from the
+    /// length of the file. The result is the same SRS, written and hashed the
+    /// same way, as the one of the `taupipp` tool.
+    pub fn from_powers_of_tau<A, B>(reader_a: A, reader_b: B, max_len: usize) -> io::Result<Self>
+    where
+        A: Read + Seek,
+        B: Read + Seek,
+        E::G1Affine: UncompressedEncoding,
+        E::G2Affine: UncompressedEncoding,
+        <E::G1Affine as UncompressedEncoding>::Uncompressed: Send,
+        <E::G2Affine as UncompressedEncoding>::Uncompressed: Send,
+    {
+        if max_len == 0 || max_len > MAX_SRS_SIZE {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("invalid SRS length {}", max_len),
+            ));
+        }
+        let mut a = PowersOfTau::<E, _>::new(reader_a)?;
+        let mut b = PowersOfTau::<E, _>::new(reader_b)?;
+        let len = max_len.min(a.num_powers).min(b.num_powers);
+
+        let g_alpha_powers = a.read_g1_powers(len)?;
+        let h_alpha_powers = a.read_g2_powers(len)?;
+        let g_beta_powers = b.read_g1_powers(len)?;
+        let h_beta_powers = b.read_g2_powers(len)?;
+
+        let generators = g_alpha_powers[0] == E::G1Affine::generator()
+            && g_beta_powers[0] == E::G1Affine::generator()
+            && h_alpha_powers[0] == E::G2Affine::generator()
+            && h_beta_powers[0] == E::G2Affine::generator();
+        if !generators {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "powers of tau do not start with the generators",
+            ));
+        }
+
+        Ok(Self {
+            g_alpha_powers,
+            g_beta_powers,
+            h_alpha_powers,
+            h_beta_powers,
+        })
+    }
+
+    #[cfg(feature = "memmap")]
     pub fn read_mmap(reader: &Mmap, max_len: usize) -> io::Result<Self> {
         fn read_length(mmap: &Mmap, offset: &mut usize) -> Result<usize, std::io::Error> {
             let u32_len = size_of::<u32>();
@@ -268,6 +609,217 @@ where
     }
 }
 
+impl<E> GenericSRS<E>
+where
+    E: MultiMillerLoop,
+{
+    /// Checks that the SRS is well formed: all four vectors have the same
+    /// length, start with the same generators and are powers of two distinct
+    /// secrets alpha and beta, and none of their points is the identity.
+    /// The power sequences are checked with randomized pairing checks,
+    /// merged so that a single final exponentiation is needed. It returns an
+    /// error if the vectors have different lengths.
+    pub fn verify<R>(&self, mut rng: R) -> Result<bool, SynthesisError>
+    where
+        R: rand_core::RngCore + Send,
+    {
+        let len = self.g_alpha_powers.len();
+        let good_len = len >= 2
+            && self.h_alpha_powers.len() == len
+            && self.g_beta_powers.len() == len
+            && self.h_beta_powers.len() == len;
+        if !good_len {
+            return Err(SynthesisError::MalformedSrs);
+        }
+
+        let (g, h) = (self.g_alpha_powers[0], self.h_alpha_powers[0]);
+        let well_formed = self.g_beta_powers[0] == g
+            && self.h_beta_powers[0] == h
+            && self.g_alpha_powers[1] != self.g_beta_powers[1]
+            && !has_identity(&self.g_alpha_powers)
+            && !has_identity(&self.g_beta_powers)
+            && !has_identity(&self.h_alpha_powers)
+            && !has_identity(&self.h_beta_powers);
+        if !well_formed {
+            return Ok(false);
+        }
+
+        // For powers P_i of a secret s in one group, with s given by Q_1 = s Q_0
+        // in the other group, a random linear combination of the relations
+        // P_{i+1} = s P_i is checked as e(sum rho_i P_{i+1}, Q_0) = e(sum rho_i P_i, Q_1).
+        let rho = (0..len - 1)
+            .map(|_| E::Fr::random(&mut rng))
+            .collect::<Vec<_>>();
+        let rho = &rho;
+        let combine = |powers: &[E::G1Affine]| -> Result<_, SynthesisError> {
+            Ok((
+                inner_product::multiexponentiation(&powers[..len - 1], rho)?.to_affine(),
+                inner_product::multiexponentiation(&powers[1..], rho)?.to_affine(),
+            ))
+        };
+        let combine_g2 = |powers: &[E::G2Affine]| -> Result<_, SynthesisError> {
+            Ok((
+                inner_product::multiexponentiation(&powers[..len - 1], rho)?.to_affine(),
+                inner_product::multiexponentiation(&powers[1..], rho)?.to_affine(),
+            ))
+        };
+        try_par! {
+            let g_alpha = combine(&self.g_alpha_powers),
+            let g_beta = combine(&self.g_beta_powers),
+            let h_alpha = combine_g2(&self.h_alpha_powers),
+            let h_beta = combine_g2(&self.h_beta_powers)
+        };
+
+        let checks = PairingChecks::<E, R>::new(rng);
+        let one = E::Gt::identity();
+        let neg_g = (-g.to_curve()).to_affine();
+        for &((low, high), base) in &[
+            (g_alpha, &self.h_alpha_powers[1]),
+            (g_beta, &self.h_beta_powers[1]),
+        ] {
+            let neg_high = (-high.to_curve()).to_affine();
+            checks.merge_miller_inputs(&[(&low, base), (&neg_high, &h)], &one);
+        }
+        for &((low, high), base) in &[
+            (h_alpha, &self.g_alpha_powers[1]),
+            (h_beta, &self.g_beta_powers[1]),
+        ] {
+            checks.merge_miller_inputs(&[(base, &low), (&neg_g, &high)], &one);
+        }
+        checks.verify()
+    }
+}
+
+fn has_identity<G: PrimeCurveAffine>(points: &[G]) -> bool {
+    points.par_iter().any(|p| p.is_identity().into())
+}
+
+/// A transcript of the `powersoftau` ceremony. For $N$ powers, the file holds
+/// the 64 bytes hash of the previous transcript followed by the accumulator:
+/// $2N-1$ powers of tau in G1, $N$ in G2, $N$ alpha and beta powers of tau in
+/// G1 and beta in G2. The points of a challenge are uncompressed, the ones of
+/// a response are compressed and followed by the public key of the
+/// contribution: six points in G1 and three in G2, uncompressed.
+struct PowersOfTau<E: Engine, R: Read + Seek> {
+    reader: R,
+    num_powers: usize,
+    compressed: bool,
+    _engine: PhantomData<E>,
+}
+
+impl<E, R> PowersOfTau<E, R>
+where
+    E: Engine,
+    E::G1Affine: UncompressedEncoding,
+    E::G2Affine: UncompressedEncoding,
+    <E::G1Affine as UncompressedEncoding>::Uncompressed: Send,
+    <E::G2Affine as UncompressedEncoding>::Uncompressed: Send,
+    R: Read + Seek,
+{
+    const HASH_LEN: u64 = 64;
+
+    fn new(mut reader: R) -> io::Result<Self> {
+        let g1_len = size_of_repr::<<E::G1Affine as UncompressedEncoding>::Uncompressed>();
+        let g2_len = size_of_repr::<<E::G2Affine as UncompressedEncoding>::Uncompressed>();
+        let g1_compressed_len = size_of_repr::<<E::G1Affine as GroupEncoding>::Repr>();
+        let g2_compressed_len = size_of_repr::<<E::G2Affine as GroupEncoding>::Repr>();
+
+        // the accumulator is `per_power * N + fixed` bytes long
+        let accumulator = |g1: u64, g2: u64| (4 * g1 + g2, g2 - g1);
+        let (challenge_per_power, challenge_fixed) = accumulator(g1_len, g2_len);
+        let (response_per_power, response_fixed) =
+            accumulator(g1_compressed_len, g2_compressed_len);
+        let public_key = 6 * g1_len + 3 * g2_len;
+
+        let file_len = reader.seek(SeekFrom::End(0))?;
+        let num_powers = |fixed: u64, per_power: u64| {
+            let rest = file_len.checked_sub(Self::HASH_LEN + fixed)?;
+            let n = rest / per_power;
+            if rest % per_power == 0 && n.is_power_of_two() {
+                Some(n as usize)
+            } else {
+                None
+            }
+        };
+        let (num_powers, compressed) =
+            if let Some(n) = num_powers(challenge_fixed, challenge_per_power) {
+                (n, false)
+            } else if let Some(n) = num_powers(response_fixed + public_key, response_per_power) {
+                (n, true)
+            } else {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("not a powers of tau transcript: {} bytes", file_len),
+                ));
+            };
+
+        Ok(PowersOfTau {
+            reader,
+            num_powers,
+            compressed,
+            _engine: PhantomData,
+        })
+    }
+
+    /// Reads the first `len` powers of tau in G1.
+    fn read_g1_powers(&mut self, len: usize) -> io::Result<Vec<E::G1Affine>> {
+        self.reader.seek(SeekFrom::Start(Self::HASH_LEN))?;
+        read_powers(&mut self.reader, len, self.compressed)
+    }
+
+    /// Reads the first `len` powers of tau in G2, located after the $2N-1$
+    /// powers in G1.
+    fn read_g2_powers(&mut self, len: usize) -> io::Result<Vec<E::G2Affine>> {
+        let g1_len = if self.compressed {
+            size_of_repr::<<E::G1Affine as GroupEncoding>::Repr>()
+        } else {
+            size_of_repr::<<E::G1Affine as UncompressedEncoding>::Uncompressed>()
+        };
+        let offset = Self::HASH_LEN + (2 * self.num_powers as u64 - 1) * g1_len;
+        self.reader.seek(SeekFrom::Start(offset))?;
+        read_powers(&mut self.reader, len, self.compressed)
+    }
+}
+
+fn size_of_repr<T: Default + AsRef<[u8]>>() -> u64 {
+    T::default().as_ref().len() as u64
+}
+
+/// Reads `len` consecutive points, compressed or not, and decodes them in
+/// parallel.
+fn read_powers<G, R>(r: &mut R, len: usize, compressed: bool) -> io::Result<Vec<G>>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+    G::Repr: Sync,
+    G::Uncompressed: Send,
+    R: Read,
+{
+    fn read_reprs<T: Default + AsMut<[u8]>, R: Read>(r: &mut R, len: usize) -> io::Result<Vec<T>> {
+        (0..len)
+            .map(|_| {
+                let mut el = T::default();
+                r.read_exact(el.as_mut())?;
+                Ok(el)
+            })
+            .collect()
+    }
+    let decoded: Vec<Option<G>> = if compressed {
+        read_reprs::<G::Repr, _>(r, len)?
+            .par_iter()
+            .map(|enc| G::from_bytes(enc).into())
+            .collect()
+    } else {
+        read_reprs::<G::Uncompressed, _>(r, len)?
+            .into_par_iter()
+            .map(|enc| G::from_uncompressed(&enc).into())
+            .collect()
+    };
+    decoded
+        .into_iter()
+        .map(|p| p.ok_or_else(|| Error::new(ErrorKind::InvalidData, "not on curve")))
+        .collect()
+}
+
 pub fn setup_fake_srs<E, R>(rng: &mut R, size: usize) -> GenericSRS<E>
 where
     E: Engine,
@@ -333,6 +885,137 @@ where
     powers_of_g.into_iter().map(|v| v.to_affine()).collect()
 }
 
+/// Wraps a writer to compute the SHA-256 digest of everything written, which
+/// `finish` appends at the end.
+struct DigestWriter<W: Write> {
+    inner: W,
+    hasher: Sha256,
+}
+
+impl<W: Write> DigestWriter<W> {
+    fn new(inner: W) -> Self {
+        DigestWriter {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        let digest = self.hasher.finalize();
+        self.inner.write_all(&digest)?;
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for DigestWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.hasher.update(&buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Wraps a reader to compute the SHA-256 digest of everything read, `finish`
+/// checks it against the digest written by `DigestWriter`.
+struct DigestReader<R: Read> {
+    inner: R,
+    hasher: Sha256,
+}
+
+impl<R: Read> DigestReader<R> {
+    fn new(inner: R) -> Self {
+        DigestReader {
+            inner,
+            hasher: Sha256::new(),
+        }
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        let mut digest = [0u8; 32];
+        self.inner.read_exact(&mut digest)?;
+        if self.hasher.finalize()[..] != digest {
+            return Err(Error::new(ErrorKind::InvalidData, "SRS digest mismatch"));
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DigestReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.hasher.update(&buf[..len]);
+        Ok(len)
+    }
+}
+
+/// Checks the digest following the specialized SRS in `mmap[start..*offset]`
+/// and advances the offset past it.
+#[cfg(feature = "memmap")]
+fn check_mmap_digest(mmap: &Mmap, start: usize, offset: &mut usize) -> io::Result<()> {
+    let digest = mmap
+        .get(*offset..*offset + 32)
+        .ok_or_else(|| Error::new(ErrorKind::UnexpectedEof, "SRS digest is truncated"))?;
+    if Sha256::digest(&mmap[start..*offset])[..] != *digest {
+        return Err(Error::new(ErrorKind::InvalidData, "SRS digest mismatch"));
+    }
+    *offset += 32;
+    Ok(())
+}
+
+fn write_header<W: Write>(w: &mut W, magic: [u8; 4], srs_hash: &[u8]) -> io::Result<()> {
+    if srs_hash.len() != 32 {
+        return Err(Error::new(
+            ErrorKind::InvalidInput,
+            "the generic SRS hash must be 32 bytes",
+        ));
+    }
+    w.write_all(&magic)?;
+    w.write_u32::<BigEndian>(SPECIALIZED_SRS_VERSION)?;
+    w.write_all(srs_hash)
+}
+
+fn read_header<R: Read>(r: &mut R, magic: [u8; 4], srs_hash: &[u8]) -> io::Result<()> {
+    let mut read_magic = [0u8; 4];
+    r.read_exact(&mut read_magic)?;
+    if read_magic != magic {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "not a specialized SRS of the expected kind",
+        ));
+    }
+    let version = r.read_u32::<BigEndian>()?;
+    if version != SPECIALIZED_SRS_VERSION {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("unsupported specialized SRS version {}", version),
+        ));
+    }
+    let mut read_hash = [0u8; 32];
+    r.read_exact(&mut read_hash)?;
+    if read_hash[..] != *srs_hash {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "SRS was specialized from a different generic SRS",
+        ));
+    }
+    Ok(())
+}
+
+fn read_specialized_len<R: Read>(r: &mut R) -> io::Result<usize> {
+    let n = r.read_u32::<BigEndian>()? as usize;
+    if !n.is_power_of_two() || 2 * n > MAX_SRS_SIZE {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            format!("invalid number of proofs {}", n),
+        ));
+    }
+    Ok(n)
+}
+
 fn write_vec<G: PrimeCurveAffine, W: Write>(w: &mut W, v: &[G]) -> io::Result<()> {
     w.write_u32::<BigEndian>(u32::try_from(v.len()).map_err(|_| {
         Error::new(
@@ -351,6 +1034,13 @@ fn write_point<G: PrimeCurveAffine, W: Write>(w: &mut W, p: &G) -> io::Result<()
     Ok(())
 }
 
+fn read_point<G: PrimeCurveAffine, R: Read>(r: &mut R) -> io::Result<G> {
+    let mut repr = G::Repr::default();
+    r.read_exact(repr.as_mut())?;
+    let opt: Option<G> = G::from_bytes(&repr).into();
+    opt.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not on curve"))
+}
+
 fn read_vec<G, R>(r: &mut R) -> io::Result<Vec<G>>
 where
     G: PrimeCurveAffine,
@@ -381,10 +1071,44 @@ where
         .collect::<io::Result<Vec<_>>>()
 }
 
+impl<E> Serialize for GenericSRS<E>
+where
+    E: Engine,
+    <E::G1Affine as GroupEncoding>::Repr: Sync,
+    <E::G2Affine as GroupEncoding>::Repr: Sync,
+{
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serde_impl::serialize(s, |w| self.write(w))
+    }
+}
+
+impl<'de, E> Deserialize<'de> for GenericSRS<E>
+where
+    E: Engine,
+    <E::G1Affine as GroupEncoding>::Repr: Sync,
+    <E::G2Affine as GroupEncoding>::Repr: Sync,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        serde_impl::deserialize(d, "generic SRS", |r| Self::read(r))
+    }
+}
+
+impl<E: Engine> Serialize for VerifierSRS<E> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serde_impl::serialize(s, |w| self.write(w))
+    }
+}
+
+impl<'de, E: Engine> Deserialize<'de> for VerifierSRS<E> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        serde_impl::deserialize(d, "verifier SRS", |r| Self::read(r))
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
-    use blstrs::Bls12;
+    use blstrs::{Bls12, G1Affine, G1Projective, G2Affine, G2Projective, Scalar as Fr};
     use rand_core::SeedableRng;
     use std::io::Cursor;
 
@@ -415,4 +1139,181 @@ mod test {
         GenericSRS::<Bls12>::read(&mut Cursor::new(&new_buffer))
             .expect_err("this should have failed");
     }
+
+    /// Writes a `powersoftau` transcript of 8 powers of `tau`.
+    fn powers_of_tau_transcript(tau: &Fr, compressed: bool) -> Vec<u8> {
+        let n = 8;
+        let g = G1Projective::generator();
+        let h = G2Projective::generator();
+        let tau_g1 = structured_generators_scalar_power(2 * n - 1, &g, tau);
+        let tau_g2 = structured_generators_scalar_power(n, &h, tau);
+
+        let mut out = vec![7u8; 64];
+        let write_g1 = |out: &mut Vec<u8>, p: &G1Affine, compressed: bool| {
+            if compressed {
+                out.extend_from_slice(&p.to_compressed())
+            } else {
+                out.extend_from_slice(&p.to_uncompressed())
+            }
+        };
+        tau_g1
+            .iter()
+            .for_each(|p| write_g1(&mut out, p, compressed));
+        for p in &tau_g2 {
+            if compressed {
+                out.extend_from_slice(&p.to_compressed())
+            } else {
+                out.extend_from_slice(&p.to_uncompressed())
+            }
+        }
+        // alpha and beta powers of tau and beta in G2, not used
+        (0..2 * n).for_each(|_| write_g1(&mut out, &G1Affine::generator(), compressed));
+        if compressed {
+            out.extend_from_slice(&G2Affine::generator().to_compressed());
+            (0..6).for_each(|_| write_g1(&mut out, &G1Affine::generator(), false));
+            (0..3).for_each(|_| out.extend_from_slice(&G2Affine::generator().to_uncompressed()));
+        } else {
+            out.extend_from_slice(&G2Affine::generator().to_uncompressed());
+        }
+        out
+    }
+
+    #[test]
+    fn test_srs_from_powers_of_tau() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let alpha = Fr::random(&mut rng);
+        let beta = Fr::random(&mut rng);
+        let challenge = powers_of_tau_transcript(&alpha, false);
+        let response = powers_of_tau_transcript(&beta, true);
+
+        for &max_len in &[8, 4] {
+            let srs = GenericSRS::<Bls12>::from_powers_of_tau(
+                Cursor::new(&challenge),
+                Cursor::new(&response),
+                max_len,
+            )
+            .unwrap();
+            let g = G1Projective::generator();
+            let h = G2Projective::generator();
+            let expected = GenericSRS::<Bls12> {
+                g_alpha_powers: structured_generators_scalar_power(max_len, &g, &alpha),
+                h_alpha_powers: structured_generators_scalar_power(max_len, &h, &alpha),
+                g_beta_powers: structured_generators_scalar_power(max_len, &g, &beta),
+                h_beta_powers: structured_generators_scalar_power(max_len, &h, &beta),
+            };
+            assert_eq!(srs, expected);
+            assert_eq!(srs.hash(), expected.hash());
+        }
+
+        // larger than the ceremonies
+        let srs = GenericSRS::<Bls12>::from_powers_of_tau(
+            Cursor::new(&response),
+            Cursor::new(&challenge),
+            1 << 10,
+        )
+        .unwrap();
+        assert_eq!(srs.g_alpha_powers.len(), 8);
+        assert_eq!(srs.h_beta_powers.len(), 8);
+
+        // truncated transcript
+        assert!(GenericSRS::<Bls12>::from_powers_of_tau(
+            Cursor::new(&challenge[..challenge.len() - 1]),
+            Cursor::new(&response),
+            8,
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_srs_verify() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let srs = setup_fake_srs::<Bls12, _>(&mut rng, 8);
+        assert!(srs.verify(&mut rng).unwrap());
+
+        let mut bad = srs.clone();
+        bad.g_alpha_powers[5] = (bad.g_alpha_powers[5].to_curve().double()).to_affine();
+        assert!(!bad.verify(&mut rng).unwrap());
+
+        let mut bad = srs.clone();
+        bad.h_beta_powers.swap(3, 4);
+        assert!(!bad.verify(&mut rng).unwrap());
+
+        let mut bad = srs.clone();
+        bad.g_beta_powers = bad.g_alpha_powers.clone();
+        bad.h_beta_powers = bad.h_alpha_powers.clone();
+        assert!(!bad.verify(&mut rng).unwrap());
+
+        let mut bad = srs.clone();
+        bad.h_alpha_powers[7] = G2Affine::identity();
+        assert!(!bad.verify(&mut rng).unwrap());
+
+        // h powers of another secret
+        let other = setup_fake_srs::<Bls12, _>(&mut rng, 8);
+        let mut bad = srs.clone();
+        bad.h_alpha_powers = other.h_alpha_powers.clone();
+        assert!(!bad.verify(&mut rng).unwrap());
+
+        let mut bad = srs.clone();
+        bad.g_alpha_powers.pop();
+        assert!(bad.verify(&mut rng).is_err());
+        assert!(bad.specialize(8).is_err());
+
+        assert!(srs.specialize(8).is_ok());
+        assert!(srs.specialize(9).is_err());
+        assert!(srs.specialize(0).is_err());
+    }
+
+    #[test]
+    fn test_specialized_srs_io() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let srs = setup_fake_srs::<Bls12, _>(&mut rng, 8);
+        let other_hash = setup_fake_srs::<Bls12, _>(&mut rng, 8).hash();
+        let hash = srs.hash();
+        let (pk, vk) = srs.specialize(4).unwrap();
+
+        let mut pk_bytes = Vec::new();
+        pk.write(&mut pk_bytes, &hash).unwrap();
+        let read_pk = ProverSRS::<Bls12>::read(&pk_bytes[..], &hash).unwrap();
+        assert_eq!(read_pk.n, pk.n);
+        assert_eq!(read_pk.vkey.a, pk.vkey.a);
+        assert_eq!(read_pk.vkey.b, pk.vkey.b);
+        assert_eq!(read_pk.wkey.a, pk.wkey.a);
+        assert_eq!(read_pk.wkey.b, pk.wkey.b);
+        assert!(read_pk.g_alpha_powers_table == pk.g_alpha_powers_table);
+        assert!(read_pk.g_beta_powers_table == pk.g_beta_powers_table);
+        assert!(read_pk.h_alpha_powers_table == pk.h_alpha_powers_table);
+        assert!(read_pk.h_beta_powers_table == pk.h_beta_powers_table);
+
+        let mut vk_bytes = Vec::new();
+        vk.write_bound(&mut vk_bytes, &hash).unwrap();
+        assert_eq!(
+            VerifierSRS::<Bls12>::read_bound(&vk_bytes[..], &hash).unwrap(),
+            vk
+        );
+
+        // bound to the generic SRS and to the content
+        assert!(ProverSRS::<Bls12>::read(&pk_bytes[..], &other_hash).is_err());
+        assert!(VerifierSRS::<Bls12>::read_bound(&vk_bytes[..], &other_hash).is_err());
+        assert!(VerifierSRS::<Bls12>::read_bound(&pk_bytes[..], &hash).is_err());
+        let last = pk_bytes.len() - 40;
+        pk_bytes[last] ^= 1;
+        assert!(ProverSRS::<Bls12>::read(&pk_bytes[..], &hash).is_err());
+        pk_bytes[last] ^= 1;
+
+        #[cfg(feature = "memmap")]
+        {
+            let mut file = tempfile::tempfile().unwrap();
+            file.write_all(&pk_bytes).unwrap();
+            file.write_all(&vk_bytes).unwrap();
+            let mmap = unsafe { Mmap::map(&file).unwrap() };
+            let mut offset = 0;
+            let mmap_pk = ProverSRS::<Bls12>::read_mmap(&mmap, &mut offset, &hash).unwrap();
+            assert_eq!(offset, pk_bytes.len());
+            assert!(mmap_pk.g_alpha_powers_table == pk.g_alpha_powers_table);
+            assert_eq!(mmap_pk.wkey.b, pk.wkey.b);
+            let mmap_vk = VerifierSRS::<Bls12>::read_mmap_bound(&mmap, &mut offset, &hash).unwrap();
+            assert_eq!(offset, mmap.len());
+            assert_eq!(mmap_vk, vk);
+        }
+    }
 }
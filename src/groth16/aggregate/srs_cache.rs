@@ -0,0 +1,400 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::mem::size_of;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use group::GroupEncoding;
+use pairing::Engine;
+
+use super::srs::{GenericSRS, PowersTables, ProverSRS, VerifierSRS};
+use crate::SynthesisError;
+
+/// Prover and verifier SRS handed out by the cache.
+pub type SharedSpecializedSrs<E> = (Arc<ProverSRS<E>>, Arc<VerifierSRS<E>>);
+
+/// SrsCache specializes a `GenericSRS`, for instance one loaded with
+/// `GenericSRS::read_mmap`, on demand for each number of proofs to aggregate
+/// and keeps the specialized SRS around for the next aggregations of the same
+/// size.
+///
+/// The precomputed tables of a `ProverSRS` are prefixes of the tables for any
+/// larger number of proofs. A size smaller than a cached one shares the
+/// tables of the cached size, unless they alone would go over the memory
+/// limit, in which case it copies its prefix of them. A larger size copies
+/// the tables of the largest smaller size cached and only precomputes the
+/// tables of the missing powers. The cached SRS are never rebuilt, so the
+/// tables of a size specialized before a larger one are not shared with it.
+///
+/// Once the memory used by the cache, see `memory_used`, goes over its limit,
+/// the least recently used sizes are evicted. The size just requested is
+/// never evicted, so a single size can go over the limit. The SRS handed out
+/// stay valid after they are evicted, but the memory they use is only freed
+/// once they are all dropped.
+///
+/// The cache can be shared between threads. Cached sizes are returned right
+/// away while other sizes are specialized, and a size is only specialized by
+/// one thread at a time, the others wait for its result.
+pub struct SrsCache<E: Engine> {
+    srs: GenericSRS<E>,
+    memory_limit: usize,
+    state: Mutex<CacheState<E>>,
+}
+
+struct CacheState<E: Engine> {
+    entries: BTreeMap<usize, CacheEntry<E>>,
+    /// incremented on each access, to find the least recently used entry
+    clock: u64,
+}
+
+struct CacheEntry<E: Engine> {
+    /// `None` until the size is specialized
+    keys: Option<CachedSrs<E>>,
+    /// held while the size is specialized
+    pending: Arc<Mutex<()>>,
+    last_used: u64,
+}
+
+struct CachedSrs<E: Engine> {
+    pk: Arc<ProverSRS<E>>,
+    vk: Arc<VerifierSRS<E>>,
+    /// identifies the tables the prover SRS shares with other sizes
+    tables_id: u64,
+    /// size of the shared tables, which can be longer than the prover SRS
+    tables_size: usize,
+}
+
+impl<E> SrsCache<E>
+where
+    E: Engine,
+    <E::G1Affine as GroupEncoding>::Repr: Sync,
+    <E::G2Affine as GroupEncoding>::Repr: Sync,
+{
+    /// Returns an empty cache of the specializations of `srs`, using at most
+    /// around `memory_limit` bytes.
+    pub fn new(srs: GenericSRS<E>, memory_limit: usize) -> Self {
+        SrsCache {
+            srs,
+            memory_limit,
+            state: Mutex::new(CacheState {
+                entries: BTreeMap::new(),
+                clock: 0,
+            }),
+        }
+    }
+
+    /// Returns the generic SRS the cache specializes.
+    pub fn srs(&self) -> &GenericSRS<E> {
+        &self.srs
+    }
+
+    /// Returns the prover and verifier SRS for `num_proofs` proofs, like
+    /// `GenericSRS::specialize` does, specializing the generic SRS only if
+    /// the size is not cached.
+    pub fn get(&self, num_proofs: usize) -> Result<SharedSpecializedSrs<E>, SynthesisError> {
+        let n = self.srs.specialized_len(num_proofs)?;
+        let pending = {
+            let mut state = self.state()?;
+            let clock = state.tick();
+            let entry = state.entries.entry(n).or_insert_with(|| CacheEntry {
+                keys: None,
+                pending: Arc::new(Mutex::new(())),
+                last_used: clock,
+            });
+            entry.last_used = clock;
+            if let Some(keys) = &entry.keys {
+                return Ok((keys.pk.clone(), keys.vk.clone()));
+            }
+            entry.pending.clone()
+        };
+
+        // a panic while specializing leaves nothing to recover
+        let _pending = pending.lock().unwrap_or_else(PoisonError::into_inner);
+        let (cached, clock) = {
+            let mut state = self.state()?;
+            let clock = state.tick();
+            if let Some(keys) = state.entries.get(&n).and_then(|entry| entry.keys.as_ref()) {
+                return Ok((keys.pk.clone(), keys.vk.clone()));
+            }
+            // the smallest larger size to share the tables of, or else the
+            // largest smaller size to copy the tables of
+            let cached = state
+                .entries
+                .range(n..)
+                .chain(state.entries.range(..n).rev())
+                .find_map(|(_, entry)| entry.keys.as_ref())
+                .map(|keys| (PowersTables::of(&keys.pk), keys.tables_id, keys.tables_size));
+            (cached, clock)
+        };
+
+        let (tables, tables_id, tables_size) = match cached {
+            Some((tables, id, size))
+                if tables.n >= n && size + keys_size::<E>(n) <= self.memory_limit =>
+            {
+                (tables.prefix(n), id, size)
+            }
+            Some((tables, _, _)) => {
+                // the tables are shared, so they are copied up to n
+                let tables = tables.prefix(std::cmp::min(tables.n, n));
+                let tables = tables.extend(&self.srs, n);
+                let size = tables.size_in_bytes();
+                (tables, clock, size)
+            }
+            None => {
+                let tables = PowersTables::new(&self.srs, n);
+                let size = tables.size_in_bytes();
+                (tables, clock, size)
+            }
+        };
+        let keys = CachedSrs {
+            pk: Arc::new(self.srs.prover_srs(tables)),
+            vk: Arc::new(self.srs.verifier_srs(n)),
+            tables_id,
+            tables_size,
+        };
+        let shared = (keys.pk.clone(), keys.vk.clone());
+
+        let mut state = self.state()?;
+        let clock = state.tick();
+        let entry = state.entries.entry(n).or_insert_with(|| CacheEntry {
+            keys: None,
+            pending: pending.clone(),
+            last_used: clock,
+        });
+        entry.keys = Some(keys);
+        entry.last_used = clock;
+        self.evict(&mut state, n);
+        Ok(shared)
+    }
+
+    /// Returns the number of bytes used by the cached prover SRS, counting
+    /// the shared tables once.
+    pub fn memory_used(&self) -> Result<usize, SynthesisError> {
+        Ok(self.state()?.memory_used())
+    }
+
+    /// Returns the sizes the cache holds a specialization for.
+    pub fn cached_sizes(&self) -> Result<Vec<usize>, SynthesisError> {
+        Ok(self
+            .state()?
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.keys.is_some())
+            .map(|(&m, _)| m)
+            .collect())
+    }
+
+    fn state(&self) -> Result<MutexGuard<'_, CacheState<E>>, SynthesisError> {
+        self.state.lock().map_err(|_| {
+            SynthesisError::IoError(io::Error::new(
+                io::ErrorKind::Other,
+                "srs cache lock poisoned",
+            ))
+        })
+    }
+
+    /// Evicts the least recently used sizes other than `n` until the cache
+    /// is under its memory limit. Sizes being specialized are kept.
+    fn evict(&self, state: &mut CacheState<E>, n: usize) {
+        while state.memory_used() > self.memory_limit {
+            let lru = state
+                .entries
+                .iter()
+                .filter(|(&m, entry)| m != n && entry.keys.is_some())
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(&m, _)| m);
+            match lru {
+                Some(m) => {
+                    state.entries.remove(&m);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+impl<E: Engine> CacheState<E> {
+    fn tick(&mut self) -> u64 {
+        self.clock += 1;
+        self.clock
+    }
+
+    fn memory_used(&self) -> usize {
+        let mut tables = BTreeMap::new();
+        let mut keys = 0;
+        for cached in self
+            .entries
+            .values()
+            .filter_map(|entry| entry.keys.as_ref())
+        {
+            tables.insert(cached.tables_id, cached.tables_size);
+            keys += keys_size::<E>(cached.pk.n);
+        }
+        tables.values().sum::<usize>() + keys
+    }
+}
+
+/// Returns the number of bytes of the commitment keys of a prover SRS for `n`
+/// proofs, which each prover SRS has its own of: n points in G1 and G2 for
+/// each of the two keys.
+fn keys_size<E: Engine>(n: usize) -> usize {
+    2 * n * (size_of::<E::G1Affine>() + size_of::<E::G2Affine>())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::groth16::aggregate::setup_fake_srs;
+    use crate::parallel::prelude::*;
+    use blstrs::Bls12;
+    use rand_core::SeedableRng;
+
+    fn prover_srs_bytes(pk: &ProverSRS<Bls12>) -> Vec<u8> {
+        let mut buffer = Vec::new();
+        pk.write(&mut buffer, &[0u8; 32]).unwrap();
+        buffer
+    }
+
+    #[test]
+    fn test_srs_cache() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let srs = setup_fake_srs::<Bls12, _>(&mut rng, 32);
+        let cache = SrsCache::new(srs.clone(), usize::MAX);
+
+        // the tables for 8 proofs are shared with the ones for 4 and 2
+        let (pk8, vk) = cache.get(5).unwrap();
+        let (expected_pk, expected_vk) = srs.specialize(5).unwrap();
+        assert_eq!(pk8.n, 8);
+        assert_eq!(prover_srs_bytes(&pk8), prover_srs_bytes(&expected_pk));
+        assert!(*vk == expected_vk);
+        let used = cache.memory_used().unwrap();
+        let (pk, _) = cache.get(2).unwrap();
+        assert_eq!(
+            prover_srs_bytes(&pk),
+            prover_srs_bytes(&srs.specialize(2).unwrap().0)
+        );
+        assert_eq!(cache.memory_used().unwrap(), used + keys_size::<Bls12>(2));
+        assert!(Arc::ptr_eq(&cache.get(2).unwrap().0, &pk));
+
+        // growing copies the tables and leaves the cached sizes as they are
+        let used = cache.memory_used().unwrap();
+        let (pk, _) = cache.get(16).unwrap();
+        assert_eq!(
+            prover_srs_bytes(&pk),
+            prover_srs_bytes(&srs.specialize(16).unwrap().0)
+        );
+        assert_eq!(
+            cache.memory_used().unwrap(),
+            used + PowersTables::new(&srs, 16).size_in_bytes() + keys_size::<Bls12>(16)
+        );
+        assert!(Arc::ptr_eq(&cache.get(8).unwrap().0, &pk8));
+        assert_eq!(cache.cached_sizes().unwrap(), vec![2, 8, 16]);
+
+        cache.get(33).expect_err("the srs is too short");
+    }
+
+    #[test]
+    fn test_srs_cache_eviction() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(1u64);
+        let srs = setup_fake_srs::<Bls12, _>(&mut rng, 32);
+        let limit = {
+            let cache = SrsCache::new(srs.clone(), usize::MAX);
+            cache.get(4).unwrap();
+            cache.get(2).unwrap();
+            cache.memory_used().unwrap()
+        };
+
+        // 16 proofs is over the limit but the size requested is kept
+        let cache = SrsCache::new(srs.clone(), limit);
+        cache.get(4).unwrap();
+        cache.get(2).unwrap();
+        cache.get(16).unwrap();
+        assert_eq!(cache.cached_sizes().unwrap(), vec![16]);
+        assert!(cache.memory_used().unwrap() > limit);
+
+        // the tables of 16 proofs are over the limit, so 4 proofs copies its
+        // prefix of them instead of keeping them alive
+        let (pk, _) = cache.get(4).unwrap();
+        assert_eq!(cache.cached_sizes().unwrap(), vec![4]);
+        assert_eq!(
+            prover_srs_bytes(&pk),
+            prover_srs_bytes(&srs.specialize(4).unwrap().0)
+        );
+        let (pk, _) = cache.get(2).unwrap();
+        assert_eq!(cache.cached_sizes().unwrap(), vec![2, 4]);
+        assert_eq!(cache.memory_used().unwrap(), limit);
+        assert_eq!(
+            prover_srs_bytes(&pk),
+            prover_srs_bytes(&srs.specialize(2).unwrap().0)
+        );
+    }
+
+    #[test]
+    fn test_srs_cache_threads() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(2u64);
+        let srs = setup_fake_srs::<Bls12, _>(&mut rng, 32);
+        let cache = SrsCache::new(srs.clone(), usize::MAX);
+        let sizes = vec![2, 16, 4, 8, 3, 16, 5, 2];
+        let keys = sizes
+            .par_iter()
+            .map(|&n| cache.get(n).unwrap())
+            .collect::<Vec<_>>();
+        for (&n, (pk, vk)) in sizes.iter().zip(keys.iter()) {
+            let (expected_pk, expected_vk) = srs.specialize(n).unwrap();
+            assert_eq!(prover_srs_bytes(pk), prover_srs_bytes(&expected_pk));
+            assert!(**vk == expected_vk);
+        }
+        assert_eq!(cache.cached_sizes().unwrap(), vec![2, 4, 8, 16]);
+    }
+
+    #[test]
+    fn test_srs_cache_pending() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(3u64);
+        let srs = setup_fake_srs::<Bls12, _>(&mut rng, 32);
+        let cache = Arc::new(SrsCache::new(srs.clone(), usize::MAX));
+        let (pk, _) = cache.get(2).unwrap();
+
+        // 16 proofs are being specialized, which does not hold up cached sizes
+        let pending = Arc::new(Mutex::new(()));
+        let guard = pending.lock().unwrap();
+        cache.state.lock().unwrap().entries.insert(
+            16,
+            CacheEntry {
+                keys: None,
+                pending: pending.clone(),
+                last_used: 0,
+            },
+        );
+        let waiting = {
+            let cache = cache.clone();
+            std::thread::spawn(move || cache.get(16).unwrap())
+        };
+        assert!(Arc::ptr_eq(&cache.get(2).unwrap().0, &pk));
+        assert_eq!(cache.cached_sizes().unwrap(), vec![2]);
+        drop(guard);
+        let (pk, _) = waiting.join().unwrap();
+        assert_eq!(
+            prover_srs_bytes(&pk),
+            prover_srs_bytes(&srs.specialize(16).unwrap().0)
+        );
+        assert_eq!(cache.cached_sizes().unwrap(), vec![2, 16]);
+    }
+
+    #[test]
+    fn test_srs_cache_poisoned() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(4u64);
+        let srs = setup_fake_srs::<Bls12, _>(&mut rng, 32);
+        let cache = SrsCache::new(srs, usize::MAX);
+        cache.get(2).unwrap();
+
+        std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _state = cache.state.lock().unwrap();
+            panic!("panic while holding the cache lock");
+        }))
+        .expect_err("the closure panics");
+        cache.get(2).expect_err("the cache lock is poisoned");
+        cache.memory_used().expect_err("the cache lock is poisoned");
+        cache
+            .cached_sizes()
+            .expect_err("the cache lock is poisoned");
+    }
+}
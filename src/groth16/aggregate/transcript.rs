@@ -1,17 +1,241 @@
 use std::marker::PhantomData;
 
+use blstrs::Compress;
 use ff::{Field, PrimeField};
+use group::{prime::PrimeCurveAffine, Curve};
 use serde::Serialize;
 use sha2::{Digest, Sha256};
 
 use pairing::Engine;
 
+use super::keccak::Keccak256;
+
+/// Protocol id of the default transcript, it prefixes all its transcripts.
 const PREFIX: &str = "snarkpack-v1";
 
-#[derive(Debug)]
-pub struct Transcript<E: Engine> {
+/// TranscriptProtocol defines how the Fiat–Shamir transcripts of the
+/// aggregation encode the elements sent by the prover and hash them into
+/// challenges. Prover and verifier must use the same protocol. Its id is
+/// bound into every transcript so that the challenges of two protocols are
+/// unrelated.
+pub trait TranscriptProtocol: Sized + Send + Sync {
+    /// Identifies the protocol.
+    const PROTOCOL_ID: &'static str;
+
+    /// Starts a transcript for the step of the protocol named
+    /// `application_tag`.
+    fn new(application_tag: &str) -> Self;
+
+    fn append_scalar<F: PrimeField + Serialize>(&mut self, x: &F);
+
+    fn append_point<G: PrimeCurveAffine + Serialize>(&mut self, p: &G);
+
+    fn append_gt<T: Compress + Copy + Serialize>(&mut self, x: &T);
+
+    fn append_u32(&mut self, x: u32);
+
+    fn append_u64(&mut self, x: u64);
+
+    /// Appends the number of elements of the list appended next.
+    fn append_len(&mut self, len: usize);
+
+    fn append_bytes(&mut self, bytes: &[u8]);
+
+    /// Appends `counter` and returns the digest of the whole transcript.
+    fn squeeze(&mut self, counter: usize) -> [u8; 32];
+}
+
+/// The default transcript protocol: elements are encoded with bincode and
+/// hashed with SHA-256.
+#[derive(Debug, Clone)]
+pub struct Sha256Transcript {
     hasher: Sha256,
     buffer: Vec<u8>,
+}
+
+impl Sha256Transcript {
+    fn append<S: Serialize + ?Sized>(&mut self, el: &S) {
+        bincode::serialize_into(&mut self.buffer, el).expect("vec");
+        self.hasher.update(&self.buffer);
+        self.buffer.clear();
+    }
+}
+
+impl TranscriptProtocol for Sha256Transcript {
+    const PROTOCOL_ID: &'static str = PREFIX;
+
+    fn new(application_tag: &str) -> Self {
+        let mut hasher = Sha256::new();
+        hasher.update(PREFIX);
+        hasher.update(application_tag);
+
+        Sha256Transcript {
+            hasher,
+            buffer: Vec::new(),
+        }
+    }
+
+    fn append_scalar<F: PrimeField + Serialize>(&mut self, x: &F) {
+        self.append(x);
+    }
+
+    fn append_point<G: PrimeCurveAffine + Serialize>(&mut self, p: &G) {
+        self.append(p);
+    }
+
+    fn append_gt<T: Compress + Copy + Serialize>(&mut self, x: &T) {
+        self.append(x);
+    }
+
+    fn append_u32(&mut self, x: u32) {
+        self.append(&x);
+    }
+
+    fn append_u64(&mut self, x: u64) {
+        self.append(&x);
+    }
+
+    fn append_len(&mut self, len: usize) {
+        self.append(&(len as u64));
+    }
+
+    fn append_bytes(&mut self, bytes: &[u8]) {
+        self.append(bytes);
+    }
+
+    fn squeeze(&mut self, counter: usize) -> [u8; 32] {
+        self.hasher.update(&counter.to_be_bytes()[..]);
+        self.hasher.clone().finalize().into()
+    }
+}
+
+/// A hash function for `CanonicalTranscript`.
+pub trait TranscriptHash: Clone + Send + Sync {
+    /// Protocol id of the transcripts using this hash function.
+    const PROTOCOL_ID: &'static str;
+
+    fn new() -> Self;
+
+    fn update(&mut self, data: &[u8]);
+
+    fn finalize(self) -> [u8; 32];
+}
+
+/// A transcript protocol with an encoding that does not depend on the hash
+/// function nor on any serialization library, so that it can be reproduced
+/// on-chain or in a circuit. Every item is appended as its length in bytes,
+/// as a big endian u32, followed by its bytes:
+/// * the protocol id and the application tag first,
+/// * scalars as their canonical representation `PrimeField::to_repr`,
+/// * curve points in their compressed form,
+/// * target group elements in their compressed form,
+/// * u32 integers as big endian u32,
+/// * other integers, lengths of lists and the counter of `squeeze` as big
+///   endian u64.
+#[derive(Debug, Clone)]
+pub struct CanonicalTranscript<H: TranscriptHash> {
+    hasher: H,
+}
+
+impl<H: TranscriptHash> CanonicalTranscript<H> {
+    fn append(&mut self, bytes: &[u8]) {
+        self.hasher.update(&(bytes.len() as u32).to_be_bytes());
+        self.hasher.update(bytes);
+    }
+}
+
+impl<H: TranscriptHash> TranscriptProtocol for CanonicalTranscript<H> {
+    const PROTOCOL_ID: &'static str = H::PROTOCOL_ID;
+
+    fn new(application_tag: &str) -> Self {
+        let mut transcript = CanonicalTranscript { hasher: H::new() };
+        transcript.append(H::PROTOCOL_ID.as_bytes());
+        transcript.append(application_tag.as_bytes());
+        transcript
+    }
+
+    fn append_scalar<F: PrimeField + Serialize>(&mut self, x: &F) {
+        self.append(x.to_repr().as_ref());
+    }
+
+    fn append_point<G: PrimeCurveAffine + Serialize>(&mut self, p: &G) {
+        self.append(p.to_bytes().as_ref());
+    }
+
+    fn append_gt<T: Compress + Copy + Serialize>(&mut self, x: &T) {
+        let mut bytes = Vec::new();
+        x.write_compressed(&mut bytes).expect("vec");
+        self.append(&bytes);
+    }
+
+    fn append_u32(&mut self, x: u32) {
+        self.append(&x.to_be_bytes());
+    }
+
+    fn append_u64(&mut self, x: u64) {
+        self.append(&x.to_be_bytes());
+    }
+
+    fn append_len(&mut self, len: usize) {
+        self.append_u64(len as u64);
+    }
+
+    fn append_bytes(&mut self, bytes: &[u8]) {
+        self.append(bytes);
+    }
+
+    fn squeeze(&mut self, counter: usize) -> [u8; 32] {
+        self.append_u64(counter as u64);
+        self.hasher.clone().finalize()
+    }
+}
+
+/// BLAKE2s-256 for `CanonicalTranscript`.
+#[derive(Debug, Clone)]
+pub struct Blake2s(blake2s_simd::State);
+
+impl TranscriptHash for Blake2s {
+    const PROTOCOL_ID: &'static str = "snarkpack-v1-blake2s";
+
+    fn new() -> Self {
+        Blake2s(blake2s_simd::State::new())
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        *self.0.finalize().as_array()
+    }
+}
+
+impl TranscriptHash for Keccak256 {
+    const PROTOCOL_ID: &'static str = "snarkpack-v1-keccak256";
+
+    fn new() -> Self {
+        Keccak256::new()
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        Keccak256::update(self, data);
+    }
+
+    fn finalize(self) -> [u8; 32] {
+        Keccak256::finalize(self)
+    }
+}
+
+/// Transcript protocol with the canonical encoding hashed with BLAKE2s.
+pub type Blake2sTranscript = CanonicalTranscript<Blake2s>;
+
+/// Transcript protocol with the canonical encoding hashed with Keccak-256, as
+/// available on Ethereum.
+pub type Keccak256Transcript = CanonicalTranscript<Keccak256>;
+
+#[derive(Debug)]
+pub struct Transcript<E: Engine, P: TranscriptProtocol = Sha256Transcript> {
+    protocol: P,
     _e: PhantomData<E>,
 }
 
@@ -49,26 +273,64 @@ where
     }
 }
 
-impl<E: Engine> Transcript<E> {
+impl<E: Engine, P: TranscriptProtocol> Transcript<E, P> {
     pub fn new(application_tag: &str) -> Self {
-        let mut hasher = sha2::Sha256::new();
-        hasher.update(PREFIX);
-        hasher.update(application_tag);
-
         Transcript {
-            hasher,
-            buffer: Vec::new(),
+            protocol: P::new(application_tag),
             _e: Default::default(),
         }
     }
 
-    pub fn write<S: Serialize>(mut self, el: &S) -> Self {
-        bincode::serialize_into(&mut self.buffer, el).expect("vec");
-        self.hasher.update(&self.buffer);
-        self.buffer.clear();
+    pub fn write_scalar(mut self, x: &E::Fr) -> Self
+    where
+        E::Fr: Serialize,
+    {
+        self.protocol.append_scalar(x);
         self
     }
 
+    pub fn write_point<G: PrimeCurveAffine + Serialize>(mut self, p: &G) -> Self {
+        self.protocol.append_point(p);
+        self
+    }
+
+    /// Writes a point of G1 given in projective form.
+    pub fn write_g1(self, p: &E::G1) -> Self
+    where
+        E::G1Affine: Serialize,
+    {
+        self.write_point(&p.to_affine())
+    }
+
+    pub fn write_gt(mut self, x: &E::Gt) -> Self
+    where
+        E::Gt: Compress + Serialize,
+    {
+        self.protocol.append_gt(x);
+        self
+    }
+
+    pub fn write_u32(mut self, x: u32) -> Self {
+        self.protocol.append_u32(x);
+        self
+    }
+
+    pub fn write_u64(mut self, x: u64) -> Self {
+        self.protocol.append_u64(x);
+        self
+    }
+
+    pub fn write_bytes(mut self, bytes: &[u8]) -> Self {
+        self.protocol.append_bytes(bytes);
+        self
+    }
+
+    /// Writes the length of `items` followed by each item with `write`.
+    pub fn write_list<T>(mut self, items: &[T], write: impl Fn(Self, &T) -> Self) -> Self {
+        self.protocol.append_len(items.len());
+        items.iter().fold(self, write)
+    }
+
     /// Generate a challenge from the transcript.
     pub fn into_challenge(mut self) -> Challenge<E> {
         let repr_bits = <<E as Engine>::Fr as PrimeField>::Repr::default()
@@ -81,9 +343,7 @@ impl<E: Engine> Transcript<E> {
         let one = E::Fr::one();
         let r = loop {
             counter_nonce += 1;
-            self.hasher.update(&counter_nonce.to_be_bytes()[..]);
-            let curr_state = self.hasher.clone();
-            let digest = curr_state.finalize();
+            let digest = self.protocol.squeeze(counter_nonce);
 
             let mut repr = <<E as Engine>::Fr as PrimeField>::Repr::default();
             repr.as_mut().copy_from_slice(&digest);
@@ -113,25 +373,66 @@ mod test {
     use group::prime::PrimeCurveAffine;
     use pairing::{MillerLoopResult, MultiMillerLoop};
 
-    #[test]
-    fn test_transcript() {
-        let mut t = Transcript::<Bls12>::new("test");
+    fn write_elements<P: TranscriptProtocol>(t: Transcript<Bls12, P>) -> Transcript<Bls12, P> {
         let g1 = G1Affine::generator();
         let g2 = G2Affine::generator();
         let gt = <Bls12 as MultiMillerLoop>::multi_miller_loop(&[(&g1, &g2.into())])
             .final_exponentiation();
 
-        t = t.write(&g1).write(&g2).write(&gt).write(&Fr::one());
+        t.write_point(&g1)
+            .write_point(&g2)
+            .write_gt(&gt)
+            .write_scalar(&Fr::one())
+            .write_list(&[1u32, 2], |t, x| t.write_u32(*x))
+            .write_u64(3)
+            .write_bytes(&[4, 5])
+    }
+
+    #[test]
+    fn test_transcript() {
+        let c1 = write_elements(Transcript::<Bls12>::new("test")).into_challenge();
+        let c12 = write_elements(Transcript::<Bls12>::new("test")).into_challenge();
+        assert_eq!(c1, c12);
+    }
 
-        let c1 = t.into_challenge();
+    #[test]
+    fn test_default_transcript_unchanged() {
+        // challenge of the bincode and SHA-256 transcript before it was made
+        // pluggable, existing aggregate proofs depend on it
+        let c = write_elements(Transcript::<Bls12, Sha256Transcript>::new("test")).into_challenge();
+        let expected = [
+            0x69, 0x38, 0xf5, 0xfb, 0x3b, 0x53, 0x75, 0xd7, 0x4a, 0x42, 0x72, 0xf5, 0x2a, 0x8e,
+            0x83, 0xeb, 0x57, 0x09, 0x22, 0x0f, 0xf1, 0x74, 0x25, 0x41, 0xe6, 0x52, 0x27, 0x03,
+            0xbf, 0x10, 0xcb, 0x6a,
+        ];
+        assert_eq!(c.to_repr().as_ref(), &expected[..]);
+    }
 
-        let t2 = Transcript::new("test")
-            .write(&g1)
-            .write(&g2)
-            .write(&gt)
-            .write(&Fr::one());
+    #[test]
+    fn test_transcript_protocols() {
+        let sha =
+            write_elements(Transcript::<Bls12, Sha256Transcript>::new("test")).into_challenge();
+        let blake =
+            write_elements(Transcript::<Bls12, Blake2sTranscript>::new("test")).into_challenge();
+        let keccak =
+            write_elements(Transcript::<Bls12, Keccak256Transcript>::new("test")).into_challenge();
+        assert_ne!(sha, blake);
+        assert_ne!(sha, keccak);
+        assert_ne!(blake, keccak);
 
-        let c12 = t2.into_challenge();
-        assert_eq!(c1, c12);
+        // items are length prefixed, they can not be shifted into each other
+        let split = |a: &[u8], b: &[u8]| {
+            Transcript::<Bls12, Keccak256Transcript>::new("test")
+                .write_bytes(a)
+                .write_bytes(b)
+                .into_challenge()
+        };
+        assert_ne!(split(b"ab", b"c"), split(b"a", b"bc"));
+        assert_ne!(
+            Transcript::<Bls12, Blake2sTranscript>::new("ab").into_challenge(),
+            Transcript::<Bls12, Blake2sTranscript>::new("a")
+                .write_bytes(b"b")
+                .into_challenge()
+        );
     }
 }
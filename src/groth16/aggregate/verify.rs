@@ -1,20 +1,20 @@
+use crate::parallel::prelude::*;
 use blstrs::Compress;
-use crossbeam_channel::bounded;
 use ff::{Field, PrimeField};
 use group::{prime::PrimeCurveAffine, Curve, Group};
 use log::debug;
 use log::*;
 use pairing::{Engine, MultiMillerLoop};
-use rayon::prelude::*;
 use serde::Serialize;
 
 use super::{
     accumulator::PairingChecks,
-    inner_product,
+    commitments_challenge, inner_product, input_combination_challenge, pad_to_power_of_two,
     prove::polynomial_evaluation_product_form_from_transcript,
-    structured_scalar_power,
-    transcript::{Challenge, Transcript},
-    AggregateProof, KZGOpening, VerifierSRS,
+    random_linear_combination_challenge, scalars_per_key, structured_scalar_power,
+    transcript::{Challenge, Sha256Transcript, Transcript, TranscriptProtocol},
+    AggregateProof, CommittedAggregateProof, HeterogeneousAggregateProof, InputCommitment,
+    KZGOpening, VerifierSRS,
 };
 use crate::groth16::{
     multiscalar::{par_multiscalar, MultiscalarPrecomp, ScalarList},
@@ -22,10 +22,30 @@ use crate::groth16::{
 };
 use crate::SynthesisError;
 
+use std::borrow::Cow;
 use std::default::Default;
+use std::ops::Range;
 use std::ops::{AddAssign, MulAssign, SubAssign};
+use std::sync::Mutex;
+#[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
 
+/// `Instant::now` panics on `wasm32-unknown-unknown`, the timings are only
+/// logged so they are reported as zero there.
+#[cfg(target_arch = "wasm32")]
+struct Instant;
+
+#[cfg(target_arch = "wasm32")]
+impl Instant {
+    fn now() -> Self {
+        Instant
+    }
+
+    fn elapsed(&self) -> std::time::Duration {
+        std::time::Duration::default()
+    }
+}
+
 /// Verifies the aggregated proofs thanks to the Groth16 verifying key, the
 /// verifier SRS from the aggregation scheme, all the public inputs of the
 /// proofs and the aggregated proof.
@@ -56,8 +76,456 @@ where
     E::G1Affine: Serialize,
     E::G2Affine: Serialize,
     R: rand_core::RngCore + Send,
+{
+    verify_aggregate_proof_with_transcript::<E, Sha256Transcript, R>(
+        ip_verifier_srs,
+        pvk,
+        rng,
+        public_inputs,
+        proof,
+        transcript_include,
+    )
+}
+
+/// Verifies an aggregate proof created with `aggregate_proofs_with_transcript`
+/// like `verify_aggregate_proof`, deriving the Fiat–Shamir challenges with the
+/// transcript protocol `P`, which must be the one used by the prover.
+pub fn verify_aggregate_proof_with_transcript<E, P, R>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvk: &PreparedVerifyingKey<E>,
+    rng: R,
+    public_inputs: &[Vec<E::Fr>],
+    proof: &AggregateProof<E>,
+    transcript_include: &[u8],
+) -> Result<bool, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+    R: rand_core::RngCore + Send,
 {
     info!("verify_aggregate_proof");
+    check_aggregate_statement(pvk, public_inputs, proof)?;
+
+    let pairing_checks = PairingChecks::new(rng);
+    merge_aggregate_proof::<E, P, R>(
+        ip_verifier_srs,
+        pvk,
+        AggregateInputs::Full(public_inputs),
+        proof,
+        transcript_include,
+        &SubChecks::all(&pairing_checks),
+        false,
+    );
+
+    let res = pairing_checks.verify();
+    info!("aggregate verify done");
+    res
+}
+
+/// Verifies an aggregate proof created with `aggregate_proofs_committed_inputs`
+/// against a commitment to the public inputs of the proofs instead of the
+/// public inputs themselves. The verifier must trust the commitment to be
+/// computed with `commit_public_inputs` from the public inputs it expects, in
+/// exchange its work is logarithmic in the number of proofs. The same WARNING
+/// as `verify_aggregate_proof` applies to `transcript_include`.
+pub fn verify_aggregate_proof_committed_inputs<E, R>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvk: &PreparedVerifyingKey<E>,
+    rng: R,
+    input_commitment: &InputCommitment<E>,
+    proof: &CommittedAggregateProof<E>,
+    transcript_include: &[u8],
+) -> Result<bool, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    E::Fr: Serialize,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+    R: rand_core::RngCore + Send,
+{
+    verify_aggregate_proof_committed_inputs_with_transcript::<E, Sha256Transcript, R>(
+        ip_verifier_srs,
+        pvk,
+        rng,
+        input_commitment,
+        proof,
+        transcript_include,
+    )
+}
+
+/// Verifies an aggregate proof created with
+/// `aggregate_proofs_committed_inputs_with_transcript` like
+/// `verify_aggregate_proof_committed_inputs`, with the transcript protocol
+/// `P` of the prover.
+pub fn verify_aggregate_proof_committed_inputs_with_transcript<E, P, R>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvk: &PreparedVerifyingKey<E>,
+    rng: R,
+    input_commitment: &InputCommitment<E>,
+    proof: &CommittedAggregateProof<E>,
+    transcript_include: &[u8],
+) -> Result<bool, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+    R: rand_core::RngCore + Send,
+{
+    info!("verify_aggregate_proof_committed_inputs");
+    proof.parsing_check()?;
+    if input_commitment.num_inputs() + 1 != pvk.ic.len()
+        || proof.input_evals.len() + 1 != pvk.ic.len()
+    {
+        return Err(SynthesisError::MalformedVerifyingKey);
+    }
+    if input_commitment.nproofs != proof.proof.tmipp.gipa.nproofs {
+        return Err(SynthesisError::MalformedProofs(
+            "input commitment does not match nproofs".to_string(),
+        ));
+    }
+
+    let pairing_checks = PairingChecks::new(rng);
+    merge_aggregate_proof::<E, P, _>(
+        ip_verifier_srs,
+        pvk,
+        AggregateInputs::Committed(input_commitment, &proof.input_evals, &proof.input_opening),
+        &proof.proof,
+        transcript_include,
+        &SubChecks::all(&pairing_checks),
+        false,
+    );
+
+    let res = pairing_checks.verify();
+    info!("aggregate verify done");
+    res
+}
+
+/// A sub-check of the verification of an aggregate proof, as reported by
+/// `verify_aggregate_proof_diagnostic`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum AggregateCheck {
+    /// The TIPP relation between the A and B of the proofs and `com_ab`.
+    Tipp,
+    /// The MIPP relation between the C of the proofs, `com_c` and `agg_c`.
+    Mipp,
+    /// The KZG opening of the final commitment key v.
+    KzgV,
+    /// The KZG opening of the final commitment key w.
+    KzgW,
+    /// The aggregated Groth16 verification equation.
+    Groth16,
+}
+
+/// Verifies an aggregate proof like `verify_aggregate_proof`, but checks each
+/// sub-check on its own so that the ones that failed can be reported. It
+/// returns the failed sub-checks in the order of `AggregateCheck`, and is
+/// empty if the proof is valid.
+///
+/// This needs a final exponentiation per sub-check and is meant to diagnose
+/// rejected proofs, `verify_aggregate_proof` should be used otherwise.
+pub fn verify_aggregate_proof_diagnostic<E, R>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvk: &PreparedVerifyingKey<E>,
+    rng: R,
+    public_inputs: &[Vec<E::Fr>],
+    proof: &AggregateProof<E>,
+    transcript_include: &[u8],
+) -> Result<Vec<AggregateCheck>, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    E::Fr: Serialize,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+    R: rand_core::RngCore + Send,
+{
+    verify_aggregate_proof_diagnostic_with_transcript::<E, Sha256Transcript, R>(
+        ip_verifier_srs,
+        pvk,
+        rng,
+        public_inputs,
+        proof,
+        transcript_include,
+    )
+}
+
+/// Diagnoses an aggregate proof created with
+/// `aggregate_proofs_with_transcript` like `verify_aggregate_proof_diagnostic`,
+/// with the transcript protocol `P` of the prover.
+pub fn verify_aggregate_proof_diagnostic_with_transcript<E, P, R>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvk: &PreparedVerifyingKey<E>,
+    rng: R,
+    public_inputs: &[Vec<E::Fr>],
+    proof: &AggregateProof<E>,
+    transcript_include: &[u8],
+) -> Result<Vec<AggregateCheck>, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+    R: rand_core::RngCore + Send,
+{
+    info!("verify_aggregate_proof_diagnostic");
+    check_aggregate_statement(pvk, public_inputs, proof)?;
+
+    let rng = Mutex::new(rng);
+    let tipp = PairingChecks::new(SharedRng(&rng));
+    let mipp = PairingChecks::new(SharedRng(&rng));
+    let kzg_v = PairingChecks::new(SharedRng(&rng));
+    let kzg_w = PairingChecks::new(SharedRng(&rng));
+    let groth16 = PairingChecks::new(SharedRng(&rng));
+    merge_aggregate_proof::<E, P, _>(
+        ip_verifier_srs,
+        pvk,
+        AggregateInputs::Full(public_inputs),
+        proof,
+        transcript_include,
+        &SubChecks {
+            tipp: &tipp,
+            mipp: &mipp,
+            kzg_v: &kzg_v,
+            kzg_w: &kzg_w,
+            groth16: &groth16,
+        },
+        false,
+    );
+
+    // all checks must be verified before returning any error
+    let results = vec![
+        (AggregateCheck::Tipp, tipp.verify()),
+        (AggregateCheck::Mipp, mipp.verify()),
+        (AggregateCheck::KzgV, kzg_v.verify()),
+        (AggregateCheck::KzgW, kzg_w.verify()),
+        (AggregateCheck::Groth16, groth16.verify()),
+    ];
+    let mut failed = Vec::new();
+    for (check, res) in results {
+        if !res? {
+            failed.push(check);
+        }
+    }
+    info!("aggregate verify done, failed checks: {:?}", failed);
+    Ok(failed)
+}
+
+/// The public inputs an aggregate proof is verified against.
+enum AggregateInputs<'a, E: Engine> {
+    /// The public inputs of every proof.
+    Full(&'a [Vec<E::Fr>]),
+    /// The commitment to the public inputs, their combination by $r$ and its
+    /// opening.
+    Committed(
+        &'a InputCommitment<E>,
+        &'a [E::Fr],
+        &'a KZGOpening<E::G2Affine>,
+    ),
+}
+
+impl<'a, E: Engine> Clone for AggregateInputs<'a, E> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, E: Engine> Copy for AggregateInputs<'a, E> {}
+
+/// The pairing checks each sub-check of an aggregate proof is merged into.
+/// They are all the same one, except when diagnosing which one fails.
+struct SubChecks<'a, E, R>
+where
+    E: MultiMillerLoop,
+    R: rand_core::RngCore + Send,
+{
+    tipp: &'a PairingChecks<E, R>,
+    mipp: &'a PairingChecks<E, R>,
+    kzg_v: &'a PairingChecks<E, R>,
+    kzg_w: &'a PairingChecks<E, R>,
+    groth16: &'a PairingChecks<E, R>,
+}
+
+impl<'a, E, R> SubChecks<'a, E, R>
+where
+    E: MultiMillerLoop,
+    R: rand_core::RngCore + Send,
+{
+    fn all(pairing_checks: &'a PairingChecks<E, R>) -> Self {
+        SubChecks {
+            tipp: pairing_checks,
+            mipp: pairing_checks,
+            kzg_v: pairing_checks,
+            kzg_w: pairing_checks,
+            groth16: pairing_checks,
+        }
+    }
+}
+
+/// Lets several pairing checks draw their random coefficients from the same
+/// random number generator.
+struct SharedRng<'a, R>(&'a Mutex<R>);
+
+impl<'a, R: rand_core::RngCore> rand_core::RngCore for SharedRng<'a, R> {
+    fn next_u32(&mut self) -> u32 {
+        self.0.lock().unwrap().next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.0.lock().unwrap().next_u64()
+    }
+
+    fn fill_bytes(&mut self, dest: &mut [u8]) {
+        self.0.lock().unwrap().fill_bytes(dest)
+    }
+
+    fn try_fill_bytes(&mut self, dest: &mut [u8]) -> Result<(), rand_core::Error> {
+        self.0.lock().unwrap().try_fill_bytes(dest)
+    }
+}
+
+/// An aggregate proof to verify with `verify_aggregate_proofs_batch`, with
+/// the same arguments as `verify_aggregate_proof`.
+pub struct AggregateBatchItem<'a, E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    pub ip_verifier_srs: &'a VerifierSRS<E>,
+    pub pvk: &'a PreparedVerifyingKey<E>,
+    pub public_inputs: &'a [Vec<E::Fr>],
+    pub proof: &'a AggregateProof<E>,
+    pub transcript_include: &'a [u8],
+}
+
+/// Verifies many aggregate proofs, possibly of different sizes and circuits,
+/// at once: the checks of all proofs are randomized and merged together so a
+/// single final exponentiation is needed. It returns the positions of the
+/// invalid proofs in `items`, in order, and is empty if they are all valid.
+/// When the batch is rejected, it is bisected to find the invalid proofs.
+///
+/// It fails like `verify_aggregate_proof` if any of the proofs is malformed.
+pub fn verify_aggregate_proofs_batch<E, R>(
+    rng: &mut R,
+    items: &[AggregateBatchItem<'_, E>],
+) -> Result<Vec<usize>, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    E::Fr: Serialize,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+    R: rand_core::RngCore + Send,
+{
+    verify_aggregate_proofs_batch_with_transcript::<E, Sha256Transcript, R>(rng, items)
+}
+
+/// Verifies many aggregate proofs created with
+/// `aggregate_proofs_with_transcript` like `verify_aggregate_proofs_batch`.
+/// All of them must use the transcript protocol `P`.
+pub fn verify_aggregate_proofs_batch_with_transcript<E, P, R>(
+    rng: &mut R,
+    items: &[AggregateBatchItem<'_, E>],
+) -> Result<Vec<usize>, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+    R: rand_core::RngCore + Send,
+{
+    info!("verify_aggregate_proofs_batch: {} proofs", items.len());
+    for item in items {
+        check_aggregate_statement(item.pvk, item.public_inputs, item.proof)?;
+    }
+    let mut invalid = Vec::new();
+    bisect_aggregate_batch::<E, P, R>(rng, items, 0..items.len(), false, &mut invalid)?;
+    Ok(invalid)
+}
+
+/// Collects the invalid proofs in `range`. If `known_invalid` is set, the
+/// range is known to contain an invalid proof and is not checked as a whole.
+fn bisect_aggregate_batch<E, P, R>(
+    rng: &mut R,
+    items: &[AggregateBatchItem<'_, E>],
+    range: Range<usize>,
+    known_invalid: bool,
+    invalid: &mut Vec<usize>,
+) -> Result<(), SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+    R: rand_core::RngCore + Send,
+{
+    if range.is_empty() {
+        return Ok(());
+    }
+    if !known_invalid {
+        let pairing_checks = PairingChecks::new(&mut *rng);
+        // a single proof does not need its Groth16 equation randomized
+        let randomize = range.len() > 1;
+        items[range.clone()].par_iter().for_each(|item| {
+            merge_aggregate_proof::<E, P, _>(
+                item.ip_verifier_srs,
+                item.pvk,
+                AggregateInputs::Full(item.public_inputs),
+                item.proof,
+                item.transcript_include,
+                &SubChecks::all(&pairing_checks),
+                randomize,
+            )
+        });
+        if pairing_checks.verify()? {
+            return Ok(());
+        }
+    }
+    if range.len() == 1 {
+        invalid.push(range.start);
+        return Ok(());
+    }
+
+    let mid = range.start + range.len() / 2;
+    let found = invalid.len();
+    bisect_aggregate_batch::<E, P, R>(rng, items, range.start..mid, false, invalid)?;
+    // If the left half is valid, the invalid proof must be on the right.
+    let right_invalid = invalid.len() == found;
+    bisect_aggregate_batch::<E, P, R>(rng, items, mid..range.end, right_invalid, invalid)
+}
+
+/// Performs the checks of the aggregate proof that do not require any pairing:
+/// it is well formed and matches the verifying key and public inputs.
+fn check_aggregate_statement<E>(
+    pvk: &PreparedVerifyingKey<E>,
+    public_inputs: &[Vec<E::Fr>],
+    proof: &AggregateProof<E>,
+) -> Result<(), SynthesisError>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
     proof.parsing_check()?;
     for pub_input in public_inputs {
         if (pub_input.len() + 1) != pvk.ic.len() {
@@ -70,40 +538,82 @@ where
             "public inputs length does not match nproofs".to_string(),
         ));
     }
+    Ok(())
+}
 
-    let hcom = Transcript::<E>::new("hcom")
-        .write(&proof.com_ab)
-        .write(&proof.com_c)
-        .into_challenge();
+/// Merges all the pairing checks of an aggregate proof, which must have passed
+/// `check_aggregate_statement`, into `checks`. The aggregated Groth16
+/// equation is the only check that is not randomized, unless `randomize` is
+/// set, which is required when the checks of several proofs are merged.
+fn merge_aggregate_proof<E, P, R>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvk: &PreparedVerifyingKey<E>,
+    inputs: AggregateInputs<'_, E>,
+    proof: &AggregateProof<E>,
+    transcript_include: &[u8],
+    checks: &SubChecks<'_, E, R>,
+    randomize: bool,
+) where
+    E: MultiMillerLoop + std::fmt::Debug,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+    R: rand_core::RngCore + Send,
+{
+    let input_commitment = match inputs {
+        AggregateInputs::Full(_) => None,
+        AggregateInputs::Committed(input_commitment, _, _) => Some(input_commitment),
+    };
+    let hcom = commitments_challenge::<E, P>(&proof.com_ab, &proof.com_c, input_commitment);
 
     // Random linear combination of proofs
-    let r = Transcript::<E>::new("random-r")
-        .write(&hcom)
-        .write(&transcript_include)
-        .into_challenge();
-
-    let pairing_checks = PairingChecks::new(rng);
-    let pairing_checks_copy = &pairing_checks;
+    let nproofs = proof.tmipp.gipa.nproofs as usize;
+    let r = random_linear_combination_challenge::<E, P>(&hcom, transcript_include, nproofs, &[]);
+    // the prover padded the proofs by repeating the last one
+    let padded_nproofs = nproofs.next_power_of_two();
 
     // 1.Check TIPA proof ab
     // 2.Check TIPA proof c
     //        s.spawn(move |_| {
     let now = Instant::now();
-    verify_tipp_mipp::<E, R>(
+    verify_tipp_mipp::<E, P, R>(
         ip_verifier_srs,
         proof,
         &r, // we give the extra r as it's not part of the proof itself - it is simply used on top for the groth16 aggregation
-        pairing_checks_copy,
+        checks,
         &hcom,
+        None,
     );
     debug!("TIPP took {} ms", now.elapsed().as_millis(),);
 
+    // The values of the committed public inputs are checked against their
+    // commitment, they are then used like the ones computed from the public
+    // inputs of every proof
+    let public_inputs = match inputs {
+        AggregateInputs::Full(public_inputs) => pad_to_power_of_two(public_inputs),
+        AggregateInputs::Committed(input_commitment, input_evals, input_opening) => {
+            verify_input_opening::<E, P, R>(
+                ip_verifier_srs,
+                input_commitment,
+                input_evals,
+                input_opening,
+                &r,
+                checks.groth16,
+            );
+            Cow::Borrowed(&[][..])
+        }
+    };
+    let public_inputs = &public_inputs[..];
+
     // Check aggregate pairing product equation
     // SUM of a geometric progression
     // SUM a^i = (1 - a^n) / (1 - a) = -(1-a^n)/-(1-a)
     // = (a^n - 1) / (a - 1)
     info!("checking aggregate pairing");
-    let mut r_sum = r.pow_vartime(&[public_inputs.len() as u64]);
+    let mut r_sum = r.pow_vartime(&[padded_nproofs as u64]);
     r_sum.sub_assign(&E::Fr::one());
     let b = (*r - E::Fr::one()).invert().unwrap();
     r_sum.mul_assign(&b);
@@ -113,14 +623,12 @@ where
     // NOTE From this point on, we are only checking *one* pairing check (the Groth16
     // verification equation) so we don't need to randomize as all other checks are being
     // randomized already. When merging all pairing checks together, this will be the only one
-    // non-randomized.
+    // non-randomized, unless the checks of other aggregate proofs are merged as well.
     //
-    let (r_vec_sender, r_vec_receiver) = bounded(1);
-
     let now = Instant::now();
-    r_vec_sender
-        .send(structured_scalar_power(public_inputs.len(), &*r))
-        .unwrap();
+    // only needed to combine the public inputs of every proof
+    let r_vec = structured_scalar_power(public_inputs.len(), &*r);
+    let powers = &r_vec;
     let elapsed = now.elapsed().as_millis();
     debug!("generation of r vector: {}ms", elapsed);
 
@@ -129,18 +637,12 @@ where
         let left = {
             let mut alpha_g1_r_sum = pvk.alpha_g1;
             alpha_g1_r_sum.mul_assign(r_sum);
-
-            E::multi_miller_loop(&[(&alpha_g1_r_sum.to_affine(), &pvk.beta_g2)])
+            alpha_g1_r_sum.to_affine()
         },
         // 4. Compute right part of the final pairing equation
-        let right = {
-            E::multi_miller_loop(&[(
-                // e(c^r vector form, h^delta)
-                // let agg_c = inner_product::multiexponentiation::<E::G1Affine>(&c, r_vec)
-                &proof.agg_c.to_affine(),
-                &pvk.delta_g2,
-            )])
-        },
+        // e(c^r vector form, h^delta)
+        // let agg_c = inner_product::multiexponentiation::<E::G1Affine>(&c, r_vec)
+        let right = proof.agg_c.to_affine(),
         // 5. compute the middle part of the final pairing equation, the one
         //    with the public inputs
         let middle = {
@@ -152,15 +654,16 @@ where
             // We incrementally build the r vector and the table
             // NOTE: in this version it's not r^2j but simply r^j
 
-            let l = public_inputs[0].len();
+            let l = pvk.ic.len() - 1;
             let mut g_ic = pvk.ic_projective[0];
             g_ic.mul_assign(r_sum);
 
-            let powers = r_vec_receiver.recv().unwrap();
-
             let now = Instant::now();
             // now we do the multi exponentiation
             let getter = |i: usize| -> <E::Fr as PrimeField>::Repr {
+                if let AggregateInputs::Committed(_, input_evals, _) = inputs {
+                    return input_evals[i].to_repr();
+                }
                 // i denotes the column of the public input, and j denotes which public input
                 let mut c = public_inputs[0][i];
                 for j in 1..public_inputs.len() {
@@ -179,19 +682,187 @@ where
 
             g_ic.add_assign(&totsi);
 
-            let ml = E::multi_miller_loop(&[(&g_ic.to_affine(), &pvk.gamma_g2)]);
             let elapsed = now.elapsed().as_millis();
             debug!("table generation: {}ms", elapsed);
 
-            ml
+            g_ic.to_affine()
         }
     };
 
-    pairing_checks_copy.merge_nonrandom(
-        vec![left, middle, right],
+    if randomize {
+        checks.groth16.merge_miller_inputs(
+            &[
+                (&left, &pvk.beta_g2_affine),
+                (&middle, &pvk.gamma_g2_affine),
+                (&right, &pvk.delta_g2_affine),
+            ],
+            &proof.ip_ab,
+        );
+    } else {
+        let left = E::multi_miller_loop(&[(&left, &pvk.beta_g2)]);
+        let middle = E::multi_miller_loop(&[(&middle, &pvk.gamma_g2)]);
+        let right = E::multi_miller_loop(&[(&right, &pvk.delta_g2)]);
+        checks.groth16.merge_nonrandom(
+            vec![left, middle, right],
+            // final value ip_ab is what we want to compare in the groth16
+            // aggregated equation A * B
+            proof.ip_ab,
+        );
+    }
+}
+
+/// Verifies proofs of different circuits aggregated with
+/// `aggregate_proofs_heterogeneous`. `pvks[k]` is the verifying key of the
+/// proofs with key index `k` and `public_inputs[i]` are the public inputs of
+/// the i-th proof, for the circuit of its key. The same WARNING as
+/// `verify_aggregate_proof` applies to `transcript_include`.
+pub fn verify_aggregate_proof_heterogeneous<E, R>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvks: &[&PreparedVerifyingKey<E>],
+    rng: R,
+    public_inputs: &[Vec<E::Fr>],
+    proof: &HeterogeneousAggregateProof<E>,
+    transcript_include: &[u8],
+) -> Result<bool, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    E::Fr: Serialize,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+    R: rand_core::RngCore + Send,
+{
+    verify_aggregate_proof_heterogeneous_with_transcript::<E, Sha256Transcript, R>(
+        ip_verifier_srs,
+        pvks,
+        rng,
+        public_inputs,
+        proof,
+        transcript_include,
+    )
+}
+
+/// Verifies proofs aggregated with
+/// `aggregate_proofs_heterogeneous_with_transcript` like
+/// `verify_aggregate_proof_heterogeneous`, with the transcript protocol `P`
+/// of the prover.
+pub fn verify_aggregate_proof_heterogeneous_with_transcript<E, P, R>(
+    ip_verifier_srs: &VerifierSRS<E>,
+    pvks: &[&PreparedVerifyingKey<E>],
+    rng: R,
+    public_inputs: &[Vec<E::Fr>],
+    proof: &HeterogeneousAggregateProof<E>,
+    transcript_include: &[u8],
+) -> Result<bool, SynthesisError>
+where
+    E: MultiMillerLoop + std::fmt::Debug,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    <E as Engine>::Gt: Compress + Serialize,
+    E::G1: Serialize,
+    E::G1Affine: Serialize,
+    E::G2Affine: Serialize,
+    R: rand_core::RngCore + Send,
+{
+    info!("verify_aggregate_proof_heterogeneous");
+    proof.parsing_check()?;
+    if pvks.len() != proof.num_keys() {
+        return Err(SynthesisError::MalformedVerifyingKey);
+    }
+    if public_inputs.len() != proof.key_indices.len() {
+        return Err(SynthesisError::MalformedProofs(
+            "public inputs length does not match nproofs".to_string(),
+        ));
+    }
+    for (pub_input, &k) in public_inputs.iter().zip(proof.key_indices.iter()) {
+        if (pub_input.len() + 1) != pvks[k as usize].ic.len() {
+            return Err(SynthesisError::MalformedVerifyingKey);
+        }
+    }
+
+    let agg_proof = &proof.proof;
+    let hcom = commitments_challenge::<E, P>(&agg_proof.com_ab, &agg_proof.com_c, None);
+
+    // Random linear combination of proofs, bound to the key of each proof
+    let r = random_linear_combination_challenge::<E, P>(
+        &hcom,
+        transcript_include,
+        public_inputs.len(),
+        &proof.key_indices,
+    );
+    // the prover padded the proofs and their key indices by repeating the last one
+    let public_inputs = pad_to_power_of_two(public_inputs);
+    let key_indices = pad_to_power_of_two(&proof.key_indices);
+    let r_vec = structured_scalar_power(public_inputs.len(), &*r);
+    let r_keys = scalars_per_key(&r_vec, &key_indices, pvks.len());
+
+    let pairing_checks = PairingChecks::new(rng);
+
+    // 1.Check TIPP proof ab
+    // 2.Check MIPP proof c, for all proofs and for the proofs of each key
+    let now = Instant::now();
+    verify_tipp_mipp::<E, P, R>(
+        ip_verifier_srs,
+        agg_proof,
+        &r,
+        &SubChecks::all(&pairing_checks),
+        &hcom,
+        Some(&KeyedMipp {
+            r: &r_keys,
+            agg_c: &proof.agg_c_keys,
+            z_c: &proof.z_c_keys,
+        }),
+    );
+    debug!("TIPP took {} ms", now.elapsed().as_millis(),);
+
+    // Check aggregate pairing product equation: the product over each key of
+    // the aggregated Groth16 equation of its proofs.
+    info!("checking aggregate pairing");
+    let now = Instant::now();
+    let miller_loops = pvks
+        .par_iter()
+        .zip(r_keys.par_iter())
+        .zip(proof.agg_c_keys.par_iter())
+        .map(|((pvk, r_key), agg_c)| {
+            let r_sum = r_key.iter().fold(E::Fr::zero(), |acc, ri| acc + ri);
+            let mut alpha_g1_r_sum = pvk.alpha_g1;
+            alpha_g1_r_sum.mul_assign(r_sum);
+
+            // public inputs of the proofs of this key, proofs of other keys
+            // have a zero coefficient
+            let mut g_ic = pvk.ic_projective[0];
+            g_ic.mul_assign(r_sum);
+            let getter = |i: usize| -> <E::Fr as PrimeField>::Repr {
+                let mut c = E::Fr::zero();
+                for (input, rj) in public_inputs.iter().zip(r_key.iter()) {
+                    if !bool::from(rj.is_zero()) {
+                        c.add_assign(&(input[i] * rj));
+                    }
+                }
+                c.to_repr()
+            };
+            let totsi = par_multiscalar::<_, E::G1Affine>(
+                &ScalarList::Getter(getter, pvk.ic.len() - 1),
+                &pvk.multiscalar.at_point(1),
+                std::mem::size_of::<<E::Fr as PrimeField>::Repr>() * 8,
+            );
+            g_ic.add_assign(&totsi);
+
+            E::multi_miller_loop(&[
+                (&alpha_g1_r_sum.to_affine(), &pvk.beta_g2),
+                (&g_ic.to_affine(), &pvk.gamma_g2),
+                (&agg_c.to_affine(), &pvk.delta_g2),
+            ])
+        })
+        .collect::<Vec<_>>();
+    debug!("per key pairing inputs: {}ms", now.elapsed().as_millis());
+
+    pairing_checks.merge_nonrandom(
+        miller_loops,
         // final value ip_ab is what we want to compare in the groth16
         // aggregated equation A * B
-        proof.ip_ab,
+        agg_proof.ip_ab,
     );
 
     let res = pairing_checks.verify();
@@ -199,17 +870,29 @@ where
     res
 }
 
+/// The MIPP relations of a heterogeneous aggregate proof, one per verifying
+/// key: $C^{r_k}$ where $r_k$ is $r$ restricted to the proofs of key $k$.
+struct KeyedMipp<'a, E: Engine> {
+    r: &'a [Vec<E::Fr>],
+    agg_c: &'a [E::G1],
+    /// MIPP values per GIPA round, then per key
+    z_c: &'a [Vec<(E::G1, E::G1)>],
+}
+
 /// verify_tipp_mipp returns a pairing equation to check the tipp proof.  $r$ is
 /// the randomness used to produce a random linear combination of A and B and
-/// used in the MIPP part with C
-fn verify_tipp_mipp<E, R>(
+/// used in the MIPP part with C. The MIPP relations of `keys` are checked
+/// with the same challenges.
+fn verify_tipp_mipp<E, P, R>(
     v_srs: &VerifierSRS<E>,
     proof: &AggregateProof<E>,
     r_shift: &E::Fr,
-    pairing_checks: &PairingChecks<E, R>,
+    checks: &SubChecks<'_, E, R>,
     hcom: &Challenge<E>,
+    keys: Option<&KeyedMipp<E>>,
 ) where
     E: MultiMillerLoop,
+    P: TranscriptProtocol,
     E::Fr: Serialize,
     <E as Engine>::Gt: Compress + Serialize,
     E::G1: Serialize,
@@ -221,7 +904,7 @@ fn verify_tipp_mipp<E, R>(
     let now = Instant::now();
     // (T,U), Z for TIPP and MIPP  and all challenges
     let (final_res, final_r, challenges, challenges_inv) =
-        gipa_verify_tipp_mipp(&proof, r_shift, hcom);
+        gipa_verify_tipp_mipp::<E, P>(&proof, r_shift, hcom, keys);
     debug!(
         "TIPP verify: gipa verify tipp {}ms",
         now.elapsed().as_millis()
@@ -231,12 +914,12 @@ fn verify_tipp_mipp<E, R>(
     let fvkey = proof.tmipp.gipa.final_vkey;
     let fwkey = proof.tmipp.gipa.final_wkey;
     // KZG challenge point
-    let c = Transcript::<E>::new("random-z")
-        .write(&challenges[0])
-        .write(&fvkey.0)
-        .write(&fvkey.1)
-        .write(&fwkey.0)
-        .write(&fwkey.1)
+    let c = Transcript::<E, P>::new("random-z")
+        .write_scalar(&challenges[0])
+        .write_point(&fvkey.0)
+        .write_point(&fvkey.1)
+        .write_point(&fwkey.0)
+        .write_point(&fwkey.1)
         .into_challenge();
 
     // we take reference so they are able to be copied in the par! macro
@@ -249,6 +932,13 @@ fn verify_tipp_mipp<E, R>(
     let final_tc = &final_res.tc;
     let final_uc = &final_res.uc;
 
+    // MIPP of the proofs of each verifying key
+    if let Some(keys) = keys {
+        if !verify_keyed_mipp(keys, final_c, &challenges, &challenges_inv) {
+            checks.mipp.invalidate();
+        }
+    }
+
     let now = Instant::now();
     par! {
         // check the opening proof for v
@@ -258,7 +948,7 @@ fn verify_tipp_mipp<E, R>(
             &proof.tmipp.vkey_opening,
             &challenges_inv,
             &c,
-            pairing_checks,
+            checks.kzg_v,
         ),
         // check the opening proof for w - note that w has been rescaled by $r^{-1}$
         let _wtuple = verify_kzg_w(
@@ -268,7 +958,7 @@ fn verify_tipp_mipp<E, R>(
             &challenges,
             &r_shift.invert().unwrap(),
             &c,
-            pairing_checks,
+            checks.kzg_w,
         ),
         //
         // We create a sequence of pairing tuple that we aggregate together at
@@ -276,12 +966,12 @@ fn verify_tipp_mipp<E, R>(
         //
         // TIPP
         // z = e(A,B)
-        let _check_z = pairing_checks.merge_miller_inputs(&[(final_a, final_b)], final_zab),
+        let _check_z = checks.tipp.merge_miller_inputs(&[(final_a, final_b)], final_zab),
         //  final_aB.0 = T = e(A,v1)e(w1,B)
-        let _check_ab0 = pairing_checks.merge_miller_inputs(&[(final_a, &fvkey.0),(&fwkey.0, final_b)], final_tab),
+        let _check_ab0 = checks.tipp.merge_miller_inputs(&[(final_a, &fvkey.0),(&fwkey.0, final_b)], final_tab),
 
         //  final_aB.1 = U = e(A,v2)e(w2,B)
-        let _check_ab1 = pairing_checks.merge_miller_inputs(&[(final_a, &fvkey.1),(&fwkey.1, final_b)], final_uab),
+        let _check_ab1 = checks.tipp.merge_miller_inputs(&[(final_a, &fvkey.1),(&fwkey.1, final_b)], final_uab),
 
         // MIPP
         // Verify base inner product commitment
@@ -291,12 +981,12 @@ fn verify_tipp_mipp<E, R>(
             &[final_r]),
         // Check commiment correctness
         // T = e(C,v1)
-        let _check_t = pairing_checks.merge_miller_inputs(&[(final_c,&fvkey.0)], final_tc),
+        let _check_t = checks.mipp.merge_miller_inputs(&[(final_c,&fvkey.0)], final_tc),
         // U = e(A,v2)
-        let _check_u = pairing_checks.merge_miller_inputs(&[(final_c,&fvkey.1)], final_uc)
+        let _check_u = checks.mipp.merge_miller_inputs(&[(final_c,&fvkey.1)], final_uc)
     };
     match final_z {
-        Err(e) => pairing_checks.report_err(e),
+        Err(e) => checks.mipp.report_err(e),
         Ok(z) => {
             debug!(
                 "TIPP verify: parallel checks before merge: {}ms",
@@ -306,12 +996,49 @@ fn verify_tipp_mipp<E, R>(
             // only check that doesn't require pairing so we can give a tuple
             // that will render the equation wrong in case it's false
             if !b {
-                pairing_checks.invalidate();
+                checks.mipp.invalidate();
             }
         }
     }
 }
 
+/// Checks the MIPP relation of each verifying key against the final C of the
+/// GIPA proof, the challenges are given in reverse order. The final $r_k$ are
+/// computed by folding each $r_k$ since they have no structure.
+fn verify_keyed_mipp<E: Engine>(
+    keys: &KeyedMipp<E>,
+    final_c: &E::G1Affine,
+    challenges: &[E::Fr],
+    challenges_inv: &[E::Fr],
+) -> bool {
+    keys.r
+        .par_iter()
+        .zip(keys.agg_c.par_iter())
+        .enumerate()
+        .map(|(k, (r_key, agg_c))| {
+            let mut r_key = r_key.clone();
+            let mut z = *agg_c;
+            for ((zs, c), c_inv) in keys
+                .z_c
+                .iter()
+                .zip(challenges.iter().rev())
+                .zip(challenges_inv.iter().rev())
+            {
+                let (zc_l, zc_r) = zs[k];
+                z += zc_l * c + zc_r * c_inv;
+
+                let split = r_key.len() / 2;
+                let (r_left, r_right) = r_key.split_at_mut(split);
+                for (r_l, r_r) in r_left.iter_mut().zip(r_right.iter()) {
+                    r_l.add_assign(*r_r * c_inv);
+                }
+                r_key.truncate(split);
+            }
+            r_key.len() == 1 && z == *final_c * r_key[0]
+        })
+        .reduce(|| true, |a, b| a && b)
+}
+
 /// gipa_verify_tipp_mipp recurse on the proof and statement and produces the final
 /// values to be checked by TIPP and MIPP verifier, namely, for TIPP for example:
 /// * T,U: the final commitment values of A and B
@@ -322,16 +1049,19 @@ fn verify_tipp_mipp<E, R>(
 /// MIPP share the same challenges however, enabling to re-use common operations
 /// between them, such as the KZG proof for commitment keys.
 #[allow(clippy::type_complexity)]
-fn gipa_verify_tipp_mipp<E>(
+fn gipa_verify_tipp_mipp<E, P>(
     proof: &AggregateProof<E>,
     r_shift: &E::Fr,
     hcom: &E::Fr,
+    keys: Option<&KeyedMipp<E>>,
 ) -> (GipaTUZ<E>, E::Fr, Vec<E::Fr>, Vec<E::Fr>)
 where
     E: MultiMillerLoop,
+    P: TranscriptProtocol,
     E::Fr: Serialize,
     <E as Engine>::Gt: Compress + Serialize,
     E::G1: Serialize,
+    E::G1Affine: Serialize,
 {
     info!("gipa verify TIPP");
     let gipa = &proof.tmipp.gipa;
@@ -348,11 +1078,11 @@ where
     let mut challenges = Vec::new();
     let mut challenges_inv = Vec::new();
 
-    let mut c_inv: E::Fr = *Transcript::<E>::new("gipa-0")
-        .write(hcom)
-        .write(&proof.ip_ab)
-        .write(&proof.agg_c)
-        .write(&r_shift)
+    let mut c_inv: E::Fr = *Transcript::<E, P>::new("gipa-0")
+        .write_scalar(hcom)
+        .write_gt(&proof.ip_ab)
+        .write_g1(&proof.agg_c)
+        .write_scalar(r_shift)
         .into_challenge();
     let mut c = c_inv.invert().unwrap();
 
@@ -374,23 +1104,33 @@ where
         if i == 0 {
             // already generated c_inv and c outside of the loop
         } else {
-            c_inv = *Transcript::<E>::new(&format!("gipa-{}", i))
-                .write(&c_inv)
-                .write(&zab_l)
-                .write(&zab_r)
-                .write(&zc_l)
-                .write(&zc_r)
-                .write(&tab_l.0)
-                .write(&tab_l.1)
-                .write(&tab_r.0)
-                .write(&tab_r.1)
-                .write(&tc_l.0)
-                .write(&tc_l.1)
-                .write(&tc_r.0)
-                .write(&tc_r.1)
+            c_inv = *Transcript::<E, P>::new(&format!("gipa-{}", i))
+                .write_scalar(&c_inv)
+                .write_gt(zab_l)
+                .write_gt(zab_r)
+                .write_g1(zc_l)
+                .write_g1(zc_r)
+                .write_gt(&tab_l.0)
+                .write_gt(&tab_l.1)
+                .write_gt(&tab_r.0)
+                .write_gt(&tab_r.1)
+                .write_gt(&tc_l.0)
+                .write_gt(&tc_l.1)
+                .write_gt(&tc_r.0)
+                .write_gt(&tc_r.1)
                 .into_challenge();
             c = c_inv.invert().unwrap();
         }
+        if let Some(keys) = keys {
+            let mut transcript = Transcript::<E, P>::new(&format!("gipa-keys-{}", i))
+                .write_scalar(&c_inv)
+                .write_list(&keys.z_c[i], |t, (l, r)| t.write_g1(l).write_g1(r));
+            if i == 0 {
+                transcript = transcript.write_list(keys.agg_c, |t, c| t.write_g1(c));
+            }
+            c_inv = *transcript.into_challenge();
+            c = c_inv.invert().unwrap();
+        }
         challenges.push(c);
         challenges_inv.push(c_inv);
     }
@@ -521,6 +1261,63 @@ where
     (final_res, final_r, challenges, challenges_inv)
 }
 
+/// Checks the combination by $r$ of each committed public input, i.e. the
+/// evaluation at $r$ of the committed polynomials. The commitments and values
+/// are combined by a challenge so that a single KZG opening is checked.
+fn verify_input_opening<E, P, R>(
+    v_srs: &VerifierSRS<E>,
+    input_commitment: &InputCommitment<E>,
+    input_evals: &[E::Fr],
+    input_opening: &KZGOpening<E::G2Affine>,
+    r: &Challenge<E>,
+    pairing_checks: &PairingChecks<E, R>,
+) where
+    E: MultiMillerLoop,
+    P: TranscriptProtocol,
+    E::Fr: Serialize,
+    R: rand_core::RngCore + Send,
+{
+    let gamma = input_combination_challenge::<E, P>(r, input_evals);
+    let gammas = structured_scalar_power(input_evals.len(), &*gamma);
+
+    // \sum_i gamma^i f_i(r)
+    let eval = input_evals
+        .iter()
+        .zip(gammas.iter())
+        .fold(E::Fr::zero(), |acc, (x, g)| acc + *x * g);
+    // commitment to \sum_i gamma^i f_i
+    let (com_a, com_b) = input_commitment.coms.iter().zip(gammas.iter()).fold(
+        (E::G2::identity(), E::G2::identity()),
+        |(a, b), ((ca, cb), g)| (a + ca.to_curve() * g, b + cb.to_curve() * g),
+    );
+
+    let ng = (-v_srs.g).to_affine();
+    par! {
+        // e(g, C_f * h^{-y}) == e(g^a * g^{-r}, \pi) = 1
+        let _check1 = kzg_check_v::<E, R>(
+            v_srs,
+            ng,
+            **r,
+            eval,
+            com_a,
+            v_srs.g_alpha,
+            input_opening.0,
+            pairing_checks,
+        ),
+        // e(g, C_f * h^{-y}) == e(g^b * g^{-r}, \pi) = 1
+        let _check2 = kzg_check_v::<E, R>(
+            v_srs,
+            ng,
+            **r,
+            eval,
+            com_b,
+            v_srs.g_beta,
+            input_opening.1,
+            pairing_checks,
+        )
+    };
+}
+
 /// verify_kzg_opening_g2 takes a KZG opening, the final commitment key, SRS and
 /// any shift (in TIPP we shift the v commitment by r^-1) and returns a pairing
 /// tuple to check if the opening is correct or not.
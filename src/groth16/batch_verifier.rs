@@ -0,0 +1,127 @@
+use std::ops::Range;
+
+use ff::PrimeField;
+use pairing::MultiMillerLoop;
+use rand_core::RngCore;
+
+use super::{verify_proof, verify_proofs_batch_multi, BatchItem, PreparedVerifyingKey, Proof};
+use crate::SynthesisError;
+
+/// Collects proofs, possibly for different circuits, and verifies them with a
+/// single randomized batch check, see [`verify_proofs_batch_multi`].
+///
+/// If the batch is rejected, [`BatchVerifier::find_invalid`] bisects it to
+/// find exactly which proofs are invalid, re-checking only the halves that
+/// are not already known to contain an invalid proof.
+pub struct BatchVerifier<'a, E: MultiMillerLoop> {
+    items: Vec<Queued<'a, E>>,
+}
+
+struct Queued<'a, E: MultiMillerLoop> {
+    pvk: &'a PreparedVerifyingKey<E>,
+    proof: Proof<E>,
+    public_inputs: Vec<E::Fr>,
+}
+
+impl<'a, E: MultiMillerLoop> Default for BatchVerifier<'a, E> {
+    fn default() -> Self {
+        BatchVerifier { items: Vec::new() }
+    }
+}
+
+impl<'a, E> BatchVerifier<'a, E>
+where
+    E: MultiMillerLoop,
+    <E::Fr as PrimeField>::Repr: Sync + Copy,
+{
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queues a proof, it is identified by its position in the queue. Fails
+    /// if the number of public inputs does not match the key.
+    pub fn queue(
+        &mut self,
+        pvk: &'a PreparedVerifyingKey<E>,
+        proof: Proof<E>,
+        public_inputs: Vec<E::Fr>,
+    ) -> Result<(), SynthesisError> {
+        if (public_inputs.len() + 1) != pvk.ic.len() {
+            return Err(SynthesisError::MalformedVerifyingKey);
+        }
+        self.items.push(Queued {
+            pvk,
+            proof,
+            public_inputs,
+        });
+        Ok(())
+    }
+
+    /// Returns the number of queued proofs.
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Removes all queued proofs.
+    pub fn clear(&mut self) {
+        self.items.clear();
+    }
+
+    /// Verifies all queued proofs at once.
+    pub fn verify<R: RngCore>(&self, rng: &mut R) -> Result<bool, SynthesisError> {
+        self.verify_range(rng, 0..self.items.len())
+    }
+
+    /// Returns the positions of all invalid proofs in the queue, in order. It
+    /// is empty if the whole batch is valid, which costs a single batch check.
+    pub fn find_invalid<R: RngCore>(&self, rng: &mut R) -> Result<Vec<usize>, SynthesisError> {
+        let mut invalid = Vec::new();
+        self.bisect(rng, 0..self.items.len(), false, &mut invalid)?;
+        Ok(invalid)
+    }
+
+    fn verify_range<R: RngCore>(
+        &self,
+        rng: &mut R,
+        range: Range<usize>,
+    ) -> Result<bool, SynthesisError> {
+        if range.len() == 1 {
+            let item = &self.items[range.start];
+            return verify_proof(item.pvk, &item.proof, &item.public_inputs);
+        }
+        let batch: Vec<BatchItem<'_, E>> = self.items[range]
+            .iter()
+            .map(|item| (item.pvk, &item.proof, &item.public_inputs[..]))
+            .collect();
+        verify_proofs_batch_multi(rng, &batch)
+    }
+
+    /// Collects the invalid proofs in `range`. If `known_invalid` is set, the
+    /// range is known to contain an invalid proof and is not checked as a whole.
+    fn bisect<R: RngCore>(
+        &self,
+        rng: &mut R,
+        range: Range<usize>,
+        known_invalid: bool,
+        invalid: &mut Vec<usize>,
+    ) -> Result<(), SynthesisError> {
+        if range.is_empty() || (!known_invalid && self.verify_range(rng, range.clone())?) {
+            return Ok(());
+        }
+        if range.len() == 1 {
+            invalid.push(range.start);
+            return Ok(());
+        }
+
+        let mid = range.start + range.len() / 2;
+        let found = invalid.len();
+        self.bisect(rng, range.start..mid, false, invalid)?;
+        // If the left half is valid, the invalid proof must be on the right.
+        let right_invalid = invalid.len() == found;
+        self.bisect(rng, mid..range.end, right_invalid, invalid)
+    }
+}
@@ -1,15 +1,23 @@
-use std::ops::{AddAssign, Mul, MulAssign};
+use std::convert::TryInto;
+use std::io::{self, Write};
+use std::ops::{AddAssign, Mul, MulAssign, Range};
 
 use std::sync::Arc;
 
+use byteorder::{BigEndian, WriteBytesExt};
+
 use ff::{Field, PrimeField};
 use group::{
     prime::{PrimeCurve, PrimeCurveAffine},
-    Curve, Group, Wnaf, WnafGroup,
+    Curve, Group, UncompressedEncoding,
 };
 use pairing::{Engine, MultiMillerLoop};
 use rand_core::RngCore;
+use rayon::prelude::*;
 
+use super::multiscalar::{
+    precompute_fixed_window, MultiscalarPrecomp, MultiscalarPrecompOwned, WINDOW_SIZE,
+};
 use super::{Parameters, VerifyingKey};
 
 use crate::domain::EvaluationDomain;
@@ -25,8 +33,6 @@ pub fn generate_random_parameters<E, C, R>(
 ) -> Result<Parameters<E>, SynthesisError>
 where
     E: gpu::GpuEngine + MultiMillerLoop,
-    <E as Engine>::G1: WnafGroup,
-    <E as Engine>::G2: WnafGroup,
     C: Circuit<E::Fr>,
     R: RngCore,
 {
@@ -43,16 +49,38 @@ where
 
 /// This is our assembly structure that we'll use to synthesize the
 /// circuit into a QAP.
-struct KeypairAssembly<Scalar: PrimeField> {
-    num_inputs: usize,
-    num_aux: usize,
-    num_constraints: usize,
-    at_inputs: Vec<Vec<(Scalar, usize)>>,
-    bt_inputs: Vec<Vec<(Scalar, usize)>>,
-    ct_inputs: Vec<Vec<(Scalar, usize)>>,
-    at_aux: Vec<Vec<(Scalar, usize)>>,
-    bt_aux: Vec<Vec<(Scalar, usize)>>,
-    ct_aux: Vec<Vec<(Scalar, usize)>>,
+pub(super) struct KeypairAssembly<Scalar: PrimeField> {
+    pub(super) num_inputs: usize,
+    pub(super) num_aux: usize,
+    pub(super) num_constraints: usize,
+    pub(super) at_inputs: Vec<Vec<(Scalar, usize)>>,
+    pub(super) bt_inputs: Vec<Vec<(Scalar, usize)>>,
+    pub(super) ct_inputs: Vec<Vec<(Scalar, usize)>>,
+    pub(super) at_aux: Vec<Vec<(Scalar, usize)>>,
+    pub(super) bt_aux: Vec<Vec<(Scalar, usize)>>,
+    pub(super) ct_aux: Vec<Vec<(Scalar, usize)>>,
+}
+
+impl<Scalar: PrimeField> KeypairAssembly<Scalar> {
+    /// Synthesizes `circuit` into a QAP, including the "one" input variable
+    /// and the input constraints that keep the IC query fully dense.
+    pub(super) fn synthesize<C: Circuit<Scalar>>(circuit: C) -> Result<Self, SynthesisError> {
+        let mut assembly = KeypairAssembly::new();
+
+        // Allocate the "one" input variable
+        assembly.alloc_input(|| "", || Ok(Scalar::one()))?;
+
+        // Synthesize the circuit.
+        circuit.synthesize(&mut assembly)?;
+
+        // Input constraints to ensure full density of IC query
+        // x * 0 = 0
+        for i in 0..assembly.num_inputs {
+            assembly.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
+        }
+
+        Ok(assembly)
+    }
 }
 
 impl<Scalar: PrimeField> ConstraintSystem<Scalar> for KeypairAssembly<Scalar> {
@@ -181,6 +209,89 @@ impl<Scalar: PrimeField> ConstraintSystem<Scalar> for KeypairAssembly<Scalar> {
     }
 }
 
+/// Number of elements computed at once before batch normalization.
+const CHUNK_SIZE: usize = 1024;
+
+/// Fixed-base tables for scalar multiplication of a generator. The `i`-th
+/// table holds the multiples of `2^(i * WINDOW_SIZE) * base`, so that a scalar
+/// multiplication is a single addition per window and needs no doublings.
+struct FixedBaseTable<G: PrimeCurveAffine> {
+    precomp: MultiscalarPrecompOwned<G>,
+}
+
+impl<G: PrimeCurveAffine> FixedBaseTable<G> {
+    fn new(base: G::Curve) -> Self {
+        let num_windows = (G::Scalar::NUM_BITS as usize - 1) / WINDOW_SIZE + 1;
+
+        let mut bases = Vec::with_capacity(num_windows);
+        let mut base = base;
+        for _ in 0..num_windows {
+            bases.push(base);
+            for _ in 0..WINDOW_SIZE {
+                base = base.double();
+            }
+        }
+        let mut bases_affine = vec![G::identity(); num_windows];
+        G::Curve::batch_normalize(&bases, &mut bases_affine);
+
+        FixedBaseTable {
+            precomp: precompute_fixed_window(&bases_affine, WINDOW_SIZE),
+        }
+    }
+
+    fn mul(&self, scalar: &G::Scalar) -> G::Curve {
+        let repr = scalar.to_repr();
+        let repr = repr.as_ref();
+
+        let mut acc = G::Curve::identity();
+        for (i, table) in self.precomp.tables().iter().enumerate() {
+            let bit = i * WINDOW_SIZE;
+            let limb = (bit / 64) * 8;
+            let limb = u64::from_le_bytes(repr[limb..limb + 8].try_into().unwrap());
+            let idx = (limb >> (bit % 64)) & self.precomp.window_mask();
+            if idx > 0 {
+                acc.add_assign(&table[idx as usize - 1]);
+            }
+        }
+
+        acc
+    }
+
+    /// Computes the points for the `len` scalars given by `exp` in parallel
+    /// chunks, batch normalizing each chunk.
+    fn mul_all<F>(&self, len: usize, exp: F) -> Vec<G>
+    where
+        F: Fn(usize) -> G::Scalar + Sync,
+    {
+        let mut affine = vec![G::identity(); len];
+
+        affine
+            .par_chunks_mut(CHUNK_SIZE)
+            .enumerate()
+            .for_each(|(chunk, affine)| {
+                let points: Vec<_> = (0..affine.len())
+                    .map(|i| self.mul(&exp(chunk * CHUNK_SIZE + i)))
+                    .collect();
+                G::Curve::batch_normalize(&points, affine);
+            });
+
+        affine
+    }
+}
+
+/// Evaluates a QAP polynomial at tau, given the Lagrange coefficients for tau.
+fn eval_at_tau<Scalar: PrimeField>(powers_of_tau: &[Scalar], p: &[(Scalar, usize)]) -> Scalar {
+    let mut acc = Scalar::zero();
+
+    for &(ref coeff, index) in p {
+        let mut n = powers_of_tau[index];
+        n.mul_assign(coeff);
+        acc.add_assign(&n);
+    }
+
+    acc
+}
+
 /// Create parameters for a circuit, given some toxic waste.
 #[allow(clippy::too_many_arguments)]
 pub fn generate_parameters<E, C>(
@@ -195,47 +306,17 @@ pub fn generate_parameters<E, C>(
 ) -> Result<Parameters<E>, SynthesisError>
 where
     E: gpu::GpuEngine + MultiMillerLoop,
-    <E as Engine>::G1: WnafGroup,
-    <E as Engine>::G2: WnafGroup,
     C: Circuit<E::Fr>,
 {
-    let mut assembly = KeypairAssembly::new();
-
-    // Allocate the "one" input variable
-    assembly.alloc_input(|| "", || Ok(E::Fr::one()))?;
-
-    // Synthesize the circuit.
-    circuit.synthesize(&mut assembly)?;
-
-    // Input constraints to ensure full density of IC query
-    // x * 0 = 0
-    for i in 0..assembly.num_inputs {
-        assembly.enforce(|| "", |lc| lc + Variable(Index::Input(i)), |lc| lc, |lc| lc);
-    }
+    let assembly = KeypairAssembly::synthesize(circuit)?;
 
     // Create bases for blind evaluation of polynomials at tau
     let powers_of_tau = vec![E::Fr::zero(); assembly.num_constraints];
     let mut powers_of_tau = EvaluationDomain::<E>::from_coeffs(powers_of_tau)?;
 
-    // Compute G1 window table
-    let mut g1_wnaf = Wnaf::new();
-    let g1_wnaf = g1_wnaf.base(g1, {
-        // H query
-        (powers_of_tau.as_ref().len() - 1)
-        // IC/L queries
-        + assembly.num_inputs + assembly.num_aux
-        // A query
-        + assembly.num_inputs + assembly.num_aux
-        // B query
-        + assembly.num_inputs + assembly.num_aux
-    });
-
-    // Compute G2 window table
-    let mut g2_wnaf = Wnaf::new();
-    let g2_wnaf = g2_wnaf.base(g2, {
-        // B query
-        assembly.num_inputs + assembly.num_aux
-    });
+    // Compute fixed-base tables
+    let g1_table = FixedBaseTable::<E::G1Affine>::new(g1);
+    let g2_table = FixedBaseTable::<E::G2Affine>::new(g2);
 
     let gamma_inverse: E::Fr =
         Option::from(gamma.invert()).ok_or(SynthesisError::UnexpectedIdentity)?;
@@ -243,58 +324,33 @@ where
 
     let worker = Worker::new();
 
-    let mut h_affine =
-        vec![<E::G1 as PrimeCurve>::Affine::identity(); powers_of_tau.as_ref().len() - 1];
+    // Compute powers of tau
     {
-        // Compute powers of tau
-        {
-            let powers_of_tau = powers_of_tau.as_mut();
-            worker.scope(powers_of_tau.len(), |scope, chunk| {
-                for (i, powers_of_tau) in powers_of_tau.chunks_mut(chunk).enumerate() {
-                    scope.execute(move || {
-                        let mut current_tau_power = tau.pow_vartime(&[(i * chunk) as u64]);
-
-                        for p in powers_of_tau {
-                            *p = current_tau_power;
-                            current_tau_power.mul_assign(&tau);
-                        }
-                    });
-                }
-            });
-        }
-
-        // coeff = t(x) / delta
-        let mut coeff = powers_of_tau.z(&tau);
-        coeff.mul_assign(&delta_inverse);
-
-        // Compute the H query with multiple threads
-        worker.scope(h_affine.len(), |scope, chunk| {
-            for (h_affine, p) in h_affine
-                .chunks_mut(chunk)
-                .zip(powers_of_tau.as_ref().chunks(chunk))
-            {
-                let mut g1_wnaf = g1_wnaf.shared();
-
+        let powers_of_tau = powers_of_tau.as_mut();
+        worker.scope(powers_of_tau.len(), |scope, chunk| {
+            for (i, powers_of_tau) in powers_of_tau.chunks_mut(chunk).enumerate() {
                 scope.execute(move || {
-                    // Set values of the H query to g1^{(tau^i * t(tau)) / delta}
-                    let h: Vec<_> = p
-                        .iter()
-                        .take(h_affine.len())
-                        .map(|p| {
-                            // Compute final exponent
-                            let exp = *p * coeff;
-
-                            // Exponentiate
-                            g1_wnaf.scalar(&exp)
-                        })
-                        .collect();
-                    // Batch normalize
-                    E::G1::batch_normalize(&h, h_affine);
+                    let mut current_tau_power = tau.pow_vartime(&[(i * chunk) as u64]);
+
+                    for p in powers_of_tau {
+                        *p = current_tau_power;
+                        current_tau_power.mul_assign(&tau);
+                    }
                 });
             }
         });
     }
 
+    // coeff = t(x) / delta
+    let mut coeff = powers_of_tau.z(&tau);
+    coeff.mul_assign(&delta_inverse);
+
+    // Set values of the H query to g1^{(tau^i * t(tau)) / delta}
+    let h_affine = {
+        let powers_of_tau = powers_of_tau.as_ref();
+        g1_table.mul_all(powers_of_tau.len() - 1, |i| powers_of_tau[i] * coeff)
+    };
+
     // Use inverse FFT to convert powers of tau to Lagrange coefficients
     powers_of_tau.ifft(&worker, &mut None)?;
     let powers_of_tau = powers_of_tau.into_coeffs();
@@ -310,9 +366,9 @@ where
 
     #[allow(clippy::too_many_arguments)]
     fn eval<E: Engine>(
-        // wNAF window tables
-        g1_wnaf: &Wnaf<usize, &[E::G1], &mut Vec<i64>>,
-        g2_wnaf: &Wnaf<usize, &[E::G2], &mut Vec<i64>>,
+        // Fixed-base tables
+        g1_table: &FixedBaseTable<E::G1Affine>,
+        g2_table: &FixedBaseTable<E::G2Affine>,
 
         // Lagrange coefficients for tau
         powers_of_tau: &[E::Fr],
@@ -334,9 +390,6 @@ where
         // Trapdoors
         alpha: &E::Fr,
         beta: &E::Fr,
-
-        // Worker
-        worker: &Worker,
     ) {
         // Sanity check
         assert_eq!(a_affine.len(), at.len());
@@ -346,21 +399,17 @@ where
         assert_eq!(a_affine.len(), b_g2_affine.len());
         assert_eq!(a_affine.len(), ext_affine.len());
 
-        // Evaluate polynomials in multiple threads
-        worker.scope(a_affine.len(), |scope, chunk| {
-            for ((((((a_affine, b_g1_affine), b_g2_affine), ext_affine), at), bt), ct) in a_affine
-                .chunks_mut(chunk)
-                .zip(b_g1_affine.chunks_mut(chunk))
-                .zip(b_g2_affine.chunks_mut(chunk))
-                .zip(ext_affine.chunks_mut(chunk))
-                .zip(at.chunks(chunk))
-                .zip(bt.chunks(chunk))
-                .zip(ct.chunks(chunk))
-            {
-                let mut g1_wnaf = g1_wnaf.shared();
-                let mut g2_wnaf = g2_wnaf.shared();
-
-                scope.execute(move || {
+        // Evaluate polynomials in parallel chunks
+        a_affine
+            .par_chunks_mut(CHUNK_SIZE)
+            .zip(b_g1_affine.par_chunks_mut(CHUNK_SIZE))
+            .zip(b_g2_affine.par_chunks_mut(CHUNK_SIZE))
+            .zip(ext_affine.par_chunks_mut(CHUNK_SIZE))
+            .zip(at.par_chunks(CHUNK_SIZE))
+            .zip(bt.par_chunks(CHUNK_SIZE))
+            .zip(ct.par_chunks(CHUNK_SIZE))
+            .for_each(
+                |((((((a_affine, b_g1_affine), b_g2_affine), ext_affine), at), bt), ct)| {
                     let mut a = vec![E::G1::identity(); a_affine.len()];
                     let mut b_g1 = vec![E::G1::identity(); a_affine.len()];
                     let mut b_g2 = vec![E::G2::identity(); a_affine.len()];
@@ -375,21 +424,6 @@ where
                         .zip(bt.iter())
                         .zip(ct.iter())
                     {
-                        fn eval_at_tau<Scalar: PrimeField>(
-                            powers_of_tau: &[Scalar],
-                            p: &[(Scalar, usize)],
-                        ) -> Scalar {
-                            let mut acc = Scalar::zero();
-
-                            for &(ref coeff, index) in p {
-                                let mut n = powers_of_tau[index];
-                                n.mul_assign(coeff);
-                                acc.add_assign(&n);
-                            }
-
-                            acc
-                        }
-
                         // Evaluate QAP polynomials at tau
                         let mut at = eval_at_tau::<E::Fr>(powers_of_tau, at);
                         let mut bt = eval_at_tau::<E::Fr>(powers_of_tau, bt);
@@ -397,13 +431,13 @@ where
 
                         // Compute A query (in G1)
                         if !bool::from(at.is_zero()) {
-                            *a = g1_wnaf.scalar(&at)
+                            *a = g1_table.mul(&at);
                         }
 
                         // Compute B query (in G1/G2)
                         if !bool::from(bt.is_zero()) {
-                            *b_g1 = g1_wnaf.scalar(&bt);
-                            *b_g2 = g2_wnaf.scalar(&bt);
+                            *b_g1 = g1_table.mul(&bt);
+                            *b_g2 = g2_table.mul(&bt);
                         }
 
                         at.mul_assign(beta);
@@ -414,7 +448,7 @@ where
                         e.add_assign(&ct);
                         e.mul_assign(inv);
 
-                        *ext = g1_wnaf.scalar(&e);
+                        *ext = g1_table.mul(&e);
                     }
 
                     // Batch normalize
@@ -422,15 +456,14 @@ where
                     E::G1::batch_normalize(&b_g1, b_g1_affine);
                     E::G2::batch_normalize(&b_g2, b_g2_affine);
                     E::G1::batch_normalize(&ext, ext_affine);
-                });
-            }
-        });
+                },
+            );
     }
 
     // Evaluate for inputs.
     eval::<E>(
-        &g1_wnaf,
-        &g2_wnaf,
+        &g1_table,
+        &g2_table,
         &powers_of_tau,
         &assembly.at_inputs,
         &assembly.bt_inputs,
@@ -442,13 +475,12 @@ where
         &gamma_inverse,
         &alpha,
         &beta,
-        &worker,
     );
 
     // Evaluate for auxiliary variables.
     eval::<E>(
-        &g1_wnaf,
-        &g2_wnaf,
+        &g1_table,
+        &g2_table,
         &powers_of_tau,
         &assembly.at_aux,
         &assembly.bt_aux,
@@ -460,7 +492,6 @@ where
         &delta_inverse,
         &alpha,
         &beta,
-        &worker,
     );
 
     // Don't allow any elements be unconstrained, so that
@@ -510,3 +541,259 @@ where
         ),
     })
 }
+
+/// Create parameters for a circuit, given some toxic waste, and write them to
+/// `writer` in the format expected by [`Parameters::read`].
+///
+/// Unlike [`generate_parameters`], the points of the queries are never held
+/// in memory as a whole: the QAP is evaluated once, keeping only the scalars
+/// of the queries, then each section is computed and written in chunks of at
+/// most `chunk_size` elements, which must not be zero. After every chunk,
+/// `progress` is called with the name of the section, the number of its
+/// elements written so far and its total number of elements. The bytes
+/// written are identical to those of [`Parameters::write`] on the output of
+/// [`generate_parameters`] for the same toxic waste.
+#[allow(clippy::too_many_arguments)]
+pub fn generate_parameters_to_writer<E, C, W, P>(
+    circuit: C,
+    g1: E::G1,
+    g2: E::G2,
+    alpha: E::Fr,
+    beta: E::Fr,
+    gamma: E::Fr,
+    delta: E::Fr,
+    tau: E::Fr,
+    chunk_size: usize,
+    mut writer: W,
+    mut progress: P,
+) -> Result<(), SynthesisError>
+where
+    E: gpu::GpuEngine + MultiMillerLoop,
+    C: Circuit<E::Fr>,
+    W: Write,
+    P: FnMut(&'static str, usize, usize),
+{
+    if chunk_size == 0 {
+        return Err(SynthesisError::IoError(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "chunk size must be positive",
+        )));
+    }
+
+    let assembly = KeypairAssembly::synthesize(circuit)?;
+    let num_inputs = assembly.num_inputs;
+    let num_variables = assembly.num_inputs + assembly.num_aux;
+
+    // Create bases for blind evaluation of polynomials at tau
+    let powers_of_tau = vec![E::Fr::zero(); assembly.num_constraints];
+    let mut powers_of_tau = EvaluationDomain::<E>::from_coeffs(powers_of_tau)?;
+    let h_len = powers_of_tau.as_ref().len() - 1;
+
+    // Compute fixed-base tables
+    let g1_table = FixedBaseTable::<E::G1Affine>::new(g1);
+    let g2_table = FixedBaseTable::<E::G2Affine>::new(g2);
+
+    let gamma_inverse: E::Fr =
+        Option::from(gamma.invert()).ok_or(SynthesisError::UnexpectedIdentity)?;
+    let delta_inverse = Option::from(delta.invert()).ok_or(SynthesisError::UnexpectedIdentity)?;
+
+    let worker = Worker::new();
+
+    // Compute powers of tau. The H query is computed from the powers on the
+    // fly, so only their Lagrange coefficients are kept.
+    {
+        let powers_of_tau = powers_of_tau.as_mut();
+        worker.scope(powers_of_tau.len(), |scope, chunk| {
+            for (i, powers_of_tau) in powers_of_tau.chunks_mut(chunk).enumerate() {
+                scope.execute(move || {
+                    let mut current_tau_power = tau.pow_vartime([(i * chunk) as u64]);
+
+                    for p in powers_of_tau {
+                        *p = current_tau_power;
+                        current_tau_power.mul_assign(&tau);
+                    }
+                });
+            }
+        });
+    }
+
+    // coeff = t(x) / delta
+    let mut coeff = powers_of_tau.z(&tau);
+    coeff.mul_assign(&delta_inverse);
+
+    // Use inverse FFT to convert powers of tau to Lagrange coefficients
+    powers_of_tau.ifft(&worker, &mut None)?;
+    let powers_of_tau = powers_of_tau.into_coeffs();
+
+    // Evaluate the QAP polynomials of every variable once, keeping only the
+    // scalars of the queries: they take a sixth of the memory of the points.
+    let (mut a_exps, (mut b_exps, mut ext_exps)): (Vec<_>, (Vec<_>, Vec<_>)) = (0..num_variables)
+        .into_par_iter()
+        .map(|i| {
+            let (at, bt, ct, inv) = if i < num_inputs {
+                (
+                    &assembly.at_inputs[i],
+                    &assembly.bt_inputs[i],
+                    &assembly.ct_inputs[i],
+                    &gamma_inverse,
+                )
+            } else {
+                let i = i - num_inputs;
+                (
+                    &assembly.at_aux[i],
+                    &assembly.bt_aux[i],
+                    &assembly.ct_aux[i],
+                    &delta_inverse,
+                )
+            };
+            let at = eval_at_tau(&powers_of_tau, at);
+            let bt = eval_at_tau(&powers_of_tau, bt);
+            let ct = eval_at_tau(&powers_of_tau, ct);
+
+            // (beta * u_i(tau) + alpha * v_i(tau) + w_i(tau)) * inv
+            let mut ext = at * beta;
+            ext.add_assign(&(bt * alpha));
+            ext.add_assign(&ct);
+            ext.mul_assign(inv);
+
+            (at, (bt, ext))
+        })
+        .unzip();
+    drop(assembly);
+
+    // IC query for the inputs, L query for the auxiliary variables
+    let l_exps = ext_exps.split_off(num_inputs);
+    let ic_exps = ext_exps;
+
+    // Don't allow any elements be unconstrained, so that
+    // the L query is always fully dense.
+    if l_exps.par_iter().any(|e| bool::from(e.is_zero())) {
+        return Err(SynthesisError::UnconstrainedVariable);
+    }
+
+    // Filter points at infinity away from A/B queries
+    a_exps.retain(|e| !bool::from(e.is_zero()));
+    b_exps.retain(|e| !bool::from(e.is_zero()));
+
+    let g1 = g1.to_affine();
+    let g2 = g2.to_affine();
+
+    let vk = VerifyingKey::<E> {
+        alpha_g1: g1.mul(alpha).to_affine(),
+        beta_g1: g1.mul(beta).to_affine(),
+        beta_g2: g2.mul(beta).to_affine(),
+        gamma_g2: g2.mul(gamma).to_affine(),
+        delta_g1: g1.mul(delta).to_affine(),
+        delta_g2: g2.mul(delta).to_affine(),
+        ic: g1_table.mul_all(ic_exps.len(), |i| ic_exps[i]),
+    };
+    vk.write(&mut writer)?;
+    progress("vk", 1, 1);
+
+    // H query: g1^{(tau^i * t(tau)) / delta}
+    write_query(
+        &mut writer,
+        &g1_table,
+        chunk_size,
+        "h",
+        h_len,
+        |range| {
+            let mut current_tau_power = tau.pow_vartime([range.start as u64]);
+            range
+                .map(|_| {
+                    let exp = current_tau_power * coeff;
+                    current_tau_power.mul_assign(&tau);
+                    exp
+                })
+                .collect()
+        },
+        &mut progress,
+    )?;
+
+    for (section, exps) in [("l", &l_exps), ("a", &a_exps), ("b_g1", &b_exps)].iter() {
+        write_query(
+            &mut writer,
+            &g1_table,
+            chunk_size,
+            section,
+            exps.len(),
+            |range| exps[range].to_vec(),
+            &mut progress,
+        )?;
+    }
+    write_query(
+        &mut writer,
+        &g2_table,
+        chunk_size,
+        "b_g2",
+        b_exps.len(),
+        |range| b_exps[range].to_vec(),
+        &mut progress,
+    )?;
+
+    Ok(())
+}
+
+/// Writes a length-prefixed query of `len` points, `chunk_size` points at a
+/// time. `exps` returns the scalars of the points in the given range.
+#[allow(clippy::too_many_arguments)]
+fn write_query<G, W, P, F>(
+    writer: &mut W,
+    table: &FixedBaseTable<G>,
+    chunk_size: usize,
+    section: &'static str,
+    len: usize,
+    mut exps: F,
+    progress: &mut P,
+) -> Result<(), SynthesisError>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+    W: Write,
+    P: FnMut(&'static str, usize, usize),
+    F: FnMut(Range<usize>) -> Vec<G::Scalar>,
+{
+    writer.write_u32::<BigEndian>(len as u32)?;
+
+    for start in (0..len).step_by(chunk_size) {
+        let end = std::cmp::min(start + chunk_size, len);
+        let chunk = exps(start..end);
+        for g in table.mul_all(chunk.len(), |i| chunk[i]) {
+            writer.write_all(g.to_uncompressed().as_ref())?;
+        }
+        progress(section, end, len);
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test_with_bls12_381 {
+    use super::*;
+
+    use blstrs::{G1Affine, G1Projective, G2Affine, G2Projective, Scalar as Fr};
+    use rand_core::SeedableRng;
+
+    #[test]
+    fn fixed_base_table_matches_scalar_multiplication() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+
+        let g1 = G1Projective::random(&mut rng);
+        let g2 = G2Projective::random(&mut rng);
+        let g1_table = FixedBaseTable::<G1Affine>::new(g1);
+        let g2_table = FixedBaseTable::<G2Affine>::new(g2);
+
+        let scalars: Vec<Fr> = [Fr::zero(), Fr::one(), -Fr::one()]
+            .iter()
+            .copied()
+            .chain((0..CHUNK_SIZE + 7).map(|_| Fr::random(&mut rng)))
+            .collect();
+
+        for s in &scalars[..10] {
+            assert_eq!(g1_table.mul(s), g1 * s);
+            assert_eq!(g2_table.mul(s), g2 * s);
+        }
+
+        let expected: Vec<_> = scalars.iter().map(|s| (g1 * s).to_affine()).collect();
+        assert_eq!(g1_table.mul_all(scalars.len(), |i| scalars[i]), expected);
+    }
+}
@@ -1,29 +1,67 @@
 //! The [Groth16] proving system.
 //!
 //! [Groth16]: https://eprint.iacr.org/2016/260
+//!
+//! Only verification is available without the `groth16` feature, see the
+//! `verifier` feature.
 
 // The `DummyEngine` currently only works on the CPU as G1/G2 is using `Fr` and `Fr` isn't
 // supported by the GPU kernels
-#[cfg(all(test, not(any(feature = "cuda", feature = "opencl"))))]
+#[cfg(all(
+    test,
+    feature = "groth16",
+    not(any(feature = "cuda", feature = "opencl"))
+))]
 mod tests;
 
 pub mod aggregate;
+mod batch_verifier;
+#[cfg(feature = "groth16")]
 mod ext;
+#[cfg(feature = "groth16")]
 mod generator;
+#[cfg(feature = "groth16")]
 mod mapped_params;
+#[cfg(feature = "groth16")]
 mod params;
+#[cfg(feature = "groth16")]
+mod params_check;
+#[cfg(feature = "groth16")]
+mod pread_params;
 mod proof;
+#[cfg(feature = "groth16")]
 mod prover;
+mod serde_impl;
+#[cfg(feature = "snarkjs")]
+mod snarkjs;
+mod solidity;
+#[cfg(feature = "groth16")]
+mod validated_params;
 mod verifier;
 mod verifying_key;
 
 mod multiscalar;
 
+pub use self::batch_verifier::*;
+#[cfg(feature = "groth16")]
 pub use self::ext::*;
+#[cfg(feature = "groth16")]
 pub use self::generator::*;
+#[cfg(feature = "groth16")]
 pub use self::mapped_params::*;
+#[cfg(feature = "groth16")]
 pub use self::params::*;
+#[cfg(feature = "groth16")]
+pub use self::params_check::MalformedParameters;
+#[cfg(feature = "groth16")]
+pub use self::pread_params::*;
 pub use self::proof::*;
+#[cfg(feature = "groth16")]
 pub use self::prover::*;
+#[cfg(feature = "snarkjs")]
+pub use self::snarkjs::*;
+pub use self::solidity::*;
+#[cfg(feature = "groth16")]
+pub use self::validated_params::*;
 pub use self::verifier::*;
 pub use self::verifying_key::*;
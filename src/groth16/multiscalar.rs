@@ -1,9 +1,15 @@
 use std::convert::TryInto;
+use std::io::{self, Read, Write};
+use std::mem;
 use std::ops::AddAssign;
+use std::sync::Arc;
 
+use crate::parallel::prelude::*;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 use ff::PrimeField;
-use group::{prime::PrimeCurveAffine, Curve, Group};
-use rayon::prelude::*;
+use group::{prime::PrimeCurveAffine, Curve, Group, UncompressedEncoding};
+#[cfg(feature = "memmap")]
+use memmap::Mmap;
 
 pub const WINDOW_SIZE: usize = 8;
 
@@ -35,14 +41,16 @@ pub trait MultiscalarPrecomp<G: PrimeCurveAffine>: Send + Sync {
     fn at_point(&self, idx: usize) -> MultiscalarPrecompRef<'_, G>;
 }
 
-/// Owned variant of the multiscalar precomputations.
+/// Owned variant of the multiscalar precomputations. The tables are shared
+/// with the precomputations returned by `prefix`, they are the first
+/// `num_points` tables of the shared ones.
 #[derive(Clone, Debug)]
 pub struct MultiscalarPrecompOwned<G: PrimeCurveAffine> {
     num_points: usize,
     window_size: usize,
     window_mask: u64,
     table_entries: usize,
-    tables: Vec<Vec<G>>,
+    tables: Arc<Vec<Vec<G>>>,
 }
 
 impl<G: PrimeCurveAffine> PartialEq for MultiscalarPrecompOwned<G> {
@@ -52,9 +60,9 @@ impl<G: PrimeCurveAffine> PartialEq for MultiscalarPrecompOwned<G> {
             && self.window_mask == other.window_mask
             && self.table_entries == other.table_entries
             && self
-                .tables
+                .tables()
                 .par_iter()
-                .zip(other.tables.par_iter())
+                .zip(other.tables().par_iter())
                 .all(|(a, b)| a == b)
     }
 }
@@ -69,7 +77,7 @@ impl<G: PrimeCurveAffine> MultiscalarPrecomp<G> for MultiscalarPrecompOwned<G> {
     }
 
     fn tables(&self) -> &[Vec<G>] {
-        &self.tables
+        &self.tables[..self.num_points]
     }
 
     fn at_point(&self, idx: usize) -> MultiscalarPrecompRef<'_, G> {
@@ -78,11 +86,203 @@ impl<G: PrimeCurveAffine> MultiscalarPrecomp<G> for MultiscalarPrecompOwned<G> {
             window_size: self.window_size,
             window_mask: self.window_mask,
             table_entries: self.table_entries,
-            tables: &self.tables[idx..],
+            tables: &self.tables()[idx..],
+        }
+    }
+}
+
+impl<G: PrimeCurveAffine> MultiscalarPrecompOwned<G> {
+    /// Returns the precomputations of the first `num_points` points, sharing
+    /// the tables with these ones. It returns `None` if there are fewer
+    /// points.
+    pub fn prefix(&self, num_points: usize) -> Option<Self> {
+        if num_points > self.num_points {
+            return None;
+        }
+        Some(MultiscalarPrecompOwned {
+            num_points,
+            tables: self.tables.clone(),
+            ..*self
+        })
+    }
+
+    /// Returns the precomputations of these points followed by `points`. The
+    /// tables are moved if they are not shared, and copied otherwise.
+    pub fn extend(self, points: &[G]) -> Self {
+        let num_points = self.num_points;
+        let mut tables =
+            Arc::try_unwrap(self.tables).unwrap_or_else(|tables| tables[..num_points].to_vec());
+        tables.truncate(num_points);
+        tables.extend(precompute_tables(points, self.table_entries));
+        MultiscalarPrecompOwned {
+            num_points: tables.len(),
+            tables: Arc::new(tables),
+            ..self
+        }
+    }
+
+    /// Drops the tables past the first `num_points` points. The memory is
+    /// released if the tables are not shared.
+    pub fn truncate(&mut self, num_points: usize) {
+        if num_points >= self.num_points {
+            return;
+        }
+        self.num_points = num_points;
+        if let Some(tables) = Arc::get_mut(&mut self.tables) {
+            tables.truncate(num_points);
+            tables.shrink_to_fit();
+        }
+    }
+
+    /// Returns the number of bytes of the tables of these precomputations,
+    /// not counting the tables of the longer precomputations they share
+    /// them with.
+    pub fn size_in_bytes(&self) -> usize {
+        self.num_points * self.table_entries * mem::size_of::<G>()
+    }
+}
+
+impl<G> MultiscalarPrecompOwned<G>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+{
+    /// Serializes the precomputed tables. Points are written uncompressed, so
+    /// reading them back does not require any square root computation.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_u32::<BigEndian>(self.window_size as u32)?;
+        writer.write_u32::<BigEndian>(self.num_points as u32)?;
+        for table in self.tables() {
+            for point in table {
+                writer.write_all(point.to_uncompressed().as_ref())?;
+            }
         }
+
+        Ok(())
+    }
+
+    /// Reads tables written by `write`. When `checked` is false, the table
+    /// entries are not validated at all, which is only safe for trusted data
+    /// or when `is_consistent_with` is run afterwards.
+    pub fn read<R: Read>(mut reader: R, checked: bool) -> io::Result<Self> {
+        let window_size = read_window_size(&mut reader)?;
+        let num_points = reader.read_u32::<BigEndian>()? as usize;
+        let table_entries = (1 << window_size) - 1;
+        let point_len = mem::size_of::<G::Uncompressed>();
+
+        let mut buffer = vec![0u8; table_entries * point_len];
+        // the number of points is not trusted, so the tables grow as they are
+        // read instead of being allocated upfront
+        let mut tables = Vec::new();
+        for _ in 0..num_points {
+            reader.read_exact(&mut buffer)?;
+            tables.push(decode_points(&buffer, checked)?);
+        }
+
+        Ok(MultiscalarPrecompOwned {
+            num_points,
+            window_size,
+            window_mask: (1 << window_size) - 1,
+            table_entries,
+            tables: Arc::new(tables),
+        })
+    }
+
+    /// Same as `read`, but decodes the tables in parallel from a memory map,
+    /// starting at `offset`. The offset is advanced past the tables.
+    #[cfg(feature = "memmap")]
+    pub fn read_mmap(mmap: &Mmap, offset: &mut usize, checked: bool) -> io::Result<Self> {
+        let mut header = mmap_slice(mmap, *offset, 2 * mem::size_of::<u32>())?;
+        let window_size = read_window_size(&mut header)?;
+        let num_points = header.read_u32::<BigEndian>()? as usize;
+        *offset += 2 * mem::size_of::<u32>();
+
+        let table_entries = (1 << window_size) - 1;
+        let table_len = table_entries * mem::size_of::<G::Uncompressed>();
+        let data = mmap_slice(mmap, *offset, num_points * table_len)?;
+
+        let tables = data
+            .par_chunks(table_len)
+            .map(|table| decode_points(table, checked))
+            .collect::<io::Result<Vec<_>>>()?;
+        *offset += num_points * table_len;
+
+        Ok(MultiscalarPrecompOwned {
+            num_points,
+            window_size,
+            window_mask: (1 << window_size) - 1,
+            table_entries,
+            tables: Arc::new(tables),
+        })
+    }
+
+    /// Returns true if these tables are exactly the ones `precompute_fixed_window`
+    /// derives from `points`. This is much cheaper than recomputing the tables,
+    /// as it avoids converting every entry to affine form.
+    pub fn is_consistent_with(&self, points: &[G]) -> bool {
+        self.num_points == points.len()
+            && self.tables.len() >= points.len()
+            && self.window_mask == (1 << self.window_size) - 1
+            && self.table_entries == (1 << self.window_size) - 1
+            && self
+                .tables()
+                .par_iter()
+                .zip(points.par_iter())
+                .all(|(table, point)| {
+                    if table.len() != self.table_entries {
+                        return false;
+                    }
+                    let mut cur = G::Curve::identity();
+                    table.iter().all(|entry| {
+                        cur.add_assign(point);
+                        cur == entry.to_curve()
+                    })
+                })
     }
 }
 
+fn read_window_size<R: Read>(mut reader: R) -> io::Result<usize> {
+    let window_size = reader.read_u32::<BigEndian>()? as usize;
+    // `multiscalar` only supports windows that evenly divide a limb, anything
+    // above 16 bits would result in absurdly large tables.
+    if window_size == 0 || window_size > 16 || 64 % window_size != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("invalid multiscalar window size {}", window_size),
+        ));
+    }
+    Ok(window_size)
+}
+
+#[cfg(feature = "memmap")]
+fn mmap_slice(mmap: &Mmap, offset: usize, len: usize) -> io::Result<&[u8]> {
+    mmap.get(offset..offset + len).ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "multiscalar tables exceed the mapped data",
+        )
+    })
+}
+
+fn decode_points<G>(bytes: &[u8], checked: bool) -> io::Result<Vec<G>>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+{
+    bytes
+        .par_chunks(mem::size_of::<G::Uncompressed>())
+        .map(|chunk| {
+            let mut repr = G::Uncompressed::default();
+            repr.as_mut().copy_from_slice(chunk);
+            let opt = if checked {
+                G::from_uncompressed(&repr)
+            } else {
+                G::from_uncompressed_unchecked(&repr)
+            };
+            Option::from(opt)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not on curve"))
+        })
+        .collect()
+}
+
 /// Referenced version of the multiscalar precomputations.
 #[derive(Debug)]
 pub struct MultiscalarPrecompRef<'a, G: PrimeCurveAffine> {
@@ -124,8 +324,19 @@ pub fn precompute_fixed_window<G: PrimeCurveAffine>(
 ) -> MultiscalarPrecompOwned<G> {
     let table_entries = (1 << window_size) - 1;
     let num_points = points.len();
+    let tables = precompute_tables(points, table_entries);
+
+    MultiscalarPrecompOwned {
+        num_points,
+        window_size,
+        window_mask: (1 << window_size) - 1,
+        table_entries,
+        tables: Arc::new(tables),
+    }
+}
 
-    let tables = points
+fn precompute_tables<G: PrimeCurveAffine>(points: &[G], table_entries: usize) -> Vec<Vec<G>> {
+    points
         .into_par_iter()
         .map(|point| {
             let mut table = Vec::with_capacity(table_entries);
@@ -140,15 +351,7 @@ pub fn precompute_fixed_window<G: PrimeCurveAffine>(
 
             table
         })
-        .collect();
-
-    MultiscalarPrecompOwned {
-        num_points,
-        window_size,
-        window_mask: (1 << window_size) - 1,
-        table_entries,
-        tables,
-    }
+        .collect()
 }
 
 /// Multipoint scalar multiplication
@@ -377,4 +580,63 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_precomp_prefix() {
+        let mut rng = XorShiftRng::from_seed([
+            0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
+            0xbc, 0xe5,
+        ]);
+        let points: Vec<G1Affine> = (0..20)
+            .map(|_| G1Projective::random(&mut rng).to_affine())
+            .collect();
+        let scalars: Vec<<Fr as PrimeField>::Repr> =
+            (0..8).map(|_| Fr::random(&mut rng).to_repr()).collect();
+
+        let table = precompute_fixed_window::<G1Affine>(&points, 4);
+        let small_table = precompute_fixed_window::<G1Affine>(&points[..8], 4);
+        let prefix = table.prefix(8).unwrap();
+        assert!(prefix == small_table);
+        assert!(table.prefix(21).is_none());
+        assert_eq!(
+            prefix.size_in_bytes(),
+            8 * 15 * std::mem::size_of::<G1Affine>()
+        );
+        assert_eq!(
+            multiscalar::<G1Affine>(&scalars, &prefix, 256),
+            multiscalar_naive(&points[..8], &scalars)
+        );
+
+        // extending a shared prefix copies it
+        assert!(prefix.clone().extend(&points[8..]) == table);
+        assert!(small_table.extend(&points[8..]) == table);
+
+        let mut truncated = table.clone();
+        truncated.truncate(8);
+        assert!(truncated == prefix);
+        // the tables are only dropped once they are not shared
+        drop((prefix, truncated));
+        let mut table = table;
+        table.truncate(4);
+        assert_eq!(table.tables.len(), 4);
+        assert_eq!(
+            table.size_in_bytes(),
+            4 * 15 * std::mem::size_of::<G1Affine>()
+        );
+    }
+
+    #[test]
+    fn test_precomp_read_truncated() {
+        let points = vec![G1Affine::generator(); 2];
+        let mut buffer = Vec::new();
+        precompute_fixed_window::<G1Affine>(&points, 4)
+            .write(&mut buffer)
+            .unwrap();
+        MultiscalarPrecompOwned::<G1Affine>::read(&buffer[..], true).unwrap();
+
+        // a header claiming far more points than the data holds must fail on
+        // the missing data rather than allocate for them
+        buffer[4..8].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(MultiscalarPrecompOwned::<G1Affine>::read(&buffer[..], true).is_err());
+    }
 }
@@ -0,0 +1,465 @@
+use std::io;
+use std::ops::AddAssign;
+
+use ff::{Field, PrimeField};
+use group::{prime::PrimeCurveAffine, Curve, Group};
+use pairing::{MillerLoopResult, MultiMillerLoop};
+use rand_core::RngCore;
+use rayon::prelude::*;
+
+use super::generator::KeypairAssembly;
+use super::Parameters;
+use crate::{Circuit, SynthesisError};
+
+/// Error returned by [`Parameters::verify_against_circuit`] when the
+/// parameters are inconsistent with the circuit. It is wrapped in a
+/// [`SynthesisError::IoError`] of kind [`io::ErrorKind::InvalidData`], from
+/// which it can be recovered with `get_ref` and `downcast_ref`.
+#[derive(thiserror::Error, Debug)]
+#[error("malformed parameters in section {section}: {reason}")]
+pub struct MalformedParameters {
+    /// The first inconsistent section, e.g. `"l"` or `"vk.ic"`.
+    pub section: &'static str,
+    pub reason: String,
+}
+
+impl<E> Parameters<E>
+where
+    E: MultiMillerLoop,
+{
+    /// Checks that these parameters are well-formed for `circuit`, for example
+    /// before proving with parameters obtained from an untrusted source.
+    ///
+    /// The QAP is re-derived from the circuit the same way the parameter
+    /// generator does, and the size and density of every section is compared
+    /// against it. The relations between the sections are then checked with
+    /// randomized pairing checks:
+    ///
+    /// - `beta` and `delta` are consistent between G1 and G2,
+    /// - `b_g1` and `b_g2` encode the same evaluations,
+    /// - every `vk.ic` and `l` element whose variable does not appear in the
+    ///   "C" polynomials is exactly `(beta * u_i(tau) + alpha * v_i(tau)) / gamma`
+    ///   (respectively `/ delta`) of the matching `a` and `b_g2` elements.
+    ///
+    /// A Groth16 CRS carries no powers of tau in G2, so the `h` query and the
+    /// "C" part of the `vk.ic`/`l` elements can only be checked for their
+    /// shape. For the same reason, the `a` and `b_g1`/`b_g2` elements of the
+    /// variables which appear in the "C" polynomials are not checked against
+    /// the QAP: a corrupted `a` element of such a variable is not detected.
+    /// The points themselves are expected to have been decoded with subgroup
+    /// checks, e.g. through [`Parameters::read`] with `checked` set.
+    ///
+    /// On failure a [`MalformedParameters`] error names the first inconsistent
+    /// section.
+    pub fn verify_against_circuit<C, R>(
+        &self,
+        circuit: C,
+        rng: &mut R,
+    ) -> Result<(), SynthesisError>
+    where
+        C: Circuit<E::Fr>,
+        R: RngCore,
+    {
+        let assembly = KeypairAssembly::synthesize(circuit)?;
+
+        // Recompute the density of the A/B queries: the generator omits
+        // variables whose polynomials are zero.
+        let a_inputs = nonzero_positions(&assembly.at_inputs, 0);
+        let a_aux = nonzero_positions(&assembly.at_aux, count(&a_inputs));
+        let b_inputs = nonzero_positions(&assembly.bt_inputs, 0);
+        let b_aux = nonzero_positions(&assembly.bt_aux, count(&b_inputs));
+
+        let domain_size = domain_size::<E::Fr>(assembly.num_constraints)?;
+
+        check_len("vk.ic", self.vk.ic.len(), assembly.num_inputs)?;
+        check_len("h", self.h.len(), domain_size - 1)?;
+        check_len("l", self.l.len(), assembly.num_aux)?;
+        check_len("a", self.a.len(), count(&a_aux))?;
+        check_len("b_g1", self.b_g1.len(), count(&b_aux))?;
+        check_len("b_g2", self.b_g2.len(), count(&b_aux))?;
+
+        let vk = &self.vk;
+        if bool::from(
+            vk.alpha_g1.is_identity()
+                | vk.beta_g1.is_identity()
+                | vk.delta_g1.is_identity()
+                | vk.beta_g2.is_identity()
+                | vk.gamma_g2.is_identity()
+                | vk.delta_g2.is_identity(),
+        ) {
+            return Err(malformed("vk", "identity element"));
+        }
+        check_no_identity("vk.ic", &vk.ic)?;
+        check_no_identity("h", &self.h)?;
+        check_no_identity("l", &self.l)?;
+        check_no_identity("a", &self.a)?;
+        check_no_identity("b_g1", &self.b_g1)?;
+        check_no_identity("b_g2", &self.b_g2)?;
+
+        // e(beta_g1, delta_g2) = e(delta_g1, beta_g2)
+        if !is_identity::<E>(&[(&vk.beta_g1, &vk.delta_g2), (&-vk.delta_g1, &vk.beta_g2)]) {
+            return Err(malformed(
+                "vk",
+                "beta and delta are inconsistent between G1 and G2",
+            ));
+        }
+
+        // e(sum r_i * b_g1_i, beta_g2) = e(beta_g1, sum r_i * b_g2_i)
+        let r = random_scalars::<E::Fr, _>(self.b_g1.len(), rng);
+        let b_g1 = linear_combination(&self.b_g1, &r);
+        let b_g2 = linear_combination(&self.b_g2, &r);
+        if !is_identity::<E>(&[(&b_g1, &vk.beta_g2), (&-vk.beta_g1, &b_g2)]) {
+            return Err(malformed(
+                "b_g1/b_g2",
+                "b_g1 and b_g2 encode different evaluations",
+            ));
+        }
+
+        self.check_ext(
+            "vk.ic",
+            &vk.ic,
+            &vk.gamma_g2,
+            &assembly.ct_inputs,
+            &a_inputs,
+            &b_inputs,
+            rng,
+        )?;
+        self.check_ext(
+            "l",
+            &self.l,
+            &vk.delta_g2,
+            &assembly.ct_aux,
+            &a_aux,
+            &b_aux,
+            rng,
+        )?;
+
+        Ok(())
+    }
+
+    /// Checks `e(ext_i, inv_g2) = e(a_i, beta_g2) * e(alpha_g1, b_g2_i)` for
+    /// all variables of `ext` with a zero "C" polynomial, batched with random
+    /// coefficients.
+    #[allow(clippy::too_many_arguments)]
+    fn check_ext<R: RngCore>(
+        &self,
+        section: &'static str,
+        ext: &[E::G1Affine],
+        inv_g2: &E::G2Affine,
+        ct: &[Vec<(E::Fr, usize)>],
+        a_positions: &[Option<usize>],
+        b_positions: &[Option<usize>],
+        rng: &mut R,
+    ) -> Result<(), SynthesisError> {
+        let checked: Vec<usize> = ct
+            .iter()
+            .enumerate()
+            .filter(|(_, ct)| is_zero_polynomial(ct))
+            .map(|(i, _)| i)
+            .collect();
+        if checked.is_empty() {
+            return Ok(());
+        }
+        let r = random_scalars::<E::Fr, _>(checked.len(), rng);
+
+        let (ext_points, ext_scalars): (Vec<_>, Vec<_>) = checked
+            .iter()
+            .zip(r.iter())
+            .map(|(&i, r)| (ext[i], *r))
+            .unzip();
+        let (a_points, a_scalars): (Vec<_>, Vec<_>) = checked
+            .iter()
+            .zip(r.iter())
+            .filter_map(|(&i, r)| a_positions[i].map(|pos| (self.a[pos], *r)))
+            .unzip();
+        let (b_points, b_scalars): (Vec<_>, Vec<_>) = checked
+            .iter()
+            .zip(r.iter())
+            .filter_map(|(&i, r)| b_positions[i].map(|pos| (self.b_g2[pos], *r)))
+            .unzip();
+
+        let ext = linear_combination(&ext_points, &ext_scalars);
+        let a = linear_combination(&a_points, &a_scalars);
+        let b = linear_combination(&b_points, &b_scalars);
+
+        if !is_identity::<E>(&[
+            (&ext, inv_g2),
+            (&-a, &self.vk.beta_g2),
+            (&-self.vk.alpha_g1, &b),
+        ]) {
+            return Err(malformed(
+                section,
+                "does not match the QAP evaluations in a and b_g2",
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+fn malformed(section: &'static str, reason: &str) -> SynthesisError {
+    let err = MalformedParameters {
+        section,
+        reason: reason.to_string(),
+    };
+    SynthesisError::IoError(io::Error::new(io::ErrorKind::InvalidData, err))
+}
+
+fn check_len(section: &'static str, actual: usize, expected: usize) -> Result<(), SynthesisError> {
+    if actual != expected {
+        return Err(malformed(
+            section,
+            &format!("expected {} elements, found {}", expected, actual),
+        ));
+    }
+    Ok(())
+}
+
+fn check_no_identity<G: PrimeCurveAffine>(
+    section: &'static str,
+    points: &[G],
+) -> Result<(), SynthesisError> {
+    match points
+        .par_iter()
+        .position_any(|p| bool::from(p.is_identity()))
+    {
+        Some(i) => Err(malformed(
+            section,
+            &format!("identity element at position {}", i),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Size of the evaluation domain the generator uses for `num_constraints`.
+fn domain_size<Scalar: PrimeField>(num_constraints: usize) -> Result<usize, SynthesisError> {
+    let mut m = 1;
+    let mut exp = 0;
+    while m < num_constraints {
+        m *= 2;
+        exp += 1;
+
+        if exp >= Scalar::S {
+            return Err(SynthesisError::PolynomialDegreeTooLarge);
+        }
+    }
+    Ok(m)
+}
+
+/// Returns whether the polynomial given by its Lagrange coefficients is zero,
+/// merging coefficients that refer to the same constraint.
+fn is_zero_polynomial<Scalar: PrimeField>(p: &[(Scalar, usize)]) -> bool {
+    let mut p = p.to_vec();
+    p.sort_unstable_by_key(|&(_, index)| index);
+
+    let mut acc = Scalar::zero();
+    let mut current = None;
+    for (coeff, index) in p {
+        if current != Some(index) {
+            if !bool::from(acc.is_zero()) {
+                return false;
+            }
+            current = Some(index);
+            acc = Scalar::zero();
+        }
+        acc.add_assign(&coeff);
+    }
+    bool::from(acc.is_zero())
+}
+
+/// Positions of the variables' evaluations in a filtered query, starting at
+/// `offset`; `None` for variables whose polynomial is zero.
+fn nonzero_positions<Scalar: PrimeField>(
+    polynomials: &[Vec<(Scalar, usize)>],
+    offset: usize,
+) -> Vec<Option<usize>> {
+    let mut next = offset;
+    polynomials
+        .iter()
+        .map(|p| {
+            if is_zero_polynomial(p) {
+                None
+            } else {
+                next += 1;
+                Some(next - 1)
+            }
+        })
+        .collect()
+}
+
+/// Number of elements in the filtered query up to and including `positions`.
+fn count(positions: &[Option<usize>]) -> usize {
+    positions
+        .iter()
+        .rev()
+        .find_map(|p| p.map(|p| p + 1))
+        .unwrap_or(0)
+}
+
+fn random_scalars<F: Field, R: RngCore>(n: usize, rng: &mut R) -> Vec<F> {
+    (0..n).map(|_| F::random(&mut *rng)).collect()
+}
+
+fn linear_combination<G: PrimeCurveAffine>(points: &[G], scalars: &[G::Scalar]) -> G
+where
+    G::Curve: Send,
+{
+    points
+        .par_iter()
+        .zip(scalars.par_iter())
+        .map(|(p, r)| p.mul(*r))
+        .reduce(G::Curve::identity, |mut acc, p| {
+            acc.add_assign(&p);
+            acc
+        })
+        .to_affine()
+}
+
+fn is_identity<E: MultiMillerLoop>(terms: &[(&E::G1Affine, &E::G2Affine)]) -> bool {
+    let prepared: Vec<E::G2Prepared> = terms.iter().map(|(_, g2)| (**g2).into()).collect();
+    let terms: Vec<_> = terms
+        .iter()
+        .zip(prepared.iter())
+        .map(|((g1, _), g2)| (*g1, g2))
+        .collect();
+
+    bool::from(
+        E::multi_miller_loop(&terms)
+            .final_exponentiation()
+            .is_identity(),
+    )
+}
+
+#[cfg(test)]
+mod test_with_bls12_381 {
+    use super::*;
+    use crate::groth16::generate_random_parameters;
+    use crate::{ConstraintSystem, SynthesisError};
+
+    use blstrs::{Bls12, G1Affine, G2Affine, Scalar as Fr};
+    use rand_core::SeedableRng;
+    use std::sync::Arc;
+
+    // a * b = c, with a and b private and c public
+    #[derive(Clone)]
+    struct MultiplyCircuit;
+
+    impl Circuit<Fr> for MultiplyCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let a = cs.alloc(|| "a", || Ok(Fr::from(3u64)))?;
+            let b = cs.alloc(|| "b", || Ok(Fr::from(4u64)))?;
+            let c = cs.alloc_input(|| "c", || Ok(Fr::from(12u64)))?;
+            let d = cs.alloc(|| "d", || Ok(Fr::from(7u64)))?;
+
+            cs.enforce(|| "a*b=c", |lc| lc + a, |lc| lc + b, |lc| lc + c);
+            cs.enforce(
+                || "(a+b)*1=d",
+                |lc| lc + a + b,
+                |lc| lc + CS::one(),
+                |lc| lc + d,
+            );
+            // d appears in both the "A" and "C" polynomials
+            let e = cs.alloc(|| "e", || Ok(Fr::from(49u64)))?;
+            cs.enforce(|| "d*d=e", |lc| lc + d, |lc| lc + d, |lc| lc + e);
+
+            Ok(())
+        }
+    }
+
+    fn section(res: Result<(), SynthesisError>) -> &'static str {
+        match res {
+            Err(SynthesisError::IoError(err)) => {
+                err.get_ref()
+                    .and_then(|err| err.downcast_ref::<MalformedParameters>())
+                    .expect("not a malformed parameters error")
+                    .section
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn verify_parameters_against_circuit() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let params = generate_random_parameters::<Bls12, _, _>(MultiplyCircuit, &mut rng).unwrap();
+
+        params
+            .verify_against_circuit(MultiplyCircuit, &mut rng)
+            .unwrap();
+
+        let g1 = G1Affine::generator();
+        let g2 = G2Affine::generator();
+
+        let mut p = params.clone();
+        p.vk.delta_g1 = (p.vk.delta_g1.to_curve() + g1).to_affine();
+        assert_eq!(
+            section(p.verify_against_circuit(MultiplyCircuit, &mut rng)),
+            "vk"
+        );
+
+        let mut p = params.clone();
+        let mut l = p.l.to_vec();
+        l[0] = (l[0].to_curve() + g1).to_affine();
+        p.l = Arc::new(l);
+        assert_eq!(
+            section(p.verify_against_circuit(MultiplyCircuit, &mut rng)),
+            "l"
+        );
+
+        let mut p = params.clone();
+        let mut ic = p.vk.ic.to_vec();
+        ic[0] = (ic[0].to_curve() + g1).to_affine();
+        p.vk.ic = ic;
+        assert_eq!(
+            section(p.verify_against_circuit(MultiplyCircuit, &mut rng)),
+            "vk.ic"
+        );
+
+        let mut p = params.clone();
+        let mut b_g2 = p.b_g2.to_vec();
+        b_g2[0] = (b_g2[0].to_curve() + g2).to_affine();
+        p.b_g2 = Arc::new(b_g2);
+        assert_eq!(
+            section(p.verify_against_circuit(MultiplyCircuit, &mut rng)),
+            "b_g1/b_g2"
+        );
+
+        let mut p = params.clone();
+        let mut h = p.h.to_vec();
+        h.pop();
+        p.h = Arc::new(h);
+        assert_eq!(
+            section(p.verify_against_circuit(MultiplyCircuit, &mut rng)),
+            "h"
+        );
+
+        let mut p = params.clone();
+        let mut a = p.a.to_vec();
+        a[0] = G1Affine::identity();
+        p.a = Arc::new(a);
+        assert_eq!(
+            section(p.verify_against_circuit(MultiplyCircuit, &mut rng)),
+            "a"
+        );
+
+        // the "a" query starts with the two inputs, then a, b and d; the
+        // element of a, which is not in the "C" polynomials, is checked
+        let mut p = params.clone();
+        let mut a = p.a.to_vec();
+        assert_eq!(a.len(), 5);
+        a[2] = (a[2].to_curve() + g1).to_affine();
+        p.a = Arc::new(a);
+        assert_eq!(
+            section(p.verify_against_circuit(MultiplyCircuit, &mut rng)),
+            "l"
+        );
+
+        // the "a" element of d, which is in the "C" polynomials, can't be
+        // checked without powers of tau in G2
+        let mut p = params;
+        let mut a = p.a.to_vec();
+        a[4] = (a[4].to_curve() + g1).to_affine();
+        p.a = Arc::new(a);
+        p.verify_against_circuit(MultiplyCircuit, &mut rng).unwrap();
+    }
+}
@@ -0,0 +1,453 @@
+use group::{prime::PrimeCurveAffine, UncompressedEncoding};
+use pairing::MultiMillerLoop;
+
+use crate::multiexp::{Source, SourceBuilder};
+use crate::SynthesisError;
+
+use byteorder::{BigEndian, ByteOrder};
+use std::fs::File;
+use std::io::{self, Seek};
+use std::marker::PhantomData;
+use std::mem;
+use std::ops::AddAssign;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+
+use super::{ParameterSource, VerifyingKey};
+
+/// Default number of points read from the file at once by a source.
+pub const DEFAULT_PREAD_BUFFER_LEN: usize = 1 << 14;
+
+/// Default number of open file handles kept around for reuse.
+pub const DEFAULT_FILE_CACHE_SIZE: usize = 8;
+
+/// Groth16 parameters that are streamed from disk with positioned reads.
+///
+/// Only the verifying key and the position of each section are kept in
+/// memory. The points are read into a small reusable buffer by each source
+/// handed to `multiexp`, so memory usage does not grow with the size of the
+/// parameters, and no mapping of the file is required.
+///
+/// Every `multiexp` window streams its bases separately, so the points are
+/// decoded several times per proof. When `checked` is set, each of those
+/// decodings performs the subgroup checks.
+pub struct PreadParameters<E>
+where
+    E: MultiMillerLoop,
+{
+    /// The parameter file we're reading from.
+    pub param_file_path: PathBuf,
+
+    /// This is always loaded (i.e. not lazily loaded).
+    pub vk: VerifyingKey<E>,
+
+    /// Elements of the form ((tau^i * t(tau)) / delta) for i between 0 and
+    /// m-2 inclusive. Never contains points at infinity.
+    pub h: Section,
+
+    /// Elements of the form (beta * u_i(tau) + alpha v_i(tau) + w_i(tau)) / delta
+    /// for all auxiliary inputs. Variables can never be unconstrained, so this
+    /// never contains points at infinity.
+    pub l: Section,
+
+    /// QAP "A" polynomials evaluated at tau in the Lagrange basis. Never contains
+    /// points at infinity: polynomials that evaluate to zero are omitted from
+    /// the CRS and the prover can deterministically skip their evaluation.
+    pub a: Section,
+
+    /// QAP "B" polynomials evaluated at tau in the Lagrange basis. Needed in
+    /// G1 and G2 for C/B queries, respectively. Never contains points at
+    /// infinity for the same reason as the "A" polynomials.
+    pub b_g1: Section,
+    pub b_g2: Section,
+
+    pub checked: bool,
+
+    /// Number of points read from the file at once by a source.
+    pub buffer_len: usize,
+
+    files: Arc<FileCache>,
+}
+
+/// Location of a section of points in the parameter file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Section {
+    /// Offset of the first point, in bytes.
+    pub offset: u64,
+    /// Number of points.
+    pub len: usize,
+}
+
+impl<E> PreadParameters<E>
+where
+    E: MultiMillerLoop,
+{
+    /// Reads the verifying key and the section offsets of the parameters at
+    /// `param_file_path`, using the default buffer length and file cache size.
+    pub fn build(param_file_path: PathBuf, checked: bool) -> io::Result<Self> {
+        let mut file = File::open(&param_file_path)?;
+        let file_len = file.metadata()?.len();
+
+        let vk = VerifyingKey::<E>::read(&mut file)?;
+        let mut offset = file.stream_position()?;
+
+        let g1_len = mem::size_of::<<E::G1Affine as UncompressedEncoding>::Uncompressed>();
+        let g2_len = mem::size_of::<<E::G2Affine as UncompressedEncoding>::Uncompressed>();
+
+        let mut read_section = |point_len: usize| -> io::Result<Section> {
+            let mut raw_len = [0u8; 4];
+            read_exact_at(&file, &mut raw_len, offset)?;
+            let len = BigEndian::read_u32(&raw_len) as usize;
+
+            let section = Section {
+                offset: offset + raw_len.len() as u64,
+                len,
+            };
+            offset = section.offset + (len * point_len) as u64;
+            if offset > file_len {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "parameter file is truncated",
+                ));
+            }
+
+            Ok(section)
+        };
+
+        let h = read_section(g1_len)?;
+        let l = read_section(g1_len)?;
+        let a = read_section(g1_len)?;
+        let b_g1 = read_section(g1_len)?;
+        let b_g2 = read_section(g2_len)?;
+
+        let files = Arc::new(FileCache::new(
+            param_file_path.clone(),
+            DEFAULT_FILE_CACHE_SIZE,
+        ));
+        files.put(file);
+
+        Ok(PreadParameters {
+            param_file_path,
+            vk,
+            h,
+            l,
+            a,
+            b_g1,
+            b_g2,
+            checked,
+            buffer_len: DEFAULT_PREAD_BUFFER_LEN,
+            files,
+        })
+    }
+
+    /// Sets the number of open file handles kept for reuse by the sources.
+    /// With a size of zero, every source opens and closes its own handle.
+    pub fn with_file_cache_size(mut self, size: usize) -> Self {
+        self.files = Arc::new(FileCache::new(self.param_file_path.clone(), size));
+        self
+    }
+
+    /// Sets the number of points read from the file at once by a source.
+    pub fn with_buffer_len(mut self, buffer_len: usize) -> Self {
+        assert!(buffer_len > 0, "buffer length must be positive");
+        self.buffer_len = buffer_len;
+        self
+    }
+
+    fn builder<G>(&self, section: Section, start: usize) -> PreadSourceBuilder<G> {
+        PreadSourceBuilder {
+            files: self.files.clone(),
+            section,
+            start,
+            checked: self.checked,
+            buffer_len: self.buffer_len,
+            _g: PhantomData,
+        }
+    }
+}
+
+impl<E> ParameterSource<E> for &PreadParameters<E>
+where
+    E: MultiMillerLoop,
+{
+    type G1Builder = PreadSourceBuilder<E::G1Affine>;
+    type G2Builder = PreadSourceBuilder<E::G2Affine>;
+
+    fn get_vk(&self, _: usize) -> Result<&VerifyingKey<E>, SynthesisError> {
+        Ok(&self.vk)
+    }
+
+    fn get_h(&self, _num_h: usize) -> Result<Self::G1Builder, SynthesisError> {
+        Ok(self.builder(self.h, 0))
+    }
+
+    fn get_l(&self, _num_l: usize) -> Result<Self::G1Builder, SynthesisError> {
+        Ok(self.builder(self.l, 0))
+    }
+
+    fn get_a(
+        &self,
+        num_inputs: usize,
+        _num_a: usize,
+    ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        Ok((self.builder(self.a, 0), self.builder(self.a, num_inputs)))
+    }
+
+    fn get_b_g1(
+        &self,
+        num_inputs: usize,
+        _num_b_g1: usize,
+    ) -> Result<(Self::G1Builder, Self::G1Builder), SynthesisError> {
+        Ok((
+            self.builder(self.b_g1, 0),
+            self.builder(self.b_g1, num_inputs),
+        ))
+    }
+
+    fn get_b_g2(
+        &self,
+        num_inputs: usize,
+        _num_b_g2: usize,
+    ) -> Result<(Self::G2Builder, Self::G2Builder), SynthesisError> {
+        Ok((
+            self.builder(self.b_g2, 0),
+            self.builder(self.b_g2, num_inputs),
+        ))
+    }
+}
+
+/// A pool of open handles to the parameter file, shared by all sources.
+struct FileCache {
+    path: PathBuf,
+    size: usize,
+    files: Mutex<Vec<File>>,
+}
+
+impl FileCache {
+    fn new(path: PathBuf, size: usize) -> Self {
+        FileCache {
+            path,
+            size,
+            files: Mutex::new(Vec::with_capacity(size)),
+        }
+    }
+
+    fn get(&self) -> io::Result<File> {
+        match self.files.lock().unwrap().pop() {
+            Some(file) => Ok(file),
+            None => File::open(&self.path),
+        }
+    }
+
+    fn put(&self, file: File) {
+        let mut files = self.files.lock().unwrap();
+        if files.len() < self.size {
+            files.push(file);
+        }
+    }
+}
+
+/// Builds sources that stream one section of a parameter file, starting at
+/// the `start`-th point.
+pub struct PreadSourceBuilder<G> {
+    files: Arc<FileCache>,
+    section: Section,
+    start: usize,
+    checked: bool,
+    buffer_len: usize,
+    _g: PhantomData<fn() -> G>,
+}
+
+impl<G> Clone for PreadSourceBuilder<G> {
+    fn clone(&self) -> Self {
+        PreadSourceBuilder {
+            files: self.files.clone(),
+            section: self.section,
+            start: self.start,
+            checked: self.checked,
+            buffer_len: self.buffer_len,
+            _g: PhantomData,
+        }
+    }
+}
+
+impl<G> SourceBuilder<G> for PreadSourceBuilder<G>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+{
+    type Source = PreadSource<G>;
+
+    fn new(self) -> PreadSource<G> {
+        PreadSource {
+            files: self.files,
+            file: None,
+            section: self.section,
+            checked: self.checked,
+            next: self.start,
+            raw: Vec::new(),
+            points: Vec::with_capacity(self.buffer_len),
+            points_start: 0,
+            buffer_len: self.buffer_len,
+        }
+    }
+
+    /// Panics if the section cannot be read, see `try_get`.
+    fn get(self) -> (Arc<Vec<G>>, usize) {
+        self.try_get().expect("failed to read parameters from disk")
+    }
+
+    /// Reads the whole section into memory, for consumers that need all bases
+    /// at once (e.g. the GPU kernels).
+    fn try_get(self) -> Result<(Arc<Vec<G>>, usize), SynthesisError> {
+        let start = self.start;
+        let mut source = self.new();
+        source.buffer_len = source.section.len;
+        source.fill(0)?;
+
+        Ok((Arc::new(mem::take(&mut source.points)), start))
+    }
+}
+
+/// A source of bases that reads points from a section of a parameter file
+/// into a reusable buffer.
+pub struct PreadSource<G: PrimeCurveAffine> {
+    files: Arc<FileCache>,
+    file: Option<File>,
+    section: Section,
+    checked: bool,
+    // index of the next point in the section
+    next: usize,
+    raw: Vec<u8>,
+    // the buffered points are the ones at `points_start..` in the section
+    points: Vec<G>,
+    points_start: usize,
+    buffer_len: usize,
+}
+
+impl<G> PreadSource<G>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+{
+    /// Reads the points of the section starting at `start` into the buffer.
+    fn fill(&mut self, start: usize) -> io::Result<()> {
+        let point_len = mem::size_of::<G::Uncompressed>();
+        let count = std::cmp::min(self.buffer_len, self.section.len - start);
+
+        if self.file.is_none() {
+            self.file = Some(self.files.get()?);
+        }
+        let file = self.file.as_ref().expect("file was opened above");
+
+        self.raw.resize(count * point_len, 0);
+        read_exact_at(
+            file,
+            &mut self.raw,
+            self.section.offset + (start * point_len) as u64,
+        )?;
+
+        self.points.clear();
+        let mut repr = G::Uncompressed::default();
+        for raw in self.raw.chunks_exact(point_len) {
+            repr.as_mut().copy_from_slice(raw);
+
+            let affine_opt = if self.checked {
+                G::from_uncompressed(&repr)
+            } else {
+                G::from_uncompressed_unchecked(&repr)
+            };
+            let affine: G = Option::from(affine_opt)
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not on curve"))?;
+
+            self.points.push(affine);
+        }
+        self.points_start = start;
+
+        Ok(())
+    }
+}
+
+impl<G> Source<G> for PreadSource<G>
+where
+    G: PrimeCurveAffine + UncompressedEncoding,
+{
+    fn add_assign_mixed(
+        &mut self,
+        to: &mut <G as PrimeCurveAffine>::Curve,
+    ) -> Result<(), SynthesisError> {
+        if self.section.len <= self.next {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "expected more bases from source",
+            )
+            .into());
+        }
+
+        if self.next < self.points_start || self.points_start + self.points.len() <= self.next {
+            self.fill(self.next)?;
+        }
+
+        let point = &self.points[self.next - self.points_start];
+        if point.is_identity().into() {
+            return Err(SynthesisError::UnexpectedIdentity);
+        }
+
+        to.add_assign(point);
+
+        self.next += 1;
+
+        Ok(())
+    }
+
+    fn skip(&mut self, amt: usize) -> Result<(), SynthesisError> {
+        if self.section.len <= self.next {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "expected more bases from source",
+            )
+            .into());
+        }
+
+        self.next += amt;
+
+        Ok(())
+    }
+}
+
+impl<G: PrimeCurveAffine> Drop for PreadSource<G> {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.take() {
+            self.files.put(file);
+        }
+    }
+}
+
+#[cfg(unix)]
+fn read_exact_at(file: &File, buf: &mut [u8], offset: u64) -> io::Result<()> {
+    use std::os::unix::fs::FileExt;
+
+    file.read_exact_at(buf, offset)
+}
+
+#[cfg(windows)]
+fn read_exact_at(file: &File, mut buf: &mut [u8], mut offset: u64) -> io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    while !buf.is_empty() {
+        match file.seek_read(buf, offset) {
+            Ok(0) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "failed to fill whole buffer",
+                ))
+            }
+            Ok(n) => {
+                buf = &mut buf[n..];
+                offset += n as u64;
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(())
+}
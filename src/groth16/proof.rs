@@ -1,14 +1,13 @@
-use std::fmt;
 use std::io::{self, Read, Write};
-use std::marker::PhantomData;
 
+use crate::parallel::prelude::*;
 use group::{prime::PrimeCurveAffine, GroupEncoding};
 use pairing::Engine;
-use rayon::prelude::*;
 
-use serde::de::Visitor;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
+use super::serde_impl;
+
 #[derive(Clone, Debug)]
 pub struct Proof<E: Engine> {
     pub a: E::G1Affine,
@@ -18,35 +17,13 @@ pub struct Proof<E: Engine> {
 
 impl<E: Engine> Serialize for Proof<E> {
     fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
-        let mut v = Vec::with_capacity(Proof::<E>::size());
-        self.write(&mut v).unwrap();
-        s.serialize_bytes(&v)
-    }
-}
-
-fn deserialize_proof<'de, D: Deserializer<'de>, E: Engine>(d: D) -> Result<Proof<E>, D::Error> {
-    struct BytesVisitor<E> {
-        _ph: PhantomData<E>,
-    }
-
-    impl<'de, E: Engine> Visitor<'de> for BytesVisitor<E> {
-        type Value = Proof<E>;
-
-        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            write!(f, "a proof")
-        }
-        #[inline]
-        fn visit_bytes<F: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, F> {
-            let p = Proof::read(v).unwrap();
-            Ok(p)
-        }
+        serde_impl::serialize(s, |w| self.write(w))
     }
-    d.deserialize_bytes(BytesVisitor { _ph: PhantomData })
 }
 
 impl<'de, E: Engine> Deserialize<'de> for Proof<E> {
     fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
-        deserialize_proof(d)
+        serde_impl::deserialize(d, "proof", |r| Self::read(r))
     }
 }
 
@@ -220,7 +197,7 @@ impl<E: Engine> Proof<E> {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "groth16"))]
 mod test_with_bls12_381 {
     use std::ops::MulAssign;
 
@@ -0,0 +1,297 @@
+//! Serde support for types that have a canonical byte encoding through their
+//! `write`/`read` functions.
+//!
+//! Human readable formats (e.g. JSON) get the encoding as a lowercase hex
+//! string, binary formats (e.g. bincode) get it as a byte array. Human
+//! readable formats also accept a byte array, which is how they used to get
+//! the encoding. Decoding errors are reported through the deserializer
+//! instead of panicking, and trailing bytes after the encoded value are
+//! rejected.
+
+use std::fmt;
+use std::io;
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserializer, Serializer};
+
+pub(crate) fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+pub(crate) fn from_hex(s: &str) -> Option<Vec<u8>> {
+    let digit = |c: u8| (c as char).to_digit(16).map(|d| d as u8);
+    let s = s.strip_prefix("0x").unwrap_or(s).as_bytes();
+    let pairs = s.chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return None;
+    }
+    pairs
+        .map(|pair| Some(digit(pair[0])? << 4 | digit(pair[1])?))
+        .collect()
+}
+
+/// Serializes the bytes produced by `write`.
+pub(crate) fn serialize<S, F>(s: S, write: F) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    F: FnOnce(&mut Vec<u8>) -> io::Result<()>,
+{
+    let mut bytes = Vec::new();
+    write(&mut bytes).map_err(serde::ser::Error::custom)?;
+    if s.is_human_readable() {
+        s.serialize_str(&to_hex(&bytes))
+    } else {
+        s.serialize_bytes(&bytes)
+    }
+}
+
+/// Deserializes a value from bytes with `read`, which must consume all of them.
+pub(crate) fn deserialize<'de, D, T, F>(
+    d: D,
+    expecting: &'static str,
+    read: F,
+) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    F: FnOnce(&mut &[u8]) -> io::Result<T>,
+{
+    struct BytesVisitor(&'static str);
+
+    impl<'de> Visitor<'de> for BytesVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{} as bytes or a hex string", self.0)
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+            from_hex(v).ok_or_else(|| E::invalid_value(de::Unexpected::Str(v), &self))
+        }
+
+        fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            // the size hint comes from the input, so it only bounds the
+            // initial allocation
+            let mut bytes = Vec::with_capacity(std::cmp::min(seq.size_hint().unwrap_or(0), 4096));
+            while let Some(b) = seq.next_element()? {
+                bytes.push(b);
+            }
+            Ok(bytes)
+        }
+    }
+
+    let visitor = BytesVisitor(expecting);
+    let bytes = if d.is_human_readable() {
+        d.deserialize_any(visitor)?
+    } else {
+        d.deserialize_bytes(visitor)?
+    };
+    read_all(&bytes, expecting, read)
+}
+
+/// Reads a value from `bytes` with `read`, which must consume all of them.
+pub(crate) fn read_all<T, E, F>(bytes: &[u8], expecting: &'static str, read: F) -> Result<T, E>
+where
+    E: de::Error,
+    F: FnOnce(&mut &[u8]) -> io::Result<T>,
+{
+    let mut rest = bytes;
+    let value = read(&mut rest)
+        .map_err(|err| de::Error::custom(format_args!("invalid {}: {}", expecting, err)))?;
+    if !rest.is_empty() {
+        return Err(de::Error::custom(format_args!(
+            "invalid {}: {} trailing bytes",
+            expecting,
+            rest.len()
+        )));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod test_with_bls12_381 {
+    use blstrs::{Bls12, G1Affine, G2Affine, Scalar};
+    use ff::Field;
+    use group::{prime::PrimeCurveAffine, Curve};
+    use rand_core::SeedableRng;
+    use serde::{de::DeserializeOwned, Serialize};
+
+    use crate::groth16::aggregate::{setup_fake_srs, AggregateProof, GenericSRS, VerifierSRS};
+    use crate::groth16::{prepare_verifying_key, PreparedVerifyingKey, Proof, VerifyingKey};
+    use blstrs::Compress;
+
+    fn roundtrip<T: Serialize + DeserializeOwned + PartialEq + std::fmt::Debug>(value: &T) {
+        let json = serde_json::to_string(value).unwrap();
+        assert!(json.starts_with('"') && json.ends_with('"'));
+        assert_eq!(&serde_json::from_str::<T>(&json).unwrap(), value);
+
+        let bin = bincode::serialize(value).unwrap();
+        assert_eq!(&bincode::deserialize::<T>(&bin).unwrap(), value);
+
+        // Truncated or extended encodings are errors, not panics.
+        assert!(bincode::deserialize::<T>(&bin[..bin.len() - 1]).is_err());
+        let mut extended = json[..json.len() - 1].to_string();
+        extended.push_str("00\"");
+        assert!(serde_json::from_str::<T>(&extended).is_err());
+        assert!(serde_json::from_str::<T>(&json[..json.len() - 3]).is_err());
+    }
+
+    fn random_vk(n: usize) -> VerifyingKey<Bls12> {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0);
+        let mut g1 = || (G1Affine::generator() * Scalar::random(&mut rng)).to_affine();
+        let (alpha_g1, beta_g1, delta_g1) = (g1(), g1(), g1());
+        let ic = (0..n).map(|_| g1()).collect();
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(1);
+        let mut g2 = || (G2Affine::generator() * Scalar::random(&mut rng)).to_affine();
+        VerifyingKey {
+            alpha_g1,
+            beta_g1,
+            beta_g2: g2(),
+            gamma_g2: g2(),
+            delta_g1,
+            delta_g2: g2(),
+            ic,
+        }
+    }
+
+    #[test]
+    fn serde_proof() {
+        let proof = Proof::<Bls12> {
+            a: G1Affine::generator(),
+            b: G2Affine::generator(),
+            c: (G1Affine::generator() * Scalar::from(3u64)).to_affine(),
+        };
+        roundtrip(&proof);
+
+        // Bincode keeps the length prefixed compressed encoding.
+        let mut bytes = Vec::new();
+        proof.write(&mut bytes).unwrap();
+        let bin = bincode::serialize(&proof).unwrap();
+        assert_eq!(&bin[8..], &bytes[..]);
+
+        // Points that are not on the curve are errors, not panics.
+        let mut bad = bin.clone();
+        bad[8 + 10] ^= 1;
+        assert!(bincode::deserialize::<Proof<Bls12>>(&bad).is_err());
+        assert!(serde_json::from_str::<Proof<Bls12>>("\"zz\"").is_err());
+    }
+
+    #[test]
+    fn serde_verifying_keys() {
+        let vk = random_vk(3);
+        roundtrip(&vk);
+
+        let pvk = prepare_verifying_key(&vk);
+        let json = serde_json::to_string(&pvk).unwrap();
+        let decoded: crate::groth16::PreparedVerifyingKey<Bls12> =
+            serde_json::from_str(&json).unwrap();
+        let mut expected = Vec::new();
+        pvk.write(&mut expected).unwrap();
+        let mut actual = Vec::new();
+        decoded.write(&mut actual).unwrap();
+        assert_eq!(actual, expected);
+
+        let bin = bincode::serialize(&pvk).unwrap();
+        assert!(
+            bincode::deserialize::<crate::groth16::PreparedVerifyingKey<Bls12>>(
+                &bin[..bin.len() - 1]
+            )
+            .is_err()
+        );
+    }
+
+    #[test]
+    fn serde_prepared_verifying_key_untrusted() {
+        let vk = random_vk(3);
+        let pvk = prepare_verifying_key(&vk);
+        let mut bytes = Vec::new();
+        pvk.write(&mut bytes).unwrap();
+
+        let mut other_vk = random_vk(3);
+        other_vk.ic.reverse();
+        other_vk.alpha_g1 = vk.beta_g1;
+        let other = prepare_verifying_key(&other_vk);
+        let mut other_bytes = Vec::new();
+        other.write(&mut other_bytes).unwrap();
+
+        // tables of another ic
+        let mut tables = Vec::new();
+        pvk.multiscalar.write(&mut tables).unwrap();
+        let head_len = bytes.len() - tables.len();
+        let mut forged_tables = bytes[..head_len].to_vec();
+        forged_tables.extend_from_slice(&other_bytes[head_len..]);
+
+        // pairing of another alpha
+        let mut gt = Vec::new();
+        pvk.alpha_g1_beta_g2.write_compressed(&mut gt).unwrap();
+        let mut forged_pairing = bytes.clone();
+        forged_pairing[8..8 + gt.len()].copy_from_slice(&other_bytes[8..8 + gt.len()]);
+
+        for forged in &[forged_tables, forged_pairing] {
+            // the trusting reader takes them as they are
+            PreparedVerifyingKey::<Bls12>::read(&forged[..], None).unwrap();
+            assert!(bincode::deserialize::<PreparedVerifyingKey<Bls12>>(
+                &bincode::serialize(forged).unwrap()
+            )
+            .is_err());
+        }
+    }
+
+    #[test]
+    fn serde_srs() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0);
+        let srs: GenericSRS<Bls12> = setup_fake_srs(&mut rng, 8);
+        roundtrip(&srs);
+
+        let (_, vk): (_, VerifierSRS<Bls12>) = srs.specialize(4).unwrap();
+        roundtrip(&vk);
+        assert_eq!(
+            vk,
+            VerifierSRS::read(&mut &bincode::serialize(&vk).unwrap()[8..]).unwrap()
+        );
+    }
+
+    fn aggregate_proof_bytes(proof: &AggregateProof<Bls12>) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        proof.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn serde_baseline_fixtures() {
+        // JSON used to get the encoding of a proof as a byte array
+        let json = include_str!("tests/proof.json");
+        let proof: Proof<Bls12> = serde_json::from_str(json).unwrap();
+        let mut bytes = Vec::new();
+        proof.write(&mut bytes).unwrap();
+        assert_eq!(bytes, serde_json::from_str::<Vec<u8>>(json).unwrap());
+
+        // binary formats keep the fields of aggregate proofs
+        let expected = aggregate_proof_bytes(
+            &AggregateProof::read(&include_bytes!("tests/aggregate_proof.v1")[..]).unwrap(),
+        );
+        let bin = include_bytes!("tests/aggregate_proof.bincode");
+        let proof: AggregateProof<Bls12> = bincode::deserialize(bin).unwrap();
+        assert_eq!(aggregate_proof_bytes(&proof), expected);
+        assert_eq!(&bincode::serialize(&proof).unwrap()[..], &bin[..]);
+        assert!(bincode::deserialize::<AggregateProof<Bls12>>(&bin[..bin.len() - 1]).is_err());
+
+        // JSON gets the encoding of aggregate proofs as a hex string and
+        // still accepts their fields
+        let proof: AggregateProof<Bls12> =
+            serde_json::from_str(include_str!("tests/aggregate_proof.json")).unwrap();
+        assert_eq!(aggregate_proof_bytes(&proof), expected);
+        let json = serde_json::to_string(&proof).unwrap();
+        assert_eq!(json, format!("\"{}\"", super::to_hex(&expected)));
+        let decoded: AggregateProof<Bls12> = serde_json::from_str(&json).unwrap();
+        assert_eq!(aggregate_proof_bytes(&decoded), expected);
+        assert!(serde_json::from_str::<AggregateProof<Bls12>>(&json[..json.len() - 3]).is_err());
+    }
+}
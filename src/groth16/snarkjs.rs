@@ -0,0 +1,475 @@
+//! JSON encoding of proofs, verifying keys and public inputs in the layout used by
+//! [snarkjs] (`proof.json`, `verification_key.json` and `public.json`).
+//!
+//! Field elements are written as decimal strings and curve points in jacobian
+//! form with `z = 1` (or `z = 0` for the point at infinity). Extension field
+//! elements are written as nested arrays of their coefficients, lowest first.
+//!
+//! snarkjs verifying keys do not contain `beta` and `delta` in G1, which
+//! bellperson needs for its [`VerifyingKey`], so they are exported as the
+//! additional fields `vk_beta_1` and `vk_delta_1` and are required on import.
+//! The redundant `vk_alphabeta_12` field is not written and is ignored on
+//! import, the snarkjs verifiers recompute it from `vk_alpha_1` and `vk_beta_2`.
+//!
+//! [snarkjs]: https://github.com/iden3/snarkjs
+
+use std::io::{self, Read, Write};
+
+use blstrs::{Bls12, G1Affine, G2Affine, Scalar};
+use group::prime::PrimeCurveAffine;
+use serde::{Deserialize, Serialize};
+
+use super::{Proof, VerifyingKey};
+
+/// The value of the `protocol` field.
+pub const SNARKJS_PROTOCOL: &str = "groth16";
+/// The value of the `curve` field.
+pub const SNARKJS_CURVE: &str = "bls12381";
+
+type G1Json = [String; 3];
+type G2Json = [[String; 2]; 3];
+
+const FP_SIZE: usize = 48;
+
+#[derive(Serialize, Deserialize)]
+struct ProofJson {
+    pi_a: G1Json,
+    pi_b: G2Json,
+    pi_c: G1Json,
+    protocol: String,
+    curve: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct VerifyingKeyJson {
+    protocol: String,
+    curve: String,
+    #[serde(rename = "nPublic")]
+    n_public: usize,
+    vk_alpha_1: G1Json,
+    vk_beta_1: Option<G1Json>,
+    vk_beta_2: G2Json,
+    vk_gamma_2: G2Json,
+    vk_delta_1: Option<G1Json>,
+    vk_delta_2: G2Json,
+    #[serde(rename = "IC")]
+    ic: Vec<G1Json>,
+}
+
+fn invalid_data<S: Into<String>>(msg: S) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+/// Formats a big-endian unsigned integer as a decimal string.
+fn be_bytes_to_decimal(bytes: &[u8]) -> String {
+    // Little-endian base 10^9 digits.
+    let mut digits: Vec<u32> = vec![0];
+    for byte in bytes {
+        let mut carry = *byte as u64;
+        for digit in digits.iter_mut() {
+            let v = ((*digit as u64) << 8) + carry;
+            *digit = (v % 1_000_000_000) as u32;
+            carry = v / 1_000_000_000;
+        }
+        while carry > 0 {
+            digits.push((carry % 1_000_000_000) as u32);
+            carry /= 1_000_000_000;
+        }
+    }
+
+    let mut out = digits.last().unwrap().to_string();
+    for digit in digits.iter().rev().skip(1) {
+        out.push_str(&format!("{:09}", digit));
+    }
+    out
+}
+
+/// Parses a decimal string into a big-endian unsigned integer of exactly `N` bytes.
+fn decimal_to_be_bytes<const N: usize>(s: &str) -> io::Result<[u8; N]> {
+    if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(invalid_data(format!("invalid decimal number {:?}", s)));
+    }
+
+    let mut out = [0u8; N];
+    for b in s.bytes() {
+        let mut carry = (b - b'0') as u32;
+        for byte in out.iter_mut().rev() {
+            let v = (*byte as u32) * 10 + carry;
+            *byte = v as u8;
+            carry = v >> 8;
+        }
+        if carry != 0 {
+            return Err(invalid_data(format!("number out of range {:?}", s)));
+        }
+    }
+    Ok(out)
+}
+
+/// Formats a 48 byte big-endian base field element.
+fn fp_to_json(bytes: &[u8]) -> String {
+    be_bytes_to_decimal(bytes)
+}
+
+/// Parses a base field element into 48 big-endian bytes. The range check
+/// against the modulus happens when the point is decoded.
+fn fp_from_json(s: &str) -> io::Result<[u8; FP_SIZE]> {
+    decimal_to_be_bytes::<FP_SIZE>(s)
+}
+
+fn scalar_to_json(s: &Scalar) -> String {
+    be_bytes_to_decimal(&s.to_bytes_be())
+}
+
+fn scalar_from_json(s: &str) -> io::Result<Scalar> {
+    let bytes = decimal_to_be_bytes::<32>(s)?;
+    Option::from(Scalar::from_bytes_be(&bytes))
+        .ok_or_else(|| invalid_data(format!("scalar out of range {:?}", s)))
+}
+
+fn g1_to_json(p: &G1Affine) -> G1Json {
+    if bool::from(p.is_identity()) {
+        return ["0".into(), "1".into(), "0".into()];
+    }
+    let bytes = p.to_uncompressed();
+    [
+        fp_to_json(&bytes[..FP_SIZE]),
+        fp_to_json(&bytes[FP_SIZE..]),
+        "1".into(),
+    ]
+}
+
+/// Decodes a G1 point, checking that it is on the curve and in the prime order subgroup.
+fn g1_from_json(p: &G1Json) -> io::Result<G1Affine> {
+    match p[2].as_str() {
+        "0" => return Ok(G1Affine::identity()),
+        "1" => {}
+        z => return Err(invalid_data(format!("unsupported z coordinate {:?}", z))),
+    }
+
+    let mut bytes = [0u8; 2 * FP_SIZE];
+    bytes[..FP_SIZE].copy_from_slice(&fp_from_json(&p[0])?);
+    bytes[FP_SIZE..].copy_from_slice(&fp_from_json(&p[1])?);
+    // The top bits of x are encoding flags, so they must be clear in a valid
+    // coordinate anyway.
+    if bytes[0] & 0xe0 != 0 {
+        return Err(invalid_data("base field element out of range"));
+    }
+    Option::from(G1Affine::from_uncompressed(&bytes))
+        .ok_or_else(|| invalid_data("G1 point not on curve or not in subgroup"))
+}
+
+fn g2_to_json(p: &G2Affine) -> G2Json {
+    if bool::from(p.is_identity()) {
+        return [
+            ["0".into(), "0".into()],
+            ["1".into(), "0".into()],
+            ["0".into(), "0".into()],
+        ];
+    }
+    // The uncompressed encoding stores the c1 coefficient first, snarkjs c0.
+    let bytes = p.to_uncompressed();
+    let fp = |i: usize| fp_to_json(&bytes[i * FP_SIZE..(i + 1) * FP_SIZE]);
+    [[fp(1), fp(0)], [fp(3), fp(2)], ["1".into(), "0".into()]]
+}
+
+/// Decodes a G2 point, checking that it is on the curve and in the prime order subgroup.
+fn g2_from_json(p: &G2Json) -> io::Result<G2Affine> {
+    match (p[2][0].as_str(), p[2][1].as_str()) {
+        ("0", "0") => return Ok(G2Affine::identity()),
+        ("1", "0") => {}
+        z => return Err(invalid_data(format!("unsupported z coordinate {:?}", z))),
+    }
+
+    let mut bytes = [0u8; 4 * FP_SIZE];
+    for (i, s) in [&p[0][1], &p[0][0], &p[1][1], &p[1][0]].iter().enumerate() {
+        bytes[i * FP_SIZE..(i + 1) * FP_SIZE].copy_from_slice(&fp_from_json(s)?);
+    }
+    if bytes[0] & 0xe0 != 0 {
+        return Err(invalid_data("base field element out of range"));
+    }
+    Option::from(G2Affine::from_uncompressed(&bytes))
+        .ok_or_else(|| invalid_data("G2 point not on curve or not in subgroup"))
+}
+
+fn check_header(protocol: &str, curve: &str) -> io::Result<()> {
+    if protocol != SNARKJS_PROTOCOL {
+        return Err(invalid_data(format!("unsupported protocol {:?}", protocol)));
+    }
+    if curve != SNARKJS_CURVE {
+        return Err(invalid_data(format!("unsupported curve {:?}", curve)));
+    }
+    Ok(())
+}
+
+impl Proof<Bls12> {
+    /// Writes the proof as a snarkjs `proof.json` document.
+    pub fn write_snarkjs_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        let json = ProofJson {
+            pi_a: g1_to_json(&self.a),
+            pi_b: g2_to_json(&self.b),
+            pi_c: g1_to_json(&self.c),
+            protocol: SNARKJS_PROTOCOL.into(),
+            curve: SNARKJS_CURVE.into(),
+        };
+        serde_json::to_writer_pretty(writer, &json)?;
+        Ok(())
+    }
+
+    /// Reads a proof from a snarkjs `proof.json` document. Like [`Proof::read`],
+    /// points must be in the prime order subgroup and not at infinity.
+    pub fn read_snarkjs_json<R: Read>(reader: R) -> io::Result<Self> {
+        let json: ProofJson = serde_json::from_reader(reader)?;
+        check_header(&json.protocol, &json.curve)?;
+
+        let proof: Self = Proof {
+            a: g1_from_json(&json.pi_a)?,
+            b: g2_from_json(&json.pi_b)?,
+            c: g1_from_json(&json.pi_c)?,
+        };
+        if bool::from(proof.a.is_identity())
+            || bool::from(proof.b.is_identity())
+            || bool::from(proof.c.is_identity())
+        {
+            return Err(invalid_data("point at infinity"));
+        }
+        Ok(proof)
+    }
+}
+
+impl VerifyingKey<Bls12> {
+    /// Writes the key as a snarkjs `verification_key.json` document.
+    pub fn write_snarkjs_json<W: Write>(&self, writer: W) -> io::Result<()> {
+        let json = VerifyingKeyJson {
+            protocol: SNARKJS_PROTOCOL.into(),
+            curve: SNARKJS_CURVE.into(),
+            n_public: self.ic.len().saturating_sub(1),
+            vk_alpha_1: g1_to_json(&self.alpha_g1),
+            vk_beta_1: Some(g1_to_json(&self.beta_g1)),
+            vk_beta_2: g2_to_json(&self.beta_g2),
+            vk_gamma_2: g2_to_json(&self.gamma_g2),
+            vk_delta_1: Some(g1_to_json(&self.delta_g1)),
+            vk_delta_2: g2_to_json(&self.delta_g2),
+            ic: self.ic.iter().map(g1_to_json).collect(),
+        };
+        serde_json::to_writer_pretty(writer, &json)?;
+        Ok(())
+    }
+
+    /// Reads a key from a snarkjs `verification_key.json` document.
+    ///
+    /// The document must carry the `vk_beta_1` and `vk_delta_1` fields.
+    pub fn read_snarkjs_json<R: Read>(reader: R) -> io::Result<Self> {
+        let json: VerifyingKeyJson = serde_json::from_reader(reader)?;
+        check_header(&json.protocol, &json.curve)?;
+
+        if json.ic.len() != json.n_public + 1 {
+            return Err(invalid_data(format!(
+                "expected {} IC points for nPublic = {}, got {}",
+                json.n_public + 1,
+                json.n_public,
+                json.ic.len()
+            )));
+        }
+        let beta_g1 = json
+            .vk_beta_1
+            .as_ref()
+            .ok_or_else(|| invalid_data("missing vk_beta_1"))?;
+        let delta_g1 = json
+            .vk_delta_1
+            .as_ref()
+            .ok_or_else(|| invalid_data("missing vk_delta_1"))?;
+
+        Ok(VerifyingKey {
+            alpha_g1: g1_from_json(&json.vk_alpha_1)?,
+            beta_g1: g1_from_json(beta_g1)?,
+            beta_g2: g2_from_json(&json.vk_beta_2)?,
+            gamma_g2: g2_from_json(&json.vk_gamma_2)?,
+            delta_g1: g1_from_json(delta_g1)?,
+            delta_g2: g2_from_json(&json.vk_delta_2)?,
+            ic: json
+                .ic
+                .iter()
+                .map(g1_from_json)
+                .collect::<io::Result<_>>()?,
+        })
+    }
+}
+
+/// Writes public inputs as a snarkjs `public.json` document.
+pub fn write_snarkjs_public_inputs<W: Write>(inputs: &[Scalar], writer: W) -> io::Result<()> {
+    let json: Vec<String> = inputs.iter().map(scalar_to_json).collect();
+    serde_json::to_writer_pretty(writer, &json)?;
+    Ok(())
+}
+
+/// Reads public inputs from a snarkjs `public.json` document. Every input
+/// must be smaller than the scalar field modulus.
+pub fn read_snarkjs_public_inputs<R: Read>(reader: R) -> io::Result<Vec<Scalar>> {
+    let json: Vec<String> = serde_json::from_reader(reader)?;
+    json.iter().map(|s| scalar_from_json(s)).collect()
+}
+
+#[cfg(test)]
+mod test_with_bls12_381 {
+    use super::*;
+
+    use ff::Field;
+    use group::Curve;
+    use rand_core::SeedableRng;
+
+    const G1_X: &str = "3685416753713387016781088315183077757961620795782546409894578378688607592378376318836054947676345821548104185464507";
+    const G1_Y: &str = "1339506544944476473020471379941921221584933875938349620426543736416511423956333506472724655353366534992391756441569";
+    const G2_X_C0: &str = "352701069587466618187139116011060144890029952792775240219908644239793785735715026873347600343865175952761926303160";
+    const G2_X_C1: &str = "3059144344244213709971259814753781636986470325476647558659373206291635324768958432433509563104347017837885763365758";
+    const G2_Y_C0: &str = "1985150602287291935568054521177171638300868978215655730859378665066344726373823718423869104263333984641494340347905";
+    const G2_Y_C1: &str = "927553665492332455747201965776037880757740193453592970025027978793976877002675564980949289727957565575433344219582";
+
+    fn generators_proof() -> Proof<Bls12> {
+        Proof {
+            a: G1Affine::generator(),
+            b: G2Affine::generator(),
+            c: G1Affine::generator(),
+        }
+    }
+
+    #[test]
+    fn proof_known_vector() {
+        let json = format!(
+            r#"{{
+  "pi_a": ["{x}", "{y}", "1"],
+  "pi_b": [["{x0}", "{x1}"], ["{y0}", "{y1}"], ["1", "0"]],
+  "pi_c": ["{x}", "{y}", "1"],
+  "protocol": "groth16",
+  "curve": "bls12381"
+}}"#,
+            x = G1_X,
+            y = G1_Y,
+            x0 = G2_X_C0,
+            x1 = G2_X_C1,
+            y0 = G2_Y_C0,
+            y1 = G2_Y_C1,
+        );
+
+        let proof = Proof::read_snarkjs_json(json.as_bytes()).unwrap();
+        assert_eq!(proof, generators_proof());
+
+        let mut out = Vec::new();
+        proof.write_snarkjs_json(&mut out).unwrap();
+        let expected: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let actual: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(expected, actual);
+    }
+
+    #[test]
+    fn proof_rejects_invalid() {
+        let mut out = Vec::new();
+        generators_proof().write_snarkjs_json(&mut out).unwrap();
+        let json = String::from_utf8(out).unwrap();
+
+        for bad in [
+            json.replace("bls12381", "bn128"),
+            json.replace("groth16", "plonk"),
+            // Off the curve.
+            json.replacen(G1_Y, G1_X, 1),
+            // Not a decimal number.
+            json.replacen(G1_X, "0x17f1", 1),
+            // Larger than the base field modulus.
+            json.replacen(G1_X, &format!("{}0", G1_X), 1),
+        ]
+        .iter()
+        {
+            assert!(Proof::read_snarkjs_json(bad.as_bytes()).is_err());
+        }
+
+        let identity = Proof {
+            a: G1Affine::identity(),
+            ..generators_proof()
+        };
+        let mut out = Vec::new();
+        identity.write_snarkjs_json(&mut out).unwrap();
+        assert!(Proof::read_snarkjs_json(&out[..]).is_err());
+    }
+
+    #[test]
+    fn public_inputs_known_vector() {
+        let inputs = vec![
+            Scalar::zero(),
+            Scalar::one(),
+            Scalar::from(1_000_000_007u64),
+            -Scalar::one(),
+        ];
+        let json = r#"["0","1","1000000007","52435875175126190479447740508185965837690552500527637822603658699938581184512"]"#;
+
+        assert_eq!(read_snarkjs_public_inputs(json.as_bytes()).unwrap(), inputs);
+
+        let mut out = Vec::new();
+        write_snarkjs_public_inputs(&inputs, &mut out).unwrap();
+        let actual: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        let expected: serde_json::Value = serde_json::from_str(json).unwrap();
+        assert_eq!(actual, expected);
+
+        // The modulus itself is out of range.
+        let modulus =
+            r#"["52435875175126190479447740508185965837690552500527637822603658699938581184513"]"#;
+        assert!(read_snarkjs_public_inputs(modulus.as_bytes()).is_err());
+    }
+
+    #[test]
+    fn verifying_key_roundtrip() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0);
+        let g1 = |rng: &mut rand_chacha::ChaChaRng| {
+            G1Affine::from(G1Affine::generator() * Scalar::random(rng))
+        };
+        let g2 = |rng: &mut rand_chacha::ChaChaRng| {
+            G2Affine::from(G2Affine::generator() * Scalar::random(rng))
+        };
+
+        let vk = VerifyingKey::<Bls12> {
+            alpha_g1: g1(&mut rng),
+            beta_g1: g1(&mut rng),
+            beta_g2: g2(&mut rng),
+            gamma_g2: g2(&mut rng),
+            delta_g1: g1(&mut rng),
+            delta_g2: g2(&mut rng),
+            ic: (0..4).map(|_| g1(&mut rng)).collect(),
+        };
+
+        let mut out = Vec::new();
+        vk.write_snarkjs_json(&mut out).unwrap();
+        let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(json["protocol"], "groth16");
+        assert_eq!(json["curve"], "bls12381");
+        assert_eq!(json["nPublic"], 3);
+        assert_eq!(json["IC"].as_array().unwrap().len(), 4);
+
+        assert_eq!(VerifyingKey::read_snarkjs_json(&out[..]).unwrap(), vk);
+
+        // Documents produced by snarkjs carry vk_alphabeta_12, which is ignored.
+        let mut with_alphabeta = json.clone();
+        with_alphabeta["vk_alphabeta_12"] = serde_json::json!([[["1", "0"]]]);
+        let bytes = serde_json::to_vec(&with_alphabeta).unwrap();
+        assert_eq!(VerifyingKey::read_snarkjs_json(&bytes[..]).unwrap(), vk);
+
+        let mut mismatch = json.clone();
+        mismatch["vk_alpha_1"][1] = mismatch["vk_beta_1"][1].clone();
+        let bytes = serde_json::to_vec(&mismatch).unwrap();
+        assert!(VerifyingKey::read_snarkjs_json(&bytes[..]).is_err());
+
+        let mut missing = json.clone();
+        missing.as_object_mut().unwrap().remove("vk_delta_1");
+        let bytes = serde_json::to_vec(&missing).unwrap();
+        assert!(VerifyingKey::read_snarkjs_json(&bytes[..]).is_err());
+
+        let mut wrong_len = json;
+        wrong_len["nPublic"] = 2.into();
+        let bytes = serde_json::to_vec(&wrong_len).unwrap();
+        assert!(VerifyingKey::read_snarkjs_json(&bytes[..]).is_err());
+
+        // The point at infinity uses z = 0.
+        let p = G1Affine::identity();
+        assert_eq!(g1_from_json(&g1_to_json(&p)).unwrap(), p);
+        let q = (G2Affine::generator().to_curve() - G2Affine::generator().to_curve()).to_affine();
+        assert!(bool::from(q.is_identity()));
+        assert_eq!(g2_from_json(&g2_to_json(&q)).unwrap(), q);
+    }
+}
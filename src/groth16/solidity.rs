@@ -0,0 +1,474 @@
+//! Generation of Solidity verifier contracts for BLS12-381 Groth16 proofs.
+//!
+//! The generated contract relies on the [EIP-2537] precompiles `BLS12_G1MSM`
+//! (`0x0c`) and `BLS12_PAIRING_CHECK` (`0x0f`) and exposes
+//! `verifyProof(bytes proof, uint256[] input) returns (bool)`. The proof is
+//! passed as the concatenation of `A`, `B` and `C` in the EIP-2537 point
+//! encoding, see [`encode_solidity_calldata`].
+//!
+//! [EIP-2537]: https://eips.ethereum.org/EIPS/eip-2537
+
+use std::io::{self, Write};
+
+use blstrs::{Bls12, G1Affine, G2Affine, Scalar};
+use byteorder::{BigEndian, WriteBytesExt};
+use group::prime::PrimeCurveAffine;
+
+use super::serde_impl::to_hex;
+use super::{Proof, VerifyingKey};
+
+/// The selector of `verifyProof(bytes,uint256[])`.
+pub const SOLIDITY_VERIFY_SELECTOR: [u8; 4] = [0x1e, 0x8e, 0x1e, 0x13];
+
+/// Size of a base field element in the EIP-2537 encoding, left padded with zeroes.
+const FP_SIZE: usize = 64;
+/// Size of a base field element in the zcash encoding.
+const FP_BYTES: usize = 48;
+const G1_SIZE: usize = 2 * FP_SIZE;
+const G2_SIZE: usize = 4 * FP_SIZE;
+/// Size of an encoded proof, `A || B || C`.
+const PROOF_SIZE: usize = 2 * G1_SIZE + G2_SIZE;
+
+/// Encodes a G1 point as `x || y`, the point at infinity as all zeroes.
+fn g1_to_eip2537(p: &G1Affine) -> [u8; G1_SIZE] {
+    let mut out = [0u8; G1_SIZE];
+    if bool::from(p.is_identity()) {
+        return out;
+    }
+    let bytes = p.to_uncompressed();
+    for i in 0..2 {
+        out[i * FP_SIZE + FP_SIZE - FP_BYTES..(i + 1) * FP_SIZE]
+            .copy_from_slice(&bytes[i * FP_BYTES..(i + 1) * FP_BYTES]);
+    }
+    out
+}
+
+/// Encodes a G2 point as `x.c0 || x.c1 || y.c0 || y.c1`, the point at
+/// infinity as all zeroes.
+fn g2_to_eip2537(p: &G2Affine) -> [u8; G2_SIZE] {
+    let mut out = [0u8; G2_SIZE];
+    if bool::from(p.is_identity()) {
+        return out;
+    }
+    // The uncompressed encoding stores the c1 coefficient first.
+    let bytes = p.to_uncompressed();
+    for (i, j) in [1, 0, 3, 2].iter().enumerate() {
+        out[i * FP_SIZE + FP_SIZE - FP_BYTES..(i + 1) * FP_SIZE]
+            .copy_from_slice(&bytes[j * FP_BYTES..(j + 1) * FP_BYTES]);
+    }
+    out
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+impl VerifyingKey<Bls12> {
+    /// Writes a self-contained Solidity contract named `contract_name` that
+    /// verifies proofs for this key with the EIP-2537 precompiles.
+    ///
+    /// The contract checks
+    /// `e(A, B) * e(alpha, -beta) * e(acc, -gamma) * e(C, -delta) == 1`,
+    /// with `acc = ic[0] + sum(input[i] * ic[i + 1])`, and returns `false`
+    /// for inputs that are not reduced or points the precompiles reject.
+    pub fn write_solidity_verifier<W: Write>(
+        &self,
+        contract_name: &str,
+        mut writer: W,
+    ) -> io::Result<()> {
+        if !is_identifier(contract_name) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid contract name {:?}", contract_name),
+            ));
+        }
+        if self.ic.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "verifying key without ic",
+            ));
+        }
+        let n_public = self.ic.len() - 1;
+        let g1 = |p: &G1Affine| to_hex(&g1_to_eip2537(p));
+        let neg_g2 = |p: &G2Affine| to_hex(&g2_to_eip2537(&-*p));
+
+        writeln!(writer, "// SPDX-License-Identifier: MIT OR Apache-2.0")?;
+        writeln!(
+            writer,
+            "// Generated by bellperson from a Groth16 verifying key. Do not edit."
+        )?;
+        writeln!(writer, "pragma solidity ^0.8.4;")?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "/// @notice Verifies Groth16 proofs over BLS12-381 with the EIP-2537 precompiles."
+        )?;
+        writeln!(writer, "contract {} {{", contract_name)?;
+        writeln!(writer, "    // Order of the BLS12-381 scalar field.")?;
+        writeln!(writer, "    uint256 private constant R = 0x73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001;")?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "    address private constant BLS12_G1MSM = address(0x0c);"
+        )?;
+        writeln!(
+            writer,
+            "    address private constant BLS12_PAIRING_CHECK = address(0x0f);"
+        )?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "    uint256 public constant N_PUBLIC = {};",
+            n_public
+        )?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "    bytes private constant ALPHA_G1 = hex\"{}\";",
+            g1(&self.alpha_g1)
+        )?;
+        writeln!(
+            writer,
+            "    bytes private constant NEG_BETA_G2 = hex\"{}\";",
+            neg_g2(&self.beta_g2)
+        )?;
+        writeln!(
+            writer,
+            "    bytes private constant NEG_GAMMA_G2 = hex\"{}\";",
+            neg_g2(&self.gamma_g2)
+        )?;
+        writeln!(
+            writer,
+            "    bytes private constant NEG_DELTA_G2 = hex\"{}\";",
+            neg_g2(&self.delta_g2)
+        )?;
+        for (i, ic) in self.ic.iter().enumerate() {
+            writeln!(
+                writer,
+                "    bytes private constant IC{} = hex\"{}\";",
+                i,
+                g1(ic)
+            )?;
+        }
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "    /// @param proof The points A, B and C in the EIP-2537 encoding."
+        )?;
+        writeln!(
+            writer,
+            "    /// @param input The public inputs, each smaller than R."
+        )?;
+        writeln!(writer, "    function verifyProof(bytes calldata proof, uint256[] calldata input) external view returns (bool) {{")?;
+        writeln!(
+            writer,
+            "        if (proof.length != {} || input.length != N_PUBLIC) {{",
+            PROOF_SIZE
+        )?;
+        writeln!(writer, "            return false;")?;
+        writeln!(writer, "        }}")?;
+        writeln!(
+            writer,
+            "        for (uint256 i = 0; i < input.length; i++) {{"
+        )?;
+        writeln!(writer, "            if (input[i] >= R) {{")?;
+        writeln!(writer, "                return false;")?;
+        writeln!(writer, "            }}")?;
+        writeln!(writer, "        }}")?;
+        writeln!(writer)?;
+        writeln!(writer, "        // acc = ic[0] + sum(input[i] * ic[i + 1])")?;
+        writeln!(
+            writer,
+            "        bytes memory msm = bytes.concat(IC0, bytes32(uint256(1)));"
+        )?;
+        for i in 0..n_public {
+            writeln!(
+                writer,
+                "        msm = bytes.concat(msm, IC{}, bytes32(input[{}]));",
+                i + 1,
+                i
+            )?;
+        }
+        writeln!(
+            writer,
+            "        (bool ok, bytes memory acc) = BLS12_G1MSM.staticcall(msm);"
+        )?;
+        writeln!(writer, "        if (!ok || acc.length != {}) {{", G1_SIZE)?;
+        writeln!(writer, "            return false;")?;
+        writeln!(writer, "        }}")?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "        // e(A, B) * e(alpha, -beta) * e(acc, -gamma) * e(C, -delta) == 1"
+        )?;
+        writeln!(writer, "        bytes memory pairing = bytes.concat(")?;
+        writeln!(writer, "            proof[:{}],", G1_SIZE + G2_SIZE)?;
+        writeln!(writer, "            ALPHA_G1,")?;
+        writeln!(writer, "            NEG_BETA_G2,")?;
+        writeln!(writer, "            acc,")?;
+        writeln!(writer, "            NEG_GAMMA_G2,")?;
+        writeln!(writer, "            proof[{}:],", G1_SIZE + G2_SIZE)?;
+        writeln!(writer, "            NEG_DELTA_G2")?;
+        writeln!(writer, "        );")?;
+        writeln!(writer, "        bytes memory out;")?;
+        writeln!(
+            writer,
+            "        (ok, out) = BLS12_PAIRING_CHECK.staticcall(pairing);"
+        )?;
+        writeln!(
+            writer,
+            "        return ok && out.length == 32 && abi.decode(out, (uint256)) == 1;"
+        )?;
+        writeln!(writer, "    }}")?;
+        writeln!(writer, "}}")?;
+
+        Ok(())
+    }
+}
+
+impl Proof<Bls12> {
+    /// Encodes the proof as `A || B || C` in the EIP-2537 point encoding, the
+    /// `proof` argument of the generated verifier.
+    pub fn to_eip2537_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(PROOF_SIZE);
+        out.extend_from_slice(&g1_to_eip2537(&self.a));
+        out.extend_from_slice(&g2_to_eip2537(&self.b));
+        out.extend_from_slice(&g1_to_eip2537(&self.c));
+        out
+    }
+}
+
+/// ABI encodes a call to `verifyProof(bytes,uint256[])` of a contract
+/// generated by [`VerifyingKey::write_solidity_verifier`].
+pub fn encode_solidity_calldata(proof: &Proof<Bls12>, inputs: &[Scalar]) -> Vec<u8> {
+    fn word(v: usize) -> [u8; 32] {
+        let mut out = [0u8; 32];
+        (&mut out[24..]).write_u64::<BigEndian>(v as u64).unwrap();
+        out
+    }
+
+    let mut out = Vec::with_capacity(4 + 4 * 32 + PROOF_SIZE + inputs.len() * 32);
+    out.extend_from_slice(&SOLIDITY_VERIFY_SELECTOR);
+    // Head: offsets of the two dynamic arguments, relative to the head start.
+    out.extend_from_slice(&word(2 * 32));
+    out.extend_from_slice(&word(2 * 32 + 32 + PROOF_SIZE));
+    // `bytes proof`, already a multiple of 32 bytes long.
+    out.extend_from_slice(&word(PROOF_SIZE));
+    out.extend_from_slice(&proof.to_eip2537_bytes());
+    // `uint256[] input`
+    out.extend_from_slice(&word(inputs.len()));
+    for input in inputs {
+        out.extend_from_slice(&input.to_bytes_be());
+    }
+    out
+}
+
+#[cfg(all(test, feature = "groth16"))]
+mod test_with_bls12_381 {
+    use super::*;
+
+    use std::convert::TryInto;
+
+    use ff::Field;
+    use group::{Curve, Group};
+    use pairing::{MillerLoopResult, MultiMillerLoop};
+    use rand_core::SeedableRng;
+
+    use crate::groth16::{prepare_verifying_key, verify_proof};
+
+    fn random_vk<R: rand_core::RngCore>(n_public: usize, rng: &mut R) -> VerifyingKey<Bls12> {
+        let mut g1 = || G1Affine::from(G1Affine::generator() * Scalar::random(&mut *rng));
+        let alpha_g1 = g1();
+        let beta_g1 = g1();
+        let delta_g1 = g1();
+        let ic = (0..=n_public).map(|_| g1()).collect();
+        let mut g2 = || G2Affine::from(G2Affine::generator() * Scalar::random(&mut *rng));
+        VerifyingKey {
+            alpha_g1,
+            beta_g1,
+            beta_g2: g2(),
+            gamma_g2: g2(),
+            delta_g1,
+            delta_g2: g2(),
+            ic,
+        }
+    }
+
+    fn fp(bytes: &[u8]) -> &[u8] {
+        assert!(bytes[..FP_SIZE - FP_BYTES].iter().all(|b| *b == 0));
+        &bytes[FP_SIZE - FP_BYTES..FP_SIZE]
+    }
+
+    fn g1_from_eip2537(bytes: &[u8]) -> G1Affine {
+        let mut repr = [0u8; 2 * FP_BYTES];
+        repr[..FP_BYTES].copy_from_slice(fp(&bytes[..FP_SIZE]));
+        repr[FP_BYTES..].copy_from_slice(fp(&bytes[FP_SIZE..]));
+        G1Affine::from_uncompressed(&repr).unwrap()
+    }
+
+    fn g2_from_eip2537(bytes: &[u8]) -> G2Affine {
+        let mut repr = [0u8; 4 * FP_BYTES];
+        for (i, j) in [1, 0, 3, 2].iter().enumerate() {
+            repr[i * FP_BYTES..(i + 1) * FP_BYTES]
+                .copy_from_slice(fp(&bytes[j * FP_SIZE..(j + 1) * FP_SIZE]));
+        }
+        G2Affine::from_uncompressed(&repr).unwrap()
+    }
+
+    fn word(bytes: &[u8]) -> usize {
+        assert!(bytes[..24].iter().all(|b| *b == 0));
+        u64::from_be_bytes(bytes[24..32].try_into().unwrap()) as usize
+    }
+
+    /// Decodes the calldata and evaluates it the way the generated contract does.
+    fn eval_calldata(vk: &VerifyingKey<Bls12>, calldata: &[u8]) -> bool {
+        assert_eq!(calldata[..4], SOLIDITY_VERIFY_SELECTOR);
+        let args = &calldata[4..];
+        let proof_at = word(&args[..32]);
+        let input_at = word(&args[32..64]);
+        assert_eq!(word(&args[proof_at..]), PROOF_SIZE);
+        let proof = &args[proof_at + 32..proof_at + 32 + PROOF_SIZE];
+        let n = word(&args[input_at..]);
+        assert_eq!(args.len(), input_at + 32 + n * 32);
+        if n + 1 != vk.ic.len() {
+            return false;
+        }
+
+        let mut acc = vk.ic[0].to_curve();
+        for (i, ic) in vk.ic[1..].iter().enumerate() {
+            let start = input_at + 32 + i * 32;
+            let input = Scalar::from_bytes_be(&args[start..start + 32].try_into().unwrap());
+            match Option::<Scalar>::from(input) {
+                Some(input) => acc += *ic * input,
+                None => return false,
+            }
+        }
+
+        let a = g1_from_eip2537(&proof[..G1_SIZE]);
+        let b = g2_from_eip2537(&proof[G1_SIZE..G1_SIZE + G2_SIZE]).into();
+        let c = g1_from_eip2537(&proof[G1_SIZE + G2_SIZE..]);
+        let neg_beta = (-vk.beta_g2).into();
+        let neg_gamma = (-vk.gamma_g2).into();
+        let neg_delta = (-vk.delta_g2).into();
+        let acc = acc.to_affine();
+        Bls12::multi_miller_loop(&[
+            (&a, &b),
+            (&vk.alpha_g1, &neg_beta),
+            (&acc, &neg_gamma),
+            (&c, &neg_delta),
+        ])
+        .final_exponentiation()
+        .is_identity()
+        .into()
+    }
+
+    #[test]
+    fn solidity_verifier_golden() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0);
+        let vk = random_vk(2, &mut rng);
+
+        let mut contract = Vec::new();
+        vk.write_solidity_verifier("Groth16Verifier", &mut contract)
+            .unwrap();
+        let contract = String::from_utf8(contract).unwrap();
+
+        let golden = "src/groth16/tests/groth16_verifier.sol";
+        if std::env::var("BELLPERSON_UPDATE_GOLDEN").is_ok() {
+            std::fs::write(golden, &contract).unwrap();
+        }
+        assert_eq!(contract, include_str!("tests/groth16_verifier.sol"));
+
+        assert!(vk.write_solidity_verifier("1Verifier", Vec::new()).is_err());
+        assert!(vk
+            .write_solidity_verifier("Groth16Verifier {", Vec::new())
+            .is_err());
+    }
+
+    #[test]
+    fn eip2537_encoding() {
+        // The generator of G1, left padded to 64 bytes per coordinate.
+        let g = g1_to_eip2537(&G1Affine::generator());
+        assert_eq!(g[..16], [0u8; 16]);
+        assert_eq!(g[16..20], [0x17, 0xf1, 0xd3, 0xa7]);
+        assert_eq!(g[64..80], [0u8; 16]);
+        assert_eq!(g[80..84], [0x08, 0xb3, 0xf4, 0x81]);
+        assert_eq!(g1_from_eip2537(&g), G1Affine::generator());
+
+        // x.c0 of the generator of G2 comes first.
+        let g = g2_to_eip2537(&G2Affine::generator());
+        assert_eq!(g[16..20], [0x02, 0x4a, 0xa2, 0xb2]);
+        assert_eq!(g[80..84], [0x13, 0xe0, 0x2b, 0x60]);
+        assert_eq!(g2_from_eip2537(&g), G2Affine::generator());
+
+        assert_eq!(g1_to_eip2537(&G1Affine::identity()), [0u8; G1_SIZE]);
+        assert_eq!(g2_to_eip2537(&G2Affine::identity()), [0u8; G2_SIZE]);
+    }
+
+    #[test]
+    fn solidity_calldata_matches_verify_proof() {
+        use crate::groth16::{create_random_proof, generate_random_parameters};
+        use crate::{Circuit, ConstraintSystem, SynthesisError};
+
+        // Proves knowledge of `x` such that `x * y = z` for public `y` and `z`.
+        struct Mul(Option<Scalar>, Option<Scalar>);
+
+        impl Circuit<Scalar> for Mul {
+            fn synthesize<CS: ConstraintSystem<Scalar>>(
+                self,
+                cs: &mut CS,
+            ) -> Result<(), SynthesisError> {
+                let x = cs.alloc(|| "x", || self.0.ok_or(SynthesisError::AssignmentMissing))?;
+                let y =
+                    cs.alloc_input(|| "y", || self.1.ok_or(SynthesisError::AssignmentMissing))?;
+                let z = cs.alloc_input(
+                    || "z",
+                    || {
+                        let mut z = self.0.ok_or(SynthesisError::AssignmentMissing)?;
+                        z *= self.1.ok_or(SynthesisError::AssignmentMissing)?;
+                        Ok(z)
+                    },
+                )?;
+                cs.enforce(|| "x * y = z", |lc| lc + x, |lc| lc + y, |lc| lc + z);
+                Ok(())
+            }
+        }
+
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(1);
+        let params = generate_random_parameters::<Bls12, _, _>(Mul(None, None), &mut rng).unwrap();
+        let pvk = prepare_verifying_key(&params.vk);
+
+        let x = Scalar::random(&mut rng);
+        let y = Scalar::random(&mut rng);
+        let proof = create_random_proof(Mul(Some(x), Some(y)), &params, &mut rng).unwrap();
+        let inputs = vec![y, x * y];
+
+        let other =
+            create_random_proof(Mul(Some(x), Some(y + Scalar::one())), &params, &mut rng).unwrap();
+        let cases = [
+            (proof.clone(), inputs.clone()),
+            (proof.clone(), vec![y, x * y + Scalar::one()]),
+            (proof.clone(), vec![x * y, y]),
+            (other, inputs.clone()),
+            (
+                Proof {
+                    c: (proof.c.to_curve().double()).to_affine(),
+                    ..proof.clone()
+                },
+                inputs,
+            ),
+        ];
+        for (i, (proof, inputs)) in cases.iter().enumerate() {
+            let expected = verify_proof(&pvk, proof, inputs).unwrap();
+            assert_eq!(expected, i == 0);
+            let calldata = encode_solidity_calldata(proof, inputs);
+            assert_eq!(calldata.len(), 4 + 4 * 32 + PROOF_SIZE + inputs.len() * 32);
+            assert_eq!(eval_calldata(&params.vk, &calldata), expected);
+        }
+
+        // A wrong number of inputs is rejected by the contract.
+        let calldata = encode_solidity_calldata(&proof, &[y]);
+        assert!(!eval_calldata(&params.vk, &calldata));
+    }
+}
@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: MIT OR Apache-2.0
+// Generated by bellperson from a Groth16 verifying key. Do not edit.
+pragma solidity ^0.8.4;
+
+/// @notice Verifies Groth16 proofs over BLS12-381 with the EIP-2537 precompiles.
+contract Groth16Verifier {
+    // Order of the BLS12-381 scalar field.
+    uint256 private constant R = 0x73eda753299d7d483339d80809a1d80553bda402fffe5bfeffffffff00000001;
+
+    address private constant BLS12_G1MSM = address(0x0c);
+    address private constant BLS12_PAIRING_CHECK = address(0x0f);
+
+    uint256 public constant N_PUBLIC = 2;
+
+    bytes private constant ALPHA_G1 = hex"0000000000000000000000000000000008e6e791b4b4da2dda0fd309d1a4c218146f60e5db4b682be59430a593bf0b7043c8e219b891d3cbb23c049014afa4c1000000000000000000000000000000000853035af18a161f2a84951c1f5c5a8e8c7be46831a32167669e5257e253ff30017fd6c234d0aea0dc07a9419a474f59";
+    bytes private constant NEG_BETA_G2 = hex"0000000000000000000000000000000013e6e1f23740da5436329580c83b713af8392b2ac91d98a035ad1d3f98ebc743348e0dd615e9296fe519659e3267ec4a0000000000000000000000000000000002aa260cf0143f6006734758c3e65d5cd1255698d3426704b5755553a64bec0f7162da55279a94e415da275ede0c0c860000000000000000000000000000000018560aa8a9f7743603c6f57a86dd9df46dcb3938570718c8db877c7baed39a6d09c5e98d64861516d1632587f4a35a640000000000000000000000000000000009edfba5ffde32822cb4aff1243e35fac4ab059a1b5c302f722b47df7dbcf3e7bbe085be5b5e1a92cc07c532dc503bb2";
+    bytes private constant NEG_GAMMA_G2 = hex"0000000000000000000000000000000004589ba5c9a82ef682619f33d7faa89a4de9a1ad06914a86dfad9c374c2637249fd82df45779a901b5c40a4bc54ea8b50000000000000000000000000000000000e35681aff5c4f2873b73a0a8659d385ff670271840b8e3609c00ec196cffd6ca2c21f91e80e56085bfabb3c8b1941e0000000000000000000000000000000005723c09b05e205a95a2c776b1ab9a9b39d584985e0eff962f8bfebe991c68c31d5c9e42504ab75287ca3d58e9a3b99a0000000000000000000000000000000015078b4ca9ab5f2fd45f472e1e1b2caeea7e7a526fbcb0e480085798916c40576f4108ef8f6d6822aea3b4126b144299";
+    bytes private constant NEG_DELTA_G2 = hex"000000000000000000000000000000000d91ec11d4ec4c6158cce383fd1d4c9ce00e348e1fe4f5121c625c0e5ae37440205ec3aa3ce50706750f4709795df31e000000000000000000000000000000000e3e86fca109b6ecd25f4413e90d4cee00b44df79d6d0771bd53bc1bbaec23571e4f64293de86d38c240734aa75c0a740000000000000000000000000000000016640660328f92ddc14f2d1befec2fa655fe55cd627ae96f1d287c9a40ac0378b6b3b7db6e162a4bb83f0bc03bc8138b00000000000000000000000000000000007a3cf833854c81df4596a76d59037968dc317ee78de3dc47b6f6f8662dc2826e70ed1c87968a3073f616e144142867";
+    bytes private constant IC0 = hex"0000000000000000000000000000000009a5e05d2f8c07180e37def1e9aaf3f7b7b7eb99524f1c0a5976173cf2c36b635a13611296fc327e0b369f674643a3bb0000000000000000000000000000000006d1d3197b8892b00b6c97e7e3d69241afc9ae40d97488256980e9571d0af4dc42aa59e2271bf432f06d8e29cb8b545a";
+    bytes private constant IC1 = hex"0000000000000000000000000000000008d947df77ec98edb19ddd2e0043c5f59d97d6d5f390869ff8587284d2017ee5950d8400edbcf55497b3effeaab40ad9000000000000000000000000000000001588a8637b5dc48c84bd1d4935d7f541d8b93d35a1722a057b8a06ca82ac8daac9ca810119276ccd206e0dad279cc212";
+    bytes private constant IC2 = hex"0000000000000000000000000000000006bb53f5024dfec7672933640e3be2cb5a3b3f3d403bc986e0a28405fb5834c33c1ddc1dbf834fb7bf3e3e4fa6925762000000000000000000000000000000000f92faedd83301360cfb742710587903065e83ba237d8a842269ec8b088208605acb3cd40be7e7697e9dc05d1e7467e6";
+
+    /// @param proof The points A, B and C in the EIP-2537 encoding.
+    /// @param input The public inputs, each smaller than R.
+    function verifyProof(bytes calldata proof, uint256[] calldata input) external view returns (bool) {
+        if (proof.length != 512 || input.length != N_PUBLIC) {
+            return false;
+        }
+        for (uint256 i = 0; i < input.length; i++) {
+            if (input[i] >= R) {
+                return false;
+            }
+        }
+
+        // acc = ic[0] + sum(input[i] * ic[i + 1])
+        bytes memory msm = bytes.concat(IC0, bytes32(uint256(1)));
+        msm = bytes.concat(msm, IC1, bytes32(input[0]));
+        msm = bytes.concat(msm, IC2, bytes32(input[1]));
+        (bool ok, bytes memory acc) = BLS12_G1MSM.staticcall(msm);
+        if (!ok || acc.length != 128) {
+            return false;
+        }
+
+        // e(A, B) * e(alpha, -beta) * e(acc, -gamma) * e(C, -delta) == 1
+        bytes memory pairing = bytes.concat(
+            proof[:384],
+            ALPHA_G1,
+            NEG_BETA_G2,
+            acc,
+            NEG_GAMMA_G2,
+            proof[384:],
+            NEG_DELTA_G2
+        );
+        bytes memory out;
+        (ok, out) = BLS12_PAIRING_CHECK.staticcall(pairing);
+        return ok && out.length == 32 && abi.decode(out, (uint256)) == 1;
+    }
+}
@@ -0,0 +1,455 @@
+use group::UncompressedEncoding;
+use pairing::MultiMillerLoop;
+use rand::Rng;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use byteorder::{BigEndian, ByteOrder};
+use log::warn;
+use memmap::{Mmap, MmapOptions};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use super::{read_g1, read_g2, MappedParameters, Parameters, Section, VerifyingKey};
+
+/// Version of the sidecar file format.
+const SIDECAR_VERSION: u32 = 1;
+
+/// Extension appended to the parameter file name to get its sidecar.
+const SIDECAR_EXTENSION: &str = ".validated";
+
+/// How a checked load trusts the sidecar left by a previous validation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SidecarCheck {
+    /// Recompute the digest of the file and compare it with the recorded one.
+    Digest,
+    /// Compare the size and modification time of the file with the recorded
+    /// ones, and fully check the given number of points chosen at random.
+    SampledPoints(usize),
+}
+
+/// Options for the validated loads of parameters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ValidationOptions {
+    pub check: SidecarCheck,
+    /// Ignore any existing sidecar and check every point again.
+    pub force_revalidation: bool,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        ValidationOptions {
+            check: SidecarCheck::Digest,
+            force_revalidation: false,
+        }
+    }
+}
+
+/// Result of a full validation of a parameter file, stored next to it.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+struct Sidecar {
+    version: u32,
+    digest: [u8; 32],
+    size: u64,
+    mtime_secs: u64,
+    mtime_nanos: u32,
+    valid: bool,
+}
+
+enum SidecarStatus {
+    Valid,
+    Invalid,
+    Stale,
+}
+
+impl<E> Parameters<E>
+where
+    E: MultiMillerLoop,
+{
+    /// Reads the parameters at `param_file_path` with all the checks of
+    /// `read_mmap(.., true)`, but only the first time: the result of the full
+    /// validation is recorded in a sidecar file next to the parameters, and
+    /// later loads that trust the sidecar (see [`SidecarCheck`]) decode the
+    /// points unchecked. When the sidecar can't be written, a warning is
+    /// logged and the next load validates the parameters again.
+    pub fn read_validated(param_file_path: &Path, options: &ValidationOptions) -> io::Result<Self> {
+        let param_file = File::open(param_file_path)?;
+        let params = unsafe { MmapOptions::new().map(&param_file)? };
+
+        match sidecar_status::<E>(param_file_path, &param_file, &params, options)? {
+            SidecarStatus::Valid => Self::read_mmap(&params, false),
+            SidecarStatus::Invalid => Err(failed_validation(param_file_path)),
+            SidecarStatus::Stale => {
+                let res = Self::read_mmap(&params, true);
+                record_validation(param_file_path, &param_file, &params, res.is_ok());
+                res
+            }
+        }
+    }
+
+    /// Builds mapped parameters that decode their points unchecked, once every
+    /// point of the file has been checked. As with [`Parameters::read_validated`],
+    /// the full check is only done when the sidecar file cannot be trusted.
+    pub fn build_mapped_parameters_validated(
+        param_file_path: PathBuf,
+        options: &ValidationOptions,
+    ) -> io::Result<MappedParameters<E>> {
+        {
+            let param_file = File::open(&param_file_path)?;
+            let params = unsafe { MmapOptions::new().map(&param_file)? };
+
+            match sidecar_status::<E>(&param_file_path, &param_file, &params, options)? {
+                SidecarStatus::Valid => {}
+                SidecarStatus::Invalid => return Err(failed_validation(&param_file_path)),
+                SidecarStatus::Stale => {
+                    let res = check_points::<E>(&params, &sections::<E>(&params)?);
+                    record_validation(&param_file_path, &param_file, &params, res.is_ok());
+                    res?;
+                }
+            }
+        }
+
+        Self::build_mapped_parameters(param_file_path, false)
+    }
+}
+
+/// Path of the sidecar recording the validation of `param_file_path`.
+pub fn sidecar_path(param_file_path: &Path) -> PathBuf {
+    let mut path = OsString::from(param_file_path.as_os_str());
+    path.push(SIDECAR_EXTENSION);
+    PathBuf::from(path)
+}
+
+fn failed_validation(param_file_path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "parameters failed validation, as recorded in {}",
+            sidecar_path(param_file_path).display()
+        ),
+    )
+}
+
+fn sidecar_status<E: MultiMillerLoop>(
+    param_file_path: &Path,
+    param_file: &File,
+    params: &Mmap,
+    options: &ValidationOptions,
+) -> io::Result<SidecarStatus> {
+    if options.force_revalidation {
+        return Ok(SidecarStatus::Stale);
+    }
+
+    let sidecar: Sidecar = match fs::read(sidecar_path(param_file_path))
+        .ok()
+        .and_then(|bytes| bincode::deserialize(&bytes).ok())
+    {
+        Some(sidecar) => sidecar,
+        None => return Ok(SidecarStatus::Stale),
+    };
+    if sidecar.version != SIDECAR_VERSION || sidecar.size != params.len() as u64 {
+        return Ok(SidecarStatus::Stale);
+    }
+
+    let trusted = match options.check {
+        SidecarCheck::Digest => digest(params) == sidecar.digest,
+        SidecarCheck::SampledPoints(samples) => {
+            let (mtime_secs, mtime_nanos) = mtime(param_file)?;
+            (mtime_secs, mtime_nanos) == (sidecar.mtime_secs, sidecar.mtime_nanos)
+                // A recorded failure does not need to be confirmed.
+                && (!sidecar.valid
+                    || sections::<E>(params)
+                        .and_then(|sections| check_sample::<E>(params, &sections, samples))
+                        .is_ok())
+        }
+    };
+
+    Ok(match (trusted, sidecar.valid) {
+        (false, _) => SidecarStatus::Stale,
+        (true, true) => SidecarStatus::Valid,
+        (true, false) => SidecarStatus::Invalid,
+    })
+}
+
+/// Writes the sidecar recording a validation. The sidecar is only a cache, so
+/// failing to write it, e.g. in a read-only directory, does not fail the load.
+fn record_validation(param_file_path: &Path, param_file: &File, params: &Mmap, valid: bool) {
+    if let Err(err) = write_sidecar(param_file_path, param_file, params, valid) {
+        warn!(
+            "failed to write the validation sidecar of {}: {}",
+            param_file_path.display(),
+            err
+        );
+    }
+}
+
+fn write_sidecar(
+    param_file_path: &Path,
+    param_file: &File,
+    params: &Mmap,
+    valid: bool,
+) -> io::Result<()> {
+    let (mtime_secs, mtime_nanos) = mtime(param_file)?;
+    let sidecar = Sidecar {
+        version: SIDECAR_VERSION,
+        digest: digest(params),
+        size: params.len() as u64,
+        mtime_secs,
+        mtime_nanos,
+        valid,
+    };
+    let bytes = bincode::serialize(&sidecar)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+
+    // Write to a temporary file first, so that a sidecar is never read while
+    // partially written. The temporary file is unique to this write, as other
+    // processes may be validating the same parameters.
+    let path = sidecar_path(param_file_path);
+    let mut tmp_path = OsString::from(path.as_os_str());
+    tmp_path.push(format!(
+        ".{}.{:016x}.tmp",
+        std::process::id(),
+        rand::random::<u64>()
+    ));
+    let res = fs::write(&tmp_path, bytes).and_then(|_| fs::rename(&tmp_path, &path));
+    if res.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    res
+}
+
+fn digest(params: &Mmap) -> [u8; 32] {
+    let mut digest = [0u8; 32];
+    digest.copy_from_slice(&Sha256::digest(&params[..]));
+    digest
+}
+
+fn mtime(param_file: &File) -> io::Result<(u64, u32)> {
+    let mtime = param_file
+        .metadata()?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok((mtime.as_secs(), mtime.subsec_nanos()))
+}
+
+/// Returns the sections of points of the parameters, in file order: h, l, a,
+/// b_g1 and b_g2. This reads (and checks) the verifying key.
+fn sections<E: MultiMillerLoop>(params: &Mmap) -> io::Result<[Section; 5]> {
+    let g1_len = mem::size_of::<<E::G1Affine as UncompressedEncoding>::Uncompressed>();
+    let g2_len = mem::size_of::<<E::G2Affine as UncompressedEncoding>::Uncompressed>();
+
+    let mut offset = 0;
+    VerifyingKey::<E>::read_mmap(params, &mut offset)?;
+
+    let mut read_section = |point_len: usize| -> io::Result<Section> {
+        let raw_len = params.get(offset..offset + 4).ok_or_else(truncated)?;
+        let len = BigEndian::read_u32(raw_len) as usize;
+
+        let section = Section {
+            offset: (offset + 4) as u64,
+            len,
+        };
+        offset += 4 + len * point_len;
+        if offset > params.len() {
+            return Err(truncated());
+        }
+
+        Ok(section)
+    };
+
+    Ok([
+        read_section(g1_len)?,
+        read_section(g1_len)?,
+        read_section(g1_len)?,
+        read_section(g1_len)?,
+        read_section(g2_len)?,
+    ])
+}
+
+fn truncated() -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, "parameter file is truncated")
+}
+
+/// Fully checks the `i`-th point of a section, `g2` telling which group the
+/// section belongs to.
+fn check_point<E: MultiMillerLoop>(
+    params: &Mmap,
+    section: &Section,
+    g2: bool,
+    i: usize,
+) -> io::Result<()> {
+    if g2 {
+        let len = mem::size_of::<<E::G2Affine as UncompressedEncoding>::Uncompressed>();
+        let start = section.offset as usize + i * len;
+        read_g2::<E>(params, start..start + len, true).map(|_| ())
+    } else {
+        let len = mem::size_of::<<E::G1Affine as UncompressedEncoding>::Uncompressed>();
+        let start = section.offset as usize + i * len;
+        read_g1::<E>(params, start..start + len, true).map(|_| ())
+    }
+}
+
+fn check_points<E: MultiMillerLoop>(params: &Mmap, sections: &[Section; 5]) -> io::Result<()> {
+    for (i, section) in sections.iter().enumerate() {
+        (0..section.len)
+            .into_par_iter()
+            .try_for_each(|j| check_point::<E>(params, section, i == 4, j))?;
+    }
+
+    Ok(())
+}
+
+/// Fully checks `samples` points chosen at random, or all of them if there
+/// are fewer.
+fn check_sample<E: MultiMillerLoop>(
+    params: &Mmap,
+    sections: &[Section; 5],
+    samples: usize,
+) -> io::Result<()> {
+    let total: usize = sections.iter().map(|s| s.len).sum();
+    if samples >= total {
+        return check_points::<E>(params, sections);
+    }
+
+    let mut rng = rand::thread_rng();
+    let indices: Vec<usize> = (0..samples).map(|_| rng.gen_range(0..total)).collect();
+
+    indices.into_par_iter().try_for_each(|mut j| {
+        for (i, section) in sections.iter().enumerate() {
+            if j < section.len {
+                return check_point::<E>(params, section, i == 4, j);
+            }
+            j -= section.len;
+        }
+        unreachable!("sampled index is smaller than the number of points")
+    })
+}
+
+#[cfg(test)]
+mod test_with_bls12_381 {
+    use super::*;
+    use crate::groth16::generate_random_parameters;
+    use crate::{Circuit, ConstraintSystem, SynthesisError};
+
+    use blstrs::{Bls12, Scalar as Fr};
+    use ff::Field;
+    use rand_core::SeedableRng;
+    use std::io::Write;
+
+    #[derive(Clone)]
+    struct MultiplyCircuit;
+
+    impl Circuit<Fr> for MultiplyCircuit {
+        fn synthesize<CS: ConstraintSystem<Fr>>(self, cs: &mut CS) -> Result<(), SynthesisError> {
+            let a = cs.alloc(|| "a", || Ok(Fr::one()))?;
+            let b = cs.alloc(|| "b", || Ok(Fr::one()))?;
+            let c = cs.alloc_input(|| "c", || Ok(Fr::one()))?;
+            cs.enforce(|| "a*b=c", |lc| lc + a, |lc| lc + b, |lc| lc + c);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn validated_loads() {
+        let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+        let params = generate_random_parameters::<Bls12, _, _>(MultiplyCircuit, &mut rng).unwrap();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("params");
+        let mut bytes = vec![];
+        params.write(&mut bytes).unwrap();
+        fs::write(&path, &bytes).unwrap();
+        let original = bytes.clone();
+
+        let digest_check = ValidationOptions::default();
+        let sampled_check = ValidationOptions {
+            check: SidecarCheck::SampledPoints(2),
+            ..ValidationOptions::default()
+        };
+        let forced = ValidationOptions {
+            force_revalidation: true,
+            ..ValidationOptions::default()
+        };
+
+        // The first load validates and records the result.
+        assert!(!sidecar_path(&path).exists());
+        assert!(Parameters::<Bls12>::read_validated(&path, &digest_check).unwrap() == params);
+        let sidecar: Sidecar =
+            bincode::deserialize(&fs::read(sidecar_path(&path)).unwrap()).unwrap();
+        assert!(sidecar.valid);
+        assert_eq!(sidecar.size, bytes.len() as u64);
+
+        // Later loads trust the sidecar, including a recorded failure, unless
+        // the revalidation is forced.
+        let failed = Sidecar {
+            valid: false,
+            ..sidecar
+        };
+        fs::write(sidecar_path(&path), bincode::serialize(&failed).unwrap()).unwrap();
+        assert!(Parameters::<Bls12>::read_validated(&path, &digest_check).is_err());
+        assert!(Parameters::<Bls12>::read_validated(&path, &sampled_check).is_err());
+        assert!(Parameters::<Bls12>::build_mapped_parameters_validated(
+            path.clone(),
+            &digest_check
+        )
+        .is_err());
+
+        let mapped =
+            Parameters::<Bls12>::build_mapped_parameters_validated(path.clone(), &forced).unwrap();
+        assert!(!mapped.checked);
+        assert!(mapped.vk == params.vk);
+        assert!(Parameters::<Bls12>::read_validated(&path, &sampled_check).unwrap() == params);
+
+        // Corrupting a point of the l query changes the digest and fails the
+        // new validation.
+        let l_offset =
+            sections::<Bls12>(&unsafe { Mmap::map(&File::open(&path).unwrap()) }.unwrap()).unwrap()
+                [1]
+            .offset as usize;
+        bytes[l_offset + 10] ^= 1;
+        let mut file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        file.write_all(&bytes).unwrap();
+        drop(file);
+
+        assert!(Parameters::<Bls12>::read_validated(&path, &digest_check).is_err());
+        let sidecar: Sidecar =
+            bincode::deserialize(&fs::read(sidecar_path(&path)).unwrap()).unwrap();
+        assert!(!sidecar.valid);
+
+        // Sampling every point finds the corruption even when the sidecar
+        // claims the file is valid.
+        let valid = Sidecar {
+            valid: true,
+            ..sidecar
+        };
+        fs::write(sidecar_path(&path), bincode::serialize(&valid).unwrap()).unwrap();
+        let sample_all = ValidationOptions {
+            check: SidecarCheck::SampledPoints(usize::MAX),
+            ..ValidationOptions::default()
+        };
+        assert!(Parameters::<Bls12>::read_validated(&path, &sample_all).is_err());
+
+        // A sidecar that can't be written doesn't fail the load, which is
+        // then validated every time.
+        fs::write(&path, &original).unwrap();
+        fs::remove_file(sidecar_path(&path)).unwrap();
+        fs::create_dir(sidecar_path(&path)).unwrap();
+        fs::write(sidecar_path(&path).join("file"), b"").unwrap();
+        for _ in 0..2 {
+            assert!(Parameters::<Bls12>::read_validated(&path, &digest_check).unwrap() == params);
+            assert!(Parameters::<Bls12>::build_mapped_parameters_validated(
+                path.clone(),
+                &digest_check
+            )
+            .is_ok());
+        }
+        // No temporary file is left behind.
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+}
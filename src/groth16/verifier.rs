@@ -1,9 +1,10 @@
 use std::ops::{AddAssign, Mul, MulAssign};
 
+use crate::parallel::prelude::*;
 use ff::{Field, PrimeField};
 use group::{prime::PrimeCurveAffine, Curve, Group};
 use pairing::{Engine, MillerLoopResult, MultiMillerLoop};
-use rayon::prelude::*;
+use rand_core::RngCore;
 
 use super::{multiscalar, PreparedVerifyingKey, Proof, VerifyingKey};
 use crate::{le_bytes_to_u64s, SynthesisError};
@@ -31,6 +32,9 @@ where
         alpha_g1: vk.alpha_g1.to_curve(),
         beta_g2: vk.beta_g2.into(),
         ic_projective: vk.ic.par_iter().map(|i| i.to_curve()).collect(),
+        beta_g2_affine: vk.beta_g2,
+        gamma_g2_affine: vk.gamma_g2,
+        delta_g2_affine: vk.delta_g2,
     }
 }
 
@@ -66,7 +70,7 @@ where
     let mut ml_acc = <E as MultiMillerLoop>::Result::default();
 
     // Start the two independent miller loops
-    rayon::in_place_scope(|s| {
+    crate::parallel::in_place_scope(|s| {
         // - Thread 1: Calculate ML alpha * beta
         let ml_a_b = &mut ml_a_b;
         s.spawn(move |_| {
@@ -105,6 +109,24 @@ where
     Ok(actual == pvk.alpha_g1_beta_g2)
 }
 
+/// Samples a random 128 bit scalar used to combine proofs in a batch.
+fn random_batch_scalar<E: Engine, R: RngCore>(rng: &mut R) -> E::Fr {
+    let mut repr = E::Fr::zero().to_repr();
+    let mut repr_u64s = le_bytes_to_u64s(&repr.as_ref());
+    assert!(repr_u64s.len() > 1);
+
+    repr_u64s[0] = rng.next_u64();
+    repr_u64s[1] = rng.next_u64();
+
+    for (i, limb) in repr_u64s.iter().enumerate() {
+        let start = i * 8;
+        let stop = start + 8;
+        repr.as_mut()[start..stop].copy_from_slice(&limb.to_le_bytes());
+    }
+
+    E::Fr::from_repr(repr).unwrap()
+}
+
 /// Randomized batch verification - see Appendix B.2 in Zcash spec
 pub fn verify_proofs_batch<'a, E, R>(
     pvk: &'a PreparedVerifyingKey<E>,
@@ -115,7 +137,7 @@ pub fn verify_proofs_batch<'a, E, R>(
 where
     E: MultiMillerLoop,
     <E::Fr as PrimeField>::Repr: Sync + Copy,
-    R: rand::RngCore,
+    R: RngCore,
 {
     debug_assert_eq!(proofs.len(), public_inputs.len());
 
@@ -140,24 +162,7 @@ where
     let mut accum_y = E::Fr::zero();
 
     for _ in 0..proof_num {
-        use rand::Rng;
-
-        let t: u128 = rng.gen();
-
-        let mut repr = E::Fr::zero().to_repr();
-        let mut repr_u64s = le_bytes_to_u64s(&repr.as_ref());
-        assert!(repr_u64s.len() > 1);
-
-        repr_u64s[0] = (t & (-1i64 as u128) >> 64) as u64;
-        repr_u64s[1] = (t >> 64) as u64;
-
-        for (i, limb) in repr_u64s.iter().enumerate() {
-            let start = i * 8;
-            let stop = start + 8;
-            repr.as_mut()[start..stop].copy_from_slice(&limb.to_le_bytes());
-        }
-
-        let fr = E::Fr::from_repr(repr).unwrap();
+        let fr = random_batch_scalar::<E, _>(rng);
         let repr = fr.to_repr();
 
         // calculate sum
@@ -180,7 +185,7 @@ where
     let accum_y = &accum_y;
     let rand_z_repr = &rand_z_repr;
 
-    rayon::in_place_scope(|s| {
+    crate::parallel::in_place_scope(|s| {
         // - Thread 1: Calculate MillerLoop(\sum Accum_Gamma)
         let ml_g = &mut ml_g;
         s.spawn(move |_| {
@@ -274,3 +279,125 @@ where
     let actual = ml_all.final_exponentiation();
     Ok(actual == y)
 }
+
+/// A proof to verify in a batch, with its key and public inputs.
+pub type BatchItem<'a, E> = (
+    &'a PreparedVerifyingKey<E>,
+    &'a Proof<E>,
+    &'a [<E as Engine>::Fr],
+);
+
+/// Randomized batch verification of proofs for different circuits, given as
+/// `(pvk, proof, public_inputs)` triples.
+///
+/// Each proof is weighted by a random 128 bit scalar `z_j` and all equations
+/// are checked at once as
+/// `prod_j e(z_j A_j, -B_j) * e(sum z_j alpha, beta) * e(sum z_j acc_j, gamma) * e(sum z_j C_j, delta) == 1`,
+/// with one Miller loop term per distinct `beta`, `gamma` and `delta` among the
+/// keys and a single final exponentiation. Proofs using the same key (by
+/// reference) share a single multiscalar multiplication for their inputs.
+pub fn verify_proofs_batch_multi<'a, E, R>(
+    rng: &mut R,
+    batch: &[BatchItem<'a, E>],
+) -> Result<bool, SynthesisError>
+where
+    E: MultiMillerLoop,
+    <E::Fr as PrimeField>::Repr: Sync + Copy,
+    R: RngCore,
+{
+    for (pvk, _, public_inputs) in batch {
+        if (public_inputs.len() + 1) != pvk.ic.len() {
+            return Err(SynthesisError::MalformedVerifyingKey);
+        }
+    }
+    if batch.is_empty() {
+        return Ok(true);
+    }
+
+    let rand_z: Vec<E::Fr> = batch
+        .iter()
+        .map(|_| random_batch_scalar::<E, _>(rng))
+        .collect();
+
+    // Group the proofs by key, comparing keys by reference.
+    let mut keys: Vec<(&PreparedVerifyingKey<E>, Vec<usize>)> = Vec::new();
+    for (j, (pvk, _, _)) in batch.iter().enumerate() {
+        match keys.iter_mut().find(|(k, _)| std::ptr::eq(*k, *pvk)) {
+            Some((_, proofs)) => proofs.push(j),
+            None => keys.push((pvk, vec![j])),
+        }
+    }
+
+    // Per key: sum z_j * alpha, sum z_j * acc_j and sum z_j * C_j.
+    let key_terms: Vec<(E::G1, E::G1, E::G1)> = keys
+        .par_iter()
+        .map(|(pvk, proofs)| {
+            let mut scalars = vec![E::Fr::zero(); pvk.ic.len()];
+            let mut acc_c = E::G1::identity();
+            for &j in proofs {
+                let (_, proof, public_inputs) = batch[j];
+                let z = rand_z[j];
+                scalars[0] += z;
+                for (s, input) in scalars[1..].iter_mut().zip(public_inputs.iter()) {
+                    *s += z * input;
+                }
+                acc_c += proof.c * z;
+            }
+
+            let scalars_repr: Vec<_> = scalars.iter().map(PrimeField::to_repr).collect();
+            let acc_inputs =
+                multiscalar::par_multiscalar::<&multiscalar::Getter<E::G1Affine>, E::G1Affine>(
+                    &multiscalar::ScalarList::Slice(&scalars_repr),
+                    &pvk.multiscalar,
+                    std::mem::size_of::<<E::Fr as PrimeField>::Repr>() * 8,
+                );
+
+            (pvk.alpha_g1 * scalars[0], acc_inputs, acc_c)
+        })
+        .collect();
+
+    // Merge the G1 sums of terms that share a G2 element.
+    let mut terms: Vec<(E::G1, &E::G2Affine, &E::G2Prepared)> = Vec::new();
+    let mut add_term = |g1: E::G1, g2: &'a E::G2Affine, prepared: &'a E::G2Prepared| match terms
+        .iter_mut()
+        .find(|(_, other, _)| *other == g2)
+    {
+        Some((acc, _, _)) => *acc += g1,
+        None => terms.push((g1, g2, prepared)),
+    };
+    for ((pvk, _), (alpha, inputs, c)) in keys.iter().zip(key_terms) {
+        add_term(alpha, &pvk.beta_g2_affine, &pvk.beta_g2);
+        add_term(inputs, &pvk.gamma_g2_affine, &pvk.gamma_g2);
+        add_term(c, &pvk.delta_g2_affine, &pvk.delta_g2);
+    }
+
+    let mut g1_terms = vec![E::G1Affine::identity(); terms.len()];
+    E::G1::batch_normalize(
+        &terms.iter().map(|(g1, _, _)| *g1).collect::<Vec<_>>(),
+        &mut g1_terms,
+    );
+    let shared: Vec<_> = g1_terms
+        .iter()
+        .zip(terms.iter())
+        .map(|(g1, (_, _, prepared))| (g1, *prepared))
+        .collect();
+
+    // prod_j e(z_j A_j, -B_j)
+    let ml_ab = batch
+        .par_iter()
+        .zip(rand_z.par_iter())
+        .map(|((_, proof, _), z)| {
+            let a = (proof.a * z).to_affine();
+            let neg_b = (-proof.b).into();
+            E::multi_miller_loop(&[(&a, &neg_b)])
+        })
+        .reduce(<E as MultiMillerLoop>::Result::default, |mut acc, ml| {
+            acc += ml;
+            acc
+        });
+
+    let mut ml_all = E::multi_miller_loop(&shared);
+    ml_all += ml_ab;
+
+    Ok(bool::from(ml_all.final_exponentiation().is_identity()))
+}
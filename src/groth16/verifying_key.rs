@@ -1,12 +1,18 @@
-use group::{prime::PrimeCurveAffine, UncompressedEncoding};
+use blstrs::Compress;
+use group::{prime::PrimeCurveAffine, Curve, UncompressedEncoding};
 use pairing::{Engine, MultiMillerLoop};
 
+use crate::parallel::prelude::*;
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+#[cfg(feature = "memmap")]
 use memmap::Mmap;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::io::{self, Read, Write};
+#[cfg(feature = "memmap")]
 use std::mem;
 
 use super::multiscalar;
+use super::serde_impl;
 
 #[derive(Debug, Clone)]
 pub struct VerifyingKey<E: Engine + MultiMillerLoop> {
@@ -53,6 +59,18 @@ fn read_uncompressed_point<C: UncompressedEncoding>(repr: &C::Uncompressed) -> i
     Option::from(opt).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not on curve"))
 }
 
+impl<E: Engine + MultiMillerLoop> Serialize for VerifyingKey<E> {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serde_impl::serialize(s, |w| self.write(w))
+    }
+}
+
+impl<'de, E: Engine + MultiMillerLoop> Deserialize<'de> for VerifyingKey<E> {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        serde_impl::deserialize(d, "verifying key", |r| Self::read(r))
+    }
+}
+
 impl<E: Engine + MultiMillerLoop> VerifyingKey<E> {
     pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
         writer.write_all(self.alpha_g1.to_uncompressed().as_ref())?;
@@ -118,6 +136,7 @@ impl<E: Engine + MultiMillerLoop> VerifyingKey<E> {
         })
     }
 
+    #[cfg(feature = "memmap")]
     pub fn read_mmap(mmap: &Mmap, offset: &mut usize) -> io::Result<Self> {
         let u32_len = mem::size_of::<u32>();
         let g1_len = mem::size_of::<<E::G1Affine as UncompressedEncoding>::Uncompressed>();
@@ -217,4 +236,343 @@ where
     pub(crate) alpha_g1: E::G1,
     pub(crate) beta_g2: <E as MultiMillerLoop>::G2Prepared,
     pub(crate) ic_projective: Vec<E::G1>,
+
+    /// Affine versions of beta, gamma and delta in G2, the prepared versions
+    /// can not be serialized directly.
+    pub(crate) beta_g2_affine: E::G2Affine,
+    pub(crate) gamma_g2_affine: E::G2Affine,
+    pub(crate) delta_g2_affine: E::G2Affine,
+}
+
+const PREPARED_VK_MAGIC: [u8; 4] = *b"BPVK";
+const PREPARED_VK_VERSION: u32 = 1;
+
+impl<E> PreparedVerifyingKey<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    /// Writes the prepared key, including the precomputed tables for the
+    /// public inputs, so that it can be loaded without running
+    /// `prepare_verifying_key` again.
+    pub fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&PREPARED_VK_MAGIC)?;
+        writer.write_u32::<BigEndian>(PREPARED_VK_VERSION)?;
+
+        self.alpha_g1_beta_g2.write_compressed(&mut writer)?;
+        writer.write_all(self.alpha_g1.to_affine().to_uncompressed().as_ref())?;
+        writer.write_all(self.beta_g2_affine.to_uncompressed().as_ref())?;
+        writer.write_all(self.gamma_g2_affine.to_uncompressed().as_ref())?;
+        writer.write_all(self.delta_g2_affine.to_uncompressed().as_ref())?;
+
+        writer.write_u32::<BigEndian>(self.ic.len() as u32)?;
+        for ic in &self.ic {
+            writer.write_all(ic.to_uncompressed().as_ref())?;
+        }
+
+        self.multiscalar.write(&mut writer)
+    }
+
+    /// Reads a prepared key written by `write`.
+    ///
+    /// If `vk` is given, the decoded key is checked to be exactly the one
+    /// `prepare_verifying_key` derives from it, which costs a single pairing
+    /// plus one pass over the precomputed tables. Without it, the file is
+    /// trusted and the precomputed tables are not validated.
+    pub fn read<R: Read>(mut reader: R, vk: Option<&VerifyingKey<E>>) -> io::Result<Self> {
+        let head = PreparedHead::read(&mut reader)?;
+        let multiscalar = multiscalar::MultiscalarPrecompOwned::read(&mut reader, false)?;
+
+        Self::assemble(head, multiscalar, vk)
+    }
+
+    /// Reads a prepared key written by `write` from an untrusted source: the
+    /// precomputed tables must be the ones of its `ic` and `alpha_g1_beta_g2`
+    /// the pairing of its `alpha_g1` and `beta_g2`.
+    fn read_untrusted<R: Read>(mut reader: R) -> io::Result<Self> {
+        let head = PreparedHead::read(&mut reader)?;
+        let multiscalar = multiscalar::MultiscalarPrecompOwned::read(&mut reader, true)?;
+        if !multiscalar.is_consistent_with(&head.ic)
+            || E::pairing(&head.alpha_g1, &head.beta_g2) != head.alpha_g1_beta_g2
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "prepared verifying key is not consistent",
+            ));
+        }
+
+        Self::assemble(head, multiscalar, None)
+    }
+
+    /// Same as `read`, but reads from a memory map starting at `offset`, the
+    /// precomputed tables are decoded in parallel. The offset is advanced past
+    /// the prepared key.
+    #[cfg(feature = "memmap")]
+    pub fn read_mmap(
+        mmap: &Mmap,
+        offset: &mut usize,
+        vk: Option<&VerifyingKey<E>>,
+    ) -> io::Result<Self> {
+        let mut data = mmap.get(*offset..).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::UnexpectedEof, "prepared key is truncated")
+        })?;
+        let available = data.len();
+        let head = PreparedHead::read(&mut data)?;
+        *offset += available - data.len();
+
+        let multiscalar = multiscalar::MultiscalarPrecompOwned::read_mmap(mmap, offset, false)?;
+
+        Self::assemble(head, multiscalar, vk)
+    }
+
+    fn assemble(
+        head: PreparedHead<E>,
+        multiscalar: multiscalar::MultiscalarPrecompOwned<E::G1Affine>,
+        vk: Option<&VerifyingKey<E>>,
+    ) -> io::Result<Self> {
+        let PreparedHead {
+            alpha_g1_beta_g2,
+            alpha_g1,
+            beta_g2,
+            gamma_g2,
+            delta_g2,
+            ic,
+        } = head;
+
+        if let Some(vk) = vk {
+            let matches = vk.alpha_g1 == alpha_g1
+                && vk.beta_g2 == beta_g2
+                && vk.gamma_g2 == gamma_g2
+                && vk.delta_g2 == delta_g2
+                && vk.ic == ic
+                && multiscalar.is_consistent_with(&vk.ic)
+                && E::pairing(&vk.alpha_g1, &vk.beta_g2) == alpha_g1_beta_g2;
+            if !matches {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "prepared verifying key does not match the verifying key",
+                ));
+            }
+        }
+
+        Ok(PreparedVerifyingKey {
+            alpha_g1_beta_g2,
+            neg_gamma_g2: (-gamma_g2).into(),
+            neg_delta_g2: (-delta_g2).into(),
+            gamma_g2: gamma_g2.into(),
+            delta_g2: delta_g2.into(),
+            ic_projective: ic.par_iter().map(|i| i.to_curve()).collect(),
+            ic,
+            multiscalar,
+            alpha_g1: alpha_g1.to_curve(),
+            beta_g2: beta_g2.into(),
+            beta_g2_affine: beta_g2,
+            gamma_g2_affine: gamma_g2,
+            delta_g2_affine: delta_g2,
+        })
+    }
+}
+
+impl<E> Serialize for PreparedVerifyingKey<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        serde_impl::serialize(s, |w| self.write(w))
+    }
+}
+
+/// Deserializes a key written by `PreparedVerifyingKey::write`, checking that
+/// the precomputed tables and `alpha_g1_beta_g2` are the ones derived from the
+/// rest of the key. Use `PreparedVerifyingKey::read` to load a trusted key
+/// without these checks.
+impl<'de, E> Deserialize<'de> for PreparedVerifyingKey<E>
+where
+    E: MultiMillerLoop,
+    <E as Engine>::Gt: Compress,
+{
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        serde_impl::deserialize(d, "prepared verifying key", |r| Self::read_untrusted(r))
+    }
+}
+
+/// Everything of a serialized `PreparedVerifyingKey` but the precomputed tables.
+struct PreparedHead<E: Engine> {
+    alpha_g1_beta_g2: E::Gt,
+    alpha_g1: E::G1Affine,
+    beta_g2: E::G2Affine,
+    gamma_g2: E::G2Affine,
+    delta_g2: E::G2Affine,
+    ic: Vec<E::G1Affine>,
+}
+
+impl<E> PreparedHead<E>
+where
+    E: Engine,
+    E::Gt: Compress,
+{
+    fn read<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut magic = [0u8; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != PREPARED_VK_MAGIC {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not a prepared verifying key",
+            ));
+        }
+
+        let version = reader.read_u32::<BigEndian>()?;
+        if version != PREPARED_VK_VERSION {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported prepared verifying key version {}", version),
+            ));
+        }
+
+        let alpha_g1_beta_g2 = E::Gt::read_compressed(&mut reader)?;
+
+        let mut g1_repr = <E::G1Affine as UncompressedEncoding>::Uncompressed::default();
+        let mut g2_repr = <E::G2Affine as UncompressedEncoding>::Uncompressed::default();
+
+        reader.read_exact(g1_repr.as_mut())?;
+        let alpha_g1 = read_uncompressed_point(&g1_repr)?;
+
+        reader.read_exact(g2_repr.as_mut())?;
+        let beta_g2 = read_uncompressed_point(&g2_repr)?;
+
+        reader.read_exact(g2_repr.as_mut())?;
+        let gamma_g2 = read_uncompressed_point(&g2_repr)?;
+
+        reader.read_exact(g2_repr.as_mut())?;
+        let delta_g2 = read_uncompressed_point(&g2_repr)?;
+
+        let ic_len = reader.read_u32::<BigEndian>()? as usize;
+        let mut ic = vec![];
+        for _ in 0..ic_len {
+            reader.read_exact(g1_repr.as_mut())?;
+            ic.push(read_uncompressed_point(&g1_repr)?);
+        }
+
+        Ok(PreparedHead {
+            alpha_g1_beta_g2,
+            alpha_g1,
+            beta_g2,
+            gamma_g2,
+            delta_g2,
+            ic,
+        })
+    }
+}
+
+#[cfg(all(test, feature = "groth16"))]
+mod test_with_bls12_381 {
+    use std::ops::MulAssign;
+
+    use super::*;
+    use crate::groth16::{
+        create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
+    };
+    use crate::{Circuit, ConstraintSystem, SynthesisError};
+    use blstrs::{Bls12, Scalar as Fr};
+    use ff::{Field, PrimeField};
+    use memmap::MmapOptions;
+    use rand::thread_rng;
+    use std::io::Write;
+
+    struct MySillyCircuit<Scalar: PrimeField> {
+        a: Option<Scalar>,
+        b: Option<Scalar>,
+    }
+
+    impl<Scalar: PrimeField> Circuit<Scalar> for MySillyCircuit<Scalar> {
+        fn synthesize<CS: ConstraintSystem<Scalar>>(
+            self,
+            cs: &mut CS,
+        ) -> Result<(), SynthesisError> {
+            let a = cs.alloc(|| "a", || self.a.ok_or(SynthesisError::AssignmentMissing))?;
+            let b = cs.alloc(|| "b", || self.b.ok_or(SynthesisError::AssignmentMissing))?;
+            let c = cs.alloc_input(
+                || "c",
+                || {
+                    let mut a = self.a.ok_or(SynthesisError::AssignmentMissing)?;
+                    let b = self.b.ok_or(SynthesisError::AssignmentMissing)?;
+
+                    a.mul_assign(&b);
+                    Ok(a)
+                },
+            )?;
+
+            cs.enforce(|| "a*b=c", |lc| lc + a, |lc| lc + b, |lc| lc + c);
+
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn prepared_verifying_key_serialization() {
+        let rng = &mut thread_rng();
+
+        let params =
+            generate_random_parameters::<Bls12, _, _>(MySillyCircuit { a: None, b: None }, rng)
+                .unwrap();
+        let pvk = prepare_verifying_key::<Bls12>(&params.vk);
+
+        let mut v = vec![];
+        pvk.write(&mut v).unwrap();
+
+        let de_pvk = PreparedVerifyingKey::<Bls12>::read(&v[..], None).unwrap();
+        let de_pvk_checked = PreparedVerifyingKey::<Bls12>::read(&v[..], Some(&params.vk)).unwrap();
+
+        let mut file = tempfile::tempfile().unwrap();
+        file.write_all(&[0u8; 3]).unwrap();
+        file.write_all(&v).unwrap();
+        let mmap = unsafe { MmapOptions::new().map(&file).unwrap() };
+        let mut offset = 3;
+        let de_pvk_mmap =
+            PreparedVerifyingKey::<Bls12>::read_mmap(&mmap, &mut offset, Some(&params.vk)).unwrap();
+        assert_eq!(offset, v.len() + 3);
+
+        for de_pvk in &[de_pvk, de_pvk_checked, de_pvk_mmap] {
+            let mut w = vec![];
+            de_pvk.write(&mut w).unwrap();
+            assert_eq!(v, w);
+        }
+
+        let a = Fr::random(&mut *rng);
+        let b = Fr::random(&mut *rng);
+        let mut c = a;
+        c.mul_assign(&b);
+
+        let proof = create_random_proof(
+            MySillyCircuit {
+                a: Some(a),
+                b: Some(b),
+            },
+            &params,
+            rng,
+        )
+        .unwrap();
+
+        let de_pvk = PreparedVerifyingKey::<Bls12>::read(&v[..], Some(&params.vk)).unwrap();
+        assert!(verify_proof(&de_pvk, &proof, &[c]).unwrap());
+        assert!(!verify_proof(&de_pvk, &proof, &[a]).unwrap());
+
+        // Keys from other parameters are rejected.
+        let other =
+            generate_random_parameters::<Bls12, _, _>(MySillyCircuit { a: None, b: None }, rng)
+                .unwrap();
+        assert!(PreparedVerifyingKey::<Bls12>::read(&v[..], Some(&other.vk)).is_err());
+
+        // Corrupting a precomputed table entry is detected.
+        let mut corrupted = v.clone();
+        let table_entry = corrupted.len() - 96;
+        corrupted[table_entry..].copy_from_slice(params.vk.ic[0].to_uncompressed().as_ref());
+        assert!(PreparedVerifyingKey::<Bls12>::read(&corrupted[..], Some(&params.vk)).is_err());
+
+        // Unknown versions are rejected.
+        let mut corrupted = v;
+        corrupted[7] = 2;
+        assert!(PreparedVerifyingKey::<Bls12>::read(&corrupted[..], None).is_err());
+    }
 }
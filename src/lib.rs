@@ -145,10 +145,11 @@ extern crate hex_literal;
 pub mod domain;
 pub mod gadgets;
 pub mod gpu;
-#[cfg(feature = "groth16")]
+#[cfg(feature = "verifier")]
 pub mod groth16;
 pub mod multicore;
 pub mod multiexp;
+mod parallel;
 #[cfg(test)]
 pub mod test_utils;
 pub mod util_cs;
@@ -160,7 +161,7 @@ pub use constraint_system::{Circuit, ConstraintSystem, Namespace, SynthesisError
 
 pub const BELLMAN_VERSION: &str = env!("CARGO_PKG_VERSION");
 
-#[cfg(feature = "groth16")]
+#[cfg(feature = "verifier")]
 pub(crate) fn le_bytes_to_u64s(le_bytes: &[u8]) -> Vec<u64> {
     use std::convert::TryInto;
 
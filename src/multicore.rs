@@ -1,17 +1,32 @@
 //! An interface for dealing with the kinds of parallel computations involved in
 //! `bellperson`.
+//!
+//! Without the `multicore` feature there is no thread pool: the same interface
+//! runs every computation right away on the calling thread.
 
+#[cfg(feature = "multicore")]
 use std::env;
 
+#[cfg(feature = "multicore")]
 use crossbeam_channel::{bounded, Receiver};
+#[cfg(feature = "multicore")]
 use lazy_static::lazy_static;
+#[cfg(feature = "multicore")]
 use yastl::Pool;
 
+#[cfg(feature = "multicore")]
+pub use yastl::Scope;
+
+#[cfg(not(feature = "multicore"))]
+pub use self::sequential::Scope;
+
+#[cfg(feature = "multicore")]
 lazy_static! {
     static ref NUM_CPUS: usize = read_num_cpus();
     pub static ref THREAD_POOL: Pool = Pool::new(*NUM_CPUS);
 }
 
+#[cfg(feature = "multicore")]
 fn read_num_cpus() -> usize {
     match env::var("BELLMAN_NUM_CPUS")
         .ok()
@@ -46,7 +61,10 @@ impl Worker {
     pub fn new() -> Worker {
         Worker {}
     }
+}
 
+#[cfg(feature = "multicore")]
+impl Worker {
     pub fn log_num_cpus(&self) -> u32 {
         log2_floor(*NUM_CPUS)
     }
@@ -60,7 +78,10 @@ impl Worker {
 
         THREAD_POOL.spawn(move || {
             let res = f();
-            sender.send(res).unwrap();
+            // the waiter is dropped when the caller returned early, e.g. on
+            // the error of another computation, panicking here would take
+            // the pool thread down with it
+            let _ = sender.send(res);
         });
 
         Waiter { receiver }
@@ -68,7 +89,7 @@ impl Worker {
 
     pub fn scope<'a, F, R>(&self, elements: usize, f: F) -> R
     where
-        F: FnOnce(&yastl::Scope<'a>, usize) -> R,
+        F: FnOnce(&Scope<'a>, usize) -> R,
     {
         let chunk_size = if elements < *NUM_CPUS {
             1
@@ -82,7 +103,7 @@ impl Worker {
     /// Executes the passed in function, and returns the result once it is finished.
     pub fn scoped<'a, F, R>(&self, f: F) -> R
     where
-        F: FnOnce(&yastl::Scope<'a>) -> R,
+        F: FnOnce(&Scope<'a>) -> R,
     {
         let (sender, receiver) = bounded(1);
         THREAD_POOL.scoped(|s| {
@@ -95,9 +116,13 @@ impl Worker {
 }
 
 pub struct Waiter<T> {
+    #[cfg(feature = "multicore")]
     receiver: Receiver<T>,
+    #[cfg(not(feature = "multicore"))]
+    result: std::sync::Mutex<Option<T>>,
 }
 
+#[cfg(feature = "multicore")]
 impl<T> Waiter<T> {
     /// Wait for the result.
     pub fn wait(&self) -> T {
@@ -113,6 +138,74 @@ impl<T> Waiter<T> {
     }
 }
 
+#[cfg(not(feature = "multicore"))]
+mod sequential {
+    use std::marker::PhantomData;
+    use std::sync::Mutex;
+
+    use super::{log2_floor, Waiter, Worker};
+
+    /// Runs executed closures right away, on the calling thread.
+    pub struct Scope<'a>(PhantomData<&'a ()>);
+
+    impl<'a> Scope<'a> {
+        pub fn execute<F>(&self, job: F)
+        where
+            F: FnOnce() + Send + 'a,
+        {
+            job()
+        }
+    }
+
+    impl Worker {
+        pub fn log_num_cpus(&self) -> u32 {
+            // everything runs on the calling thread
+            log2_floor(1)
+        }
+
+        pub fn compute<F, R>(&self, f: F) -> Waiter<R>
+        where
+            F: FnOnce() -> R + Send + 'static,
+            R: Send + 'static,
+        {
+            Waiter::done(f())
+        }
+
+        pub fn scope<'a, F, R>(&self, elements: usize, f: F) -> R
+        where
+            F: FnOnce(&Scope<'a>, usize) -> R,
+        {
+            f(&Scope(PhantomData), elements.max(1))
+        }
+
+        /// Executes the passed in function, and returns the result once it is finished.
+        pub fn scoped<'a, F, R>(&self, f: F) -> R
+        where
+            F: FnOnce(&Scope<'a>) -> R,
+        {
+            f(&Scope(PhantomData))
+        }
+    }
+
+    impl<T> Waiter<T> {
+        /// Wait for the result.
+        pub fn wait(&self) -> T {
+            self.result
+                .lock()
+                .expect("waiter lock poisoned")
+                .take()
+                .expect("the result was already taken")
+        }
+
+        /// One off sending.
+        pub fn done(val: T) -> Self {
+            Waiter {
+                result: Mutex::new(Some(val)),
+            }
+        }
+    }
+}
+
 fn log2_floor(num: usize) -> u32 {
     assert!(num > 0);
 
@@ -127,6 +220,7 @@ fn log2_floor(num: usize) -> u32 {
 
 #[cfg(test)]
 pub mod tests {
+    #[cfg(feature = "multicore")]
     use crate::test_utils;
 
     use super::*;
@@ -142,6 +236,7 @@ pub mod tests {
         assert_eq!(log2_floor(8), 3);
     }
 
+    #[cfg(feature = "multicore")]
     #[test]
     fn test_read_num_cpus() {
         // use bellman if set
@@ -9,11 +9,11 @@ use ff::{Field, PrimeField};
 use group::{prime::PrimeCurveAffine, Group};
 use log::{info, warn};
 use pairing::Engine;
-use rayon::prelude::*;
 
 use super::multicore::{Waiter, Worker};
 use super::SynthesisError;
 use crate::gpu;
+use crate::parallel::prelude::*;
 
 /// An object that builds a source of bases.
 pub trait SourceBuilder<G: PrimeCurveAffine>: Send + Sync + 'static + Clone {
@@ -22,6 +22,13 @@ pub trait SourceBuilder<G: PrimeCurveAffine>: Send + Sync + 'static + Clone {
     #[allow(clippy::wrong_self_convention)]
     fn new(self) -> Self::Source;
     fn get(self) -> (Arc<Vec<G>>, usize);
+    /// Returns all the bases at once along with the number of bases to skip,
+    /// for consumers that need them in memory (e.g. the GPU kernels). Sources
+    /// that can fail to load their bases override this to return the error
+    /// instead of panicking in `get`.
+    fn try_get(self) -> Result<(Arc<Vec<G>>, usize), SynthesisError> {
+        Ok(self.get())
+    }
 }
 
 /// A source of bases, like an iterator.
@@ -361,12 +368,25 @@ where
     S: SourceBuilder<G>,
 {
     if let Some(ref mut kern) = kern {
-        if let Ok(p) = kern.with(|k: &mut gpu::MultiexpKernel<E>| {
+        // the bases are only loaded once the GPU is acquired, an error while
+        // loading them is returned instead of falling back to the CPU
+        let mut load_err = None;
+        let res = kern.with(|k: &mut gpu::MultiexpKernel<E>| {
             let exps = density_map.as_ref().generate_exps::<E>(exponents.clone());
-            let (bss, skip) = bases.clone().get();
+            let (bss, skip) = match bases.clone().try_get() {
+                Ok(bases) => bases,
+                Err(e) => {
+                    load_err = Some(e);
+                    return Err(gpu::GPUError::Simple("failed to load the bases"));
+                }
+            };
             let n = exps.len();
             k.multiexp(pool, bss, exps, skip, n)
-        }) {
+        });
+        if let Some(e) = load_err {
+            return Waiter::done(Err(e));
+        }
+        if let Ok(p) = res {
             return Waiter::done(Ok(p));
         }
     }
@@ -0,0 +1,163 @@
+//! Data parallelism used by the verifier side of `bellperson` and by the
+//! multiexp.
+//!
+//! With the `multicore` feature this is simply rayon. Without it, the same
+//! subset of the rayon interface is provided by sequential code that runs on
+//! the calling thread, so that verification also works on targets that cannot
+//! spawn threads, like `wasm32-unknown-unknown`.
+
+// without the verifier only the multiexp uses this module
+#![cfg_attr(not(feature = "verifier"), allow(dead_code, unused_imports))]
+
+#[cfg(feature = "multicore")]
+pub(crate) use rayon::{in_place_scope, join, prelude};
+
+#[cfg(not(feature = "multicore"))]
+pub(crate) use self::sequential::{in_place_scope, join, prelude};
+
+#[cfg(not(feature = "multicore"))]
+mod sequential {
+    /// Runs spawned closures right away, on the calling thread.
+    pub struct Scope(());
+
+    impl Scope {
+        pub fn spawn<F: FnOnce(&Scope)>(&self, f: F) {
+            f(self)
+        }
+    }
+
+    pub fn in_place_scope<F, R>(f: F) -> R
+    where
+        F: FnOnce(&Scope) -> R,
+    {
+        f(&Scope(()))
+    }
+
+    pub fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+    where
+        A: FnOnce() -> RA,
+        B: FnOnce() -> RB,
+    {
+        (a(), b())
+    }
+
+    pub mod prelude {
+        use std::iter;
+        use std::slice;
+
+        /// A sequential iterator with the rayon specific adaptors. Everything
+        /// else is the standard [`Iterator`] interface.
+        pub struct Iter<I>(I);
+
+        impl<I: Iterator> Iterator for Iter<I> {
+            type Item = I::Item;
+
+            fn next(&mut self) -> Option<Self::Item> {
+                self.0.next()
+            }
+
+            fn size_hint(&self) -> (usize, Option<usize>) {
+                self.0.size_hint()
+            }
+        }
+
+        // The adaptors below shadow their `Iterator` counterparts so that the
+        // result can still be folded and reduced the rayon way.
+        impl<I: Iterator> Iter<I> {
+            pub fn map<B, F>(self, f: F) -> Iter<iter::Map<I, F>>
+            where
+                F: FnMut(I::Item) -> B,
+            {
+                Iter(self.0.map(f))
+            }
+
+            pub fn flat_map<U, F>(self, f: F) -> Iter<iter::FlatMap<I, U, F>>
+            where
+                U: IntoIterator,
+                F: FnMut(I::Item) -> U,
+            {
+                Iter(self.0.flat_map(f))
+            }
+
+            pub fn zip<J: IntoIterator>(self, other: J) -> Iter<iter::Zip<I, J::IntoIter>> {
+                Iter(self.0.zip(other))
+            }
+
+            pub fn enumerate(self) -> Iter<iter::Enumerate<I>> {
+                Iter(self.0.enumerate())
+            }
+
+            pub fn with_min_len(self, _min: usize) -> Self {
+                self
+            }
+
+            pub fn fold<T, ID, F>(self, identity: ID, f: F) -> Iter<iter::Once<T>>
+            where
+                ID: Fn() -> T,
+                F: FnMut(T, I::Item) -> T,
+            {
+                Iter(iter::once(self.0.fold(identity(), f)))
+            }
+
+            pub fn reduce<ID, F>(self, identity: ID, f: F) -> I::Item
+            where
+                ID: Fn() -> I::Item,
+                F: FnMut(I::Item, I::Item) -> I::Item,
+            {
+                self.0.fold(identity(), f)
+            }
+        }
+
+        pub trait IntoParallelIterator: IntoIterator + Sized {
+            fn into_par_iter(self) -> Iter<Self::IntoIter> {
+                Iter(self.into_iter())
+            }
+        }
+
+        impl<I: IntoIterator> IntoParallelIterator for I {}
+
+        pub trait IntoParallelRefIterator<'a> {
+            type Iter: Iterator;
+
+            fn par_iter(&'a self) -> Iter<Self::Iter>;
+        }
+
+        impl<'a, T: 'a + ?Sized> IntoParallelRefIterator<'a> for T
+        where
+            &'a T: IntoIterator,
+        {
+            type Iter = <&'a T as IntoIterator>::IntoIter;
+
+            fn par_iter(&'a self) -> Iter<Self::Iter> {
+                Iter(self.into_iter())
+            }
+        }
+
+        pub trait IntoParallelRefMutIterator<'a> {
+            type Iter: Iterator;
+
+            fn par_iter_mut(&'a mut self) -> Iter<Self::Iter>;
+        }
+
+        impl<'a, T: 'a + ?Sized> IntoParallelRefMutIterator<'a> for T
+        where
+            &'a mut T: IntoIterator,
+        {
+            type Iter = <&'a mut T as IntoIterator>::IntoIter;
+
+            fn par_iter_mut(&'a mut self) -> Iter<Self::Iter> {
+                Iter(self.into_iter())
+            }
+        }
+
+        pub trait ParallelSlice<T> {
+            fn par_chunks(&self, chunk_size: usize) -> Iter<slice::Chunks<'_, T>>;
+        }
+
+        impl<T> ParallelSlice<T> for [T] {
+            fn par_chunks(&self, chunk_size: usize) -> Iter<slice::Chunks<'_, T>> {
+                Iter(self.chunks(chunk_size))
+            }
+        }
+    }
+}
@@ -1,7 +1,18 @@
 use bellperson::gadgets::num::AllocatedNum;
 use bellperson::groth16::{
     aggregate::{
-        aggregate_proofs, setup_fake_srs, verify_aggregate_proof, AggregateProof, GenericSRS,
+        aggregate_proofs, aggregate_proofs_committed_inputs,
+        aggregate_proofs_committed_inputs_with_transcript, aggregate_proofs_heterogeneous,
+        aggregate_proofs_heterogeneous_with_transcript, aggregate_proofs_with_transcript,
+        commit_public_inputs, setup_fake_srs, verify_aggregate_proof,
+        verify_aggregate_proof_committed_inputs,
+        verify_aggregate_proof_committed_inputs_with_transcript, verify_aggregate_proof_diagnostic,
+        verify_aggregate_proof_diagnostic_with_transcript, verify_aggregate_proof_heterogeneous,
+        verify_aggregate_proof_heterogeneous_with_transcript,
+        verify_aggregate_proof_with_transcript, verify_aggregate_proofs_batch,
+        verify_aggregate_proofs_batch_with_transcript, AggregateBatchItem, AggregateCheck,
+        AggregateProof, Aggregator, Blake2sTranscript, CommittedAggregateProof, GenericSRS,
+        HeterogeneousAggregateProof, InputCommitment, Keccak256Transcript, Sha256Transcript,
     },
     create_random_proof, generate_random_parameters, prepare_verifying_key, verify_proof,
     verify_proofs_batch, Parameters, Proof,
@@ -310,7 +321,7 @@ fn test_groth16_bench() {
     let inclusion = vec![1, 2, 3];
     for i in nb_proofs {
         let mut records = Vec::new();
-        let (pk, vk) = generic.specialize(i);
+        let (pk, vk) = generic.specialize(i).unwrap();
         for _ in 0..n_average {
             println!("Proofs {}", i);
             // Aggregate proofs using inner product proofs
@@ -436,7 +447,7 @@ fn test_groth16_aggregation() {
 
     // Generate parameters for inner product aggregation
     let generic = setup_fake_srs(&mut rng, NUM_PROOFS);
-    let (pk, vk) = generic.specialize(NUM_PROOFS);
+    let (pk, vk) = generic.specialize(NUM_PROOFS).unwrap();
 
     // Create parameters for our circuit
     let params = {
@@ -528,9 +539,47 @@ fn test_groth16_aggregation() {
         false
     );
 
-    // 2. Non power of two
-    let err = aggregate_proofs::<Bls12>(&pk, &to_include, &proofs[0..NUM_PROOFS - 1]).unwrap_err();
-    assert!(matches!(err, SynthesisError::NonPowerOfTwo));
+    // 2. Non power of two, the proofs are padded by repeating the last one
+    let (pk3, vk3) = generic.specialize(3).unwrap();
+    for &(pk, vk, n) in &[(&pk3, &vk3, 3), (&pk, &vk, NUM_PROOFS - 1)] {
+        let mut padded_proof = aggregate_proofs::<Bls12>(pk, &to_include, &proofs[..n])
+            .expect("failed to aggregate proofs");
+        assert_eq!(padded_proof.tmipp.gipa.nproofs as usize, n);
+        assert!(verify_aggregate_proof(
+            vk,
+            &pvk,
+            &mut rng,
+            &statements[..n],
+            &padded_proof,
+            &to_include,
+        )
+        .unwrap());
+
+        // The padding can not be passed off as actual proofs.
+        let mut padded_statements = statements[..n].to_vec();
+        padded_statements.resize(n.next_power_of_two(), statements[n - 1].clone());
+        assert!(verify_aggregate_proof(
+            vk,
+            &pvk,
+            &mut rng,
+            &padded_statements,
+            &padded_proof,
+            &to_include,
+        )
+        .is_err());
+        padded_proof.tmipp.gipa.nproofs = n.next_power_of_two() as u32;
+        assert!(!verify_aggregate_proof(
+            vk,
+            &pvk,
+            &mut rng,
+            &padded_statements,
+            &padded_proof,
+            &to_include,
+        )
+        .unwrap());
+    }
+    let err = aggregate_proofs::<Bls12>(&pk3, &to_include, &proofs[..5]).unwrap_err();
+    assert!(matches!(err, SynthesisError::MalformedSrs));
 
     // 3. aggregate invalid proof content (random A, B, and C)
     let old_a = proofs[0].a;
@@ -592,6 +641,742 @@ fn test_groth16_aggregation() {
     aggregate_proof.tmipp.gipa.final_a = old_finala;
 }
 
+#[test]
+fn test_groth16_aggregation_diagnostic() {
+    const NUM_PROOFS: usize = 4;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+
+    let c = TestCircuit::<Fr> {
+        public_inputs: vec![Default::default(); 2],
+        public_product: Default::default(),
+        witness_input: Default::default(),
+    };
+    let params = generate_random_parameters(c, &mut rng).unwrap();
+    let pvk = prepare_verifying_key(&params.vk);
+    let (pk, vk) = setup_fake_srs(&mut rng, NUM_PROOFS)
+        .specialize(NUM_PROOFS)
+        .unwrap();
+
+    let (mut proofs, statements): (Vec<_>, Vec<_>) = (0..NUM_PROOFS)
+        .map(|_| generate_proof(2, &params, &mut rng))
+        .unzip();
+    let to_include = vec![1, 2, 3];
+    let mut aggregate_proof = aggregate_proofs::<Bls12>(&pk, &to_include, &proofs).unwrap();
+
+    let mut verifier_rng = rand_chacha::ChaChaRng::seed_from_u64(1u64);
+    let mut diagnose = |proof: &AggregateProof<Bls12>| {
+        verify_aggregate_proof_diagnostic(
+            &vk,
+            &pvk,
+            &mut verifier_rng,
+            &statements,
+            proof,
+            &to_include,
+        )
+        .unwrap()
+    };
+    assert!(diagnose(&aggregate_proof).is_empty());
+
+    // An invalid proof only breaks the Groth16 equation.
+    proofs[0].a = <Bls12 as Engine>::G1::random(&mut rng).to_affine();
+    let invalid_agg = aggregate_proofs::<Bls12>(&pk, &to_include, &proofs).unwrap();
+    assert_eq!(diagnose(&invalid_agg), vec![AggregateCheck::Groth16]);
+
+    let old_finalc = aggregate_proof.tmipp.gipa.final_c;
+    aggregate_proof.tmipp.gipa.final_c = <Bls12 as Engine>::G1::random(&mut rng).to_affine();
+    assert_eq!(diagnose(&aggregate_proof), vec![AggregateCheck::Mipp]);
+    aggregate_proof.tmipp.gipa.final_c = old_finalc;
+
+    // agg_c is bound to the transcript, every check depending on a challenge
+    // fails along with the Groth16 equation.
+    let old_aggc = aggregate_proof.agg_c;
+    aggregate_proof.agg_c = <Bls12 as Engine>::G1::random(&mut rng);
+    assert_eq!(diagnose(&aggregate_proof).len(), 5);
+    aggregate_proof.agg_c = old_aggc;
+
+    let old_finala = aggregate_proof.tmipp.gipa.final_a;
+    aggregate_proof.tmipp.gipa.final_a = <Bls12 as Engine>::G1::random(&mut rng).to_affine();
+    assert_eq!(diagnose(&aggregate_proof), vec![AggregateCheck::Tipp]);
+    aggregate_proof.tmipp.gipa.final_a = old_finala;
+
+    let old_opening = aggregate_proof.tmipp.vkey_opening;
+    aggregate_proof.tmipp.vkey_opening.1 = <Bls12 as Engine>::G2::random(&mut rng).to_affine();
+    assert_eq!(diagnose(&aggregate_proof), vec![AggregateCheck::KzgV]);
+    aggregate_proof.tmipp.vkey_opening = old_opening;
+
+    let old_opening = aggregate_proof.tmipp.wkey_opening;
+    aggregate_proof.tmipp.wkey_opening.0 = <Bls12 as Engine>::G1::random(&mut rng).to_affine();
+    assert_eq!(diagnose(&aggregate_proof), vec![AggregateCheck::KzgW]);
+    aggregate_proof.tmipp.wkey_opening = old_opening;
+
+    // Malformed proofs are still rejected with an error.
+    aggregate_proof.tmipp.gipa.nproofs = 2;
+    assert!(verify_aggregate_proof_diagnostic(
+        &vk,
+        &pvk,
+        &mut rng,
+        &statements,
+        &aggregate_proof,
+        &to_include,
+    )
+    .is_err());
+}
+
+/// Aggregates and verifies proofs with each transcript protocol.
+#[test]
+fn test_groth16_aggregation_transcripts() {
+    const NUM_PROOFS: usize = 3;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+
+    let c = TestCircuit::<Fr> {
+        public_inputs: vec![Default::default(); 2],
+        public_product: Default::default(),
+        witness_input: Default::default(),
+    };
+    let params = generate_random_parameters(c, &mut rng).unwrap();
+    let pvk = prepare_verifying_key(&params.vk);
+    let (pk, vk) = setup_fake_srs(&mut rng, NUM_PROOFS.next_power_of_two())
+        .specialize(NUM_PROOFS)
+        .unwrap();
+
+    let (proofs, statements): (Vec<_>, Vec<_>) = (0..NUM_PROOFS)
+        .map(|_| generate_proof(2, &params, &mut rng))
+        .unzip();
+    let to_include = vec![1, 2, 3];
+
+    // The default protocol is the one of aggregate_proofs.
+    let sha =
+        aggregate_proofs_with_transcript::<Bls12, Sha256Transcript>(&pk, &to_include, &proofs)
+            .unwrap();
+    assert!(verify_aggregate_proof(&vk, &pvk, &mut rng, &statements, &sha, &to_include).unwrap());
+
+    let blake =
+        aggregate_proofs_with_transcript::<Bls12, Blake2sTranscript>(&pk, &to_include, &proofs)
+            .unwrap();
+    assert!(
+        verify_aggregate_proof_with_transcript::<_, Blake2sTranscript, _>(
+            &vk,
+            &pvk,
+            &mut rng,
+            &statements,
+            &blake,
+            &to_include,
+        )
+        .unwrap()
+    );
+
+    let keccak =
+        aggregate_proofs_with_transcript::<Bls12, Keccak256Transcript>(&pk, &to_include, &proofs)
+            .unwrap();
+    assert!(
+        verify_aggregate_proof_with_transcript::<_, Keccak256Transcript, _>(
+            &vk,
+            &pvk,
+            &mut rng,
+            &statements,
+            &keccak,
+            &to_include,
+        )
+        .unwrap()
+    );
+
+    // A proof does not verify with another protocol.
+    assert!(
+        !verify_aggregate_proof_with_transcript::<_, Keccak256Transcript, _>(
+            &vk,
+            &pvk,
+            &mut rng,
+            &statements,
+            &blake,
+            &to_include,
+        )
+        .unwrap()
+    );
+    assert!(
+        !verify_aggregate_proof(&vk, &pvk, &mut rng, &statements, &keccak, &to_include).unwrap()
+    );
+}
+
+/// Aggregates and verifies proofs of every aggregation mode with a transcript
+/// protocol other than the default one.
+#[test]
+fn test_groth16_aggregation_transcript_modes() {
+    const NUM_PROOFS: usize = 3;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+
+    let params = [2, 4]
+        .iter()
+        .map(|&n| {
+            let c = TestCircuit::<Fr> {
+                public_inputs: vec![Default::default(); n],
+                public_product: Default::default(),
+                witness_input: Default::default(),
+            };
+            generate_random_parameters(c, &mut rng).unwrap()
+        })
+        .collect::<Vec<_>>();
+    let pvk = prepare_verifying_key(&params[0].vk);
+    let (pk, vk) = setup_fake_srs(&mut rng, NUM_PROOFS.next_power_of_two())
+        .specialize(NUM_PROOFS)
+        .unwrap();
+
+    let (proofs, statements): (Vec<_>, Vec<_>) = (0..NUM_PROOFS)
+        .map(|_| generate_proof(2, &params[0], &mut rng))
+        .unzip();
+    let to_include = vec![1, 2, 3];
+    let keccak =
+        aggregate_proofs_with_transcript::<Bls12, Keccak256Transcript>(&pk, &to_include, &proofs)
+            .unwrap();
+
+    // diagnostic
+    assert!(
+        verify_aggregate_proof_diagnostic_with_transcript::<_, Keccak256Transcript, _>(
+            &vk,
+            &pvk,
+            &mut rng,
+            &statements,
+            &keccak,
+            &to_include,
+        )
+        .unwrap()
+        .is_empty()
+    );
+    assert!(!verify_aggregate_proof_diagnostic(
+        &vk,
+        &pvk,
+        &mut rng,
+        &statements,
+        &keccak,
+        &to_include,
+    )
+    .unwrap()
+    .is_empty());
+
+    // batch
+    let items = [AggregateBatchItem {
+        ip_verifier_srs: &vk,
+        pvk: &pvk,
+        public_inputs: &statements,
+        proof: &keccak,
+        transcript_include: &to_include,
+    }];
+    assert!(
+        verify_aggregate_proofs_batch_with_transcript::<_, Keccak256Transcript, _>(
+            &mut rng, &items
+        )
+        .unwrap()
+        .is_empty()
+    );
+    assert_eq!(
+        verify_aggregate_proofs_batch(&mut rng, &items).unwrap(),
+        vec![0]
+    );
+
+    // committed inputs
+    let input_commitment = commit_public_inputs(&pk, &statements).unwrap();
+    let committed = aggregate_proofs_committed_inputs_with_transcript::<Bls12, Blake2sTranscript>(
+        &pk,
+        &to_include,
+        &proofs,
+        &statements,
+        &input_commitment,
+    )
+    .unwrap();
+    assert!(
+        verify_aggregate_proof_committed_inputs_with_transcript::<_, Blake2sTranscript, _>(
+            &vk,
+            &pvk,
+            &mut rng,
+            &input_commitment,
+            &committed,
+            &to_include,
+        )
+        .unwrap()
+    );
+    assert!(!verify_aggregate_proof_committed_inputs(
+        &vk,
+        &pvk,
+        &mut rng,
+        &input_commitment,
+        &committed,
+        &to_include,
+    )
+    .unwrap());
+
+    // heterogeneous
+    let pvks = params
+        .iter()
+        .map(|p| prepare_verifying_key(&p.vk))
+        .collect::<Vec<_>>();
+    let pvks = pvks.iter().collect::<Vec<_>>();
+    let key_indices = vec![1u32, 0, 1];
+    let (proofs, statements): (Vec<_>, Vec<_>) = key_indices
+        .iter()
+        .map(|&k| generate_proof([2, 4][k as usize], &params[k as usize], &mut rng))
+        .unzip();
+    let heterogeneous = aggregate_proofs_heterogeneous_with_transcript::<Bls12, Blake2sTranscript>(
+        &pk,
+        &to_include,
+        &proofs,
+        &key_indices,
+    )
+    .unwrap();
+    assert!(
+        verify_aggregate_proof_heterogeneous_with_transcript::<_, Blake2sTranscript, _>(
+            &vk,
+            &pvks,
+            &mut rng,
+            &statements,
+            &heterogeneous,
+            &to_include,
+        )
+        .unwrap()
+    );
+    assert!(!verify_aggregate_proof_heterogeneous(
+        &vk,
+        &pvks,
+        &mut rng,
+        &statements,
+        &heterogeneous,
+        &to_include,
+    )
+    .unwrap());
+}
+
+/// Aggregates proofs as they come and checks the result is the proof
+/// `aggregate_proofs` creates.
+#[test]
+fn test_groth16_aggregation_streaming() {
+    const NUM_PROOFS: usize = 16;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+
+    let c = TestCircuit::<Fr> {
+        public_inputs: vec![Default::default(); 2],
+        public_product: Default::default(),
+        witness_input: Default::default(),
+    };
+    let params = generate_random_parameters(c, &mut rng).unwrap();
+    let pvk = prepare_verifying_key(&params.vk);
+    let generic = setup_fake_srs(&mut rng, NUM_PROOFS);
+    let (proofs, statements): (Vec<_>, Vec<_>) = (0..NUM_PROOFS)
+        .map(|_| generate_proof(2, &params, &mut rng))
+        .unzip();
+    let to_include = vec![1, 2, 3];
+
+    // with and without padding proofs
+    for &n in &[NUM_PROOFS, 11] {
+        let (pk, vk) = generic.specialize(n).unwrap();
+
+        let start = Instant::now();
+        let expected = aggregate_proofs(&pk, &to_include, &proofs[..n]).unwrap();
+        let oneshot_time = start.elapsed();
+
+        let mut aggregator = Aggregator::new(&pk, n).unwrap();
+        assert!(aggregator.is_empty());
+        for proof in &proofs[..n] {
+            aggregator.add(proof.clone()).unwrap();
+        }
+        assert!(aggregator.is_complete());
+        assert_eq!(aggregator.len(), n);
+        aggregator
+            .add(proofs[0].clone())
+            .expect_err("all proofs are already added");
+        let start = Instant::now();
+        let aggregate_proof = aggregator.finalize(&to_include).unwrap();
+        let finalize_time = start.elapsed();
+        println!(
+            "{} proofs: aggregation {}ms, finalization {}ms",
+            n,
+            oneshot_time.as_millis(),
+            finalize_time.as_millis()
+        );
+
+        assert_eq!(aggregate_proof, expected);
+        assert!(verify_aggregate_proof(
+            &vk,
+            &pvk,
+            &mut rng,
+            &statements[..n],
+            &aggregate_proof,
+            &to_include,
+        )
+        .unwrap());
+    }
+
+    let (pk, vk) = generic.specialize(4).unwrap();
+    let mut aggregator = Aggregator::<_, Keccak256Transcript>::with_transcript(&pk, 4).unwrap();
+    for proof in &proofs[..3] {
+        aggregator.add(proof.clone()).unwrap();
+    }
+    assert!(!aggregator.is_complete());
+    aggregator.add(proofs[3].clone()).unwrap();
+    let aggregate_proof = aggregator.finalize(&to_include).unwrap();
+    assert!(
+        verify_aggregate_proof_with_transcript::<_, Keccak256Transcript, _>(
+            &vk,
+            &pvk,
+            &mut rng,
+            &statements[..4],
+            &aggregate_proof,
+            &to_include,
+        )
+        .unwrap()
+    );
+
+    // the SRS must be specialized for the number of proofs
+    assert!(Aggregator::new(&pk, NUM_PROOFS).is_err());
+    assert!(Aggregator::new(&pk, 1).is_err());
+    let mut aggregator = Aggregator::new(&pk, 3).unwrap();
+    aggregator.add(proofs[0].clone()).unwrap();
+    aggregator
+        .finalize(&to_include)
+        .expect_err("proofs are missing");
+}
+
+/// Verifies aggregated proofs against a commitment to their public inputs.
+#[test]
+fn test_groth16_aggregation_committed_inputs() {
+    const NUM_PROOFS: usize = 5;
+    const NUM_PUBLIC_INPUTS: usize = 3;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+
+    let c = TestCircuit::<Fr> {
+        public_inputs: vec![Default::default(); NUM_PUBLIC_INPUTS],
+        public_product: Default::default(),
+        witness_input: Default::default(),
+    };
+    let params = generate_random_parameters(c, &mut rng).unwrap();
+    let pvk = prepare_verifying_key(&params.vk);
+    let (pk, vk) = setup_fake_srs(&mut rng, NUM_PROOFS.next_power_of_two())
+        .specialize(NUM_PROOFS)
+        .unwrap();
+
+    // the public inputs of the proofs differ
+    let (proofs, statements): (Vec<_>, Vec<_>) = (0..NUM_PROOFS)
+        .map(|i| {
+            let mut statement = (0..NUM_PUBLIC_INPUTS)
+                .map(|j| Fr::from((i * NUM_PUBLIC_INPUTS + j) as u64 + 2))
+                .collect::<Vec<_>>();
+            let w = Fr::from(3);
+            let product = statement.iter().fold(w, |acc, x| acc * x);
+            let c = TestCircuit {
+                public_inputs: statement.iter().map(|x| Some(*x)).collect(),
+                public_product: Some(product),
+                witness_input: Some(w),
+            };
+            statement.push(product);
+            (
+                create_random_proof(c, &params, &mut rng).unwrap(),
+                statement,
+            )
+        })
+        .unzip();
+
+    let to_include = vec![1, 2, 3];
+    let input_commitment = commit_public_inputs(&pk, &statements).unwrap();
+    let mut aggregate_proof = aggregate_proofs_committed_inputs::<Bls12>(
+        &pk,
+        &to_include,
+        &proofs,
+        &statements,
+        &input_commitment,
+    )
+    .unwrap();
+    assert!(verify_aggregate_proof_committed_inputs(
+        &vk,
+        &pvk,
+        &mut rng,
+        &input_commitment,
+        &aggregate_proof,
+        &to_include,
+    )
+    .unwrap());
+
+    // Both the commitment and the proof survive serialization.
+    let mut buffer = Vec::new();
+    input_commitment.write(&mut buffer).unwrap();
+    assert_eq!(
+        InputCommitment::<Bls12>::read(&buffer[..]).unwrap(),
+        input_commitment
+    );
+    let mut buffer = Vec::new();
+    aggregate_proof.write(&mut buffer).unwrap();
+    let deserialized = CommittedAggregateProof::<Bls12>::read(&buffer[..]).unwrap();
+    assert_eq!(deserialized, aggregate_proof);
+
+    // A commitment to other public inputs is rejected.
+    let mut other_statements = statements.clone();
+    other_statements.swap(0, 1);
+    let other_commitment = commit_public_inputs(&pk, &other_statements).unwrap();
+    assert!(!verify_aggregate_proof_committed_inputs(
+        &vk,
+        &pvk,
+        &mut rng,
+        &other_commitment,
+        &aggregate_proof,
+        &to_include,
+    )
+    .unwrap());
+    // So is an aggregation bound to another commitment.
+    let other_proof = aggregate_proofs_committed_inputs::<Bls12>(
+        &pk,
+        &to_include,
+        &proofs,
+        &other_statements,
+        &other_commitment,
+    )
+    .unwrap();
+    assert!(!verify_aggregate_proof_committed_inputs(
+        &vk,
+        &pvk,
+        &mut rng,
+        &other_commitment,
+        &other_proof,
+        &to_include,
+    )
+    .unwrap());
+
+    // The values of the public inputs must match their commitment.
+    let old_eval = aggregate_proof.input_evals[1];
+    aggregate_proof.input_evals[1] = Fr::random(&mut rng);
+    assert!(!verify_aggregate_proof_committed_inputs(
+        &vk,
+        &pvk,
+        &mut rng,
+        &input_commitment,
+        &aggregate_proof,
+        &to_include,
+    )
+    .unwrap());
+    aggregate_proof.input_evals[1] = old_eval;
+
+    let mut wrong_len = input_commitment.clone();
+    wrong_len.nproofs = 4;
+    assert!(verify_aggregate_proof_committed_inputs(
+        &vk,
+        &pvk,
+        &mut rng,
+        &wrong_len,
+        &aggregate_proof,
+        &to_include,
+    )
+    .is_err());
+    assert!(commit_public_inputs(&pk, &statements[..3]).is_err());
+    assert!(aggregate_proofs_committed_inputs::<Bls12>(
+        &pk,
+        &to_include,
+        &proofs,
+        &statements,
+        &wrong_len,
+    )
+    .is_err());
+}
+
+/// Aggregates proofs of different circuits, each verified with its own key.
+#[test]
+fn test_groth16_aggregation_heterogeneous() {
+    const NUM_PROOFS: usize = 5;
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+
+    let generic = setup_fake_srs(&mut rng, NUM_PROOFS.next_power_of_two());
+    let (pk, vk) = generic.specialize(NUM_PROOFS).unwrap();
+
+    // the first and last circuits have the same shape but different keys
+    let publics = [3, 5, 3];
+    let params = publics
+        .iter()
+        .map(|&n| {
+            let c = TestCircuit::<Fr> {
+                public_inputs: vec![Default::default(); n],
+                public_product: Default::default(),
+                witness_input: Default::default(),
+            };
+            generate_random_parameters(c, &mut rng).unwrap()
+        })
+        .collect::<Vec<_>>();
+    let pvks = params
+        .iter()
+        .map(|p| prepare_verifying_key(&p.vk))
+        .collect::<Vec<_>>();
+    let pvks = pvks.iter().collect::<Vec<_>>();
+
+    let key_indices = vec![0u32, 1, 2, 1, 0];
+    let (proofs, statements): (Vec<_>, Vec<_>) = key_indices
+        .iter()
+        .map(|&k| generate_proof(publics[k as usize], &params[k as usize], &mut rng))
+        .unzip();
+
+    let to_include = vec![1, 2, 3];
+    let mut aggregate_proof =
+        aggregate_proofs_heterogeneous::<Bls12>(&pk, &to_include, &proofs, &key_indices)
+            .expect("failed to aggregate proofs");
+    assert!(verify_aggregate_proof_heterogeneous(
+        &vk,
+        &pvks,
+        &mut rng,
+        &statements,
+        &aggregate_proof,
+        &to_include,
+    )
+    .unwrap());
+
+    // The key of each proof survives serialization.
+    let mut buffer = Vec::new();
+    aggregate_proof.write(&mut buffer).unwrap();
+    let deserialized = HeterogeneousAggregateProof::<Bls12>::read(&buffer[..]).unwrap();
+    assert_eq!(deserialized, aggregate_proof);
+    assert!(verify_aggregate_proof_heterogeneous(
+        &vk,
+        &pvks,
+        &mut rng,
+        &statements,
+        &deserialized,
+        &to_include,
+    )
+    .unwrap());
+
+    // Verifying a proof against another key of the same shape fails.
+    aggregate_proof.key_indices[0] = 2;
+    assert!(!verify_aggregate_proof_heterogeneous(
+        &vk,
+        &pvks,
+        &mut rng,
+        &statements,
+        &aggregate_proof,
+        &to_include,
+    )
+    .unwrap());
+    aggregate_proof.key_indices[0] = 0;
+
+    let swapped_pvks = vec![pvks[2], pvks[1], pvks[0]];
+    assert!(!verify_aggregate_proof_heterogeneous(
+        &vk,
+        &swapped_pvks,
+        &mut rng,
+        &statements,
+        &aggregate_proof,
+        &to_include,
+    )
+    .unwrap());
+
+    // The per key commitments are bound to the proofs of their key.
+    aggregate_proof.agg_c_keys.swap(0, 2);
+    assert!(!verify_aggregate_proof_heterogeneous(
+        &vk,
+        &pvks,
+        &mut rng,
+        &statements,
+        &aggregate_proof,
+        &to_include,
+    )
+    .unwrap());
+    aggregate_proof.agg_c_keys.swap(0, 2);
+
+    // A proof whose key has a different number of public inputs is rejected.
+    aggregate_proof.key_indices[0] = 1;
+    assert!(verify_aggregate_proof_heterogeneous(
+        &vk,
+        &pvks,
+        &mut rng,
+        &statements,
+        &aggregate_proof,
+        &to_include,
+    )
+    .is_err());
+    assert!(verify_aggregate_proof_heterogeneous(
+        &vk,
+        &pvks[..2],
+        &mut rng,
+        &statements,
+        &deserialized,
+        &to_include,
+    )
+    .is_err());
+}
+
+#[test]
+fn test_groth16_aggregation_batch() {
+    let mut rng = rand_chacha::ChaChaRng::seed_from_u64(0u64);
+
+    let generic = setup_fake_srs(&mut rng, 8);
+    let srs8 = generic.specialize(8).unwrap();
+    let srs3 = generic.specialize(3).unwrap();
+
+    // two circuits with a different number of public inputs
+    let params = [2, 4]
+        .iter()
+        .map(|&n| {
+            let c = TestCircuit::<Fr> {
+                public_inputs: vec![Default::default(); n],
+                public_product: Default::default(),
+                witness_input: Default::default(),
+            };
+            let p = generate_random_parameters(c, &mut rng).unwrap();
+            let pvk = prepare_verifying_key(&p.vk);
+            (n, p, pvk)
+        })
+        .collect::<Vec<_>>();
+
+    let to_include = vec![1, 2, 3];
+    let mut batch = Vec::new();
+    for &(&(n, ref p, _), nproofs) in &[(&params[0], 8), (&params[1], 3), (&params[0], 3)] {
+        let (proofs, statements): (Vec<_>, Vec<_>) =
+            (0..nproofs).map(|_| generate_proof(n, p, &mut rng)).unzip();
+        let srs = if nproofs == 8 { &srs8 } else { &srs3 };
+        let agg = aggregate_proofs::<Bls12>(&srs.0, &to_include, &proofs).unwrap();
+        batch.push((agg, statements));
+    }
+    let keys = [0, 1, 0];
+
+    let items = batch
+        .iter()
+        .zip(keys.iter())
+        .map(|((agg, statements), &k)| AggregateBatchItem {
+            ip_verifier_srs: if statements.len() == 8 {
+                &srs8.1
+            } else {
+                &srs3.1
+            },
+            pvk: &params[k].2,
+            public_inputs: statements,
+            proof: agg,
+            transcript_include: &to_include,
+        })
+        .collect::<Vec<_>>();
+    assert!(verify_aggregate_proofs_batch(&mut rng, &items)
+        .unwrap()
+        .is_empty());
+    assert!(verify_aggregate_proofs_batch(&mut rng, &items[..1])
+        .unwrap()
+        .is_empty());
+
+    // An invalid proof is singled out.
+    let mut buffer = Vec::new();
+    batch[1].0.write(&mut buffer).unwrap();
+    let mut invalid = AggregateProof::<Bls12>::read(&buffer[..]).unwrap();
+    invalid.agg_c = <Bls12 as Engine>::G1::random(&mut rng);
+    let mut invalid_items = items;
+    invalid_items[1].proof = &invalid;
+    assert_eq!(
+        verify_aggregate_proofs_batch(&mut rng, &invalid_items).unwrap(),
+        vec![1]
+    );
+    invalid_items[1].proof = &batch[1].0;
+    invalid_items[2].transcript_include = &[4, 5, 6];
+    invalid_items[0].proof = &invalid;
+    invalid_items[0].public_inputs = &batch[1].1;
+    invalid_items[0].ip_verifier_srs = &srs3.1;
+    invalid_items[0].pvk = &params[1].2;
+    assert_eq!(
+        verify_aggregate_proofs_batch(&mut rng, &invalid_items).unwrap(),
+        vec![0, 2]
+    );
+
+    // A malformed proof fails the whole batch.
+    invalid_items[0].pvk = &params[0].2;
+    assert!(verify_aggregate_proofs_batch(&mut rng, &invalid_items).is_err());
+}
+
 #[test]
 fn test_groth16_aggregation_mimc() {
     const NUM_PROOFS_TO_AGGREGATE: usize = 8; //1024;
@@ -621,7 +1406,7 @@ fn test_groth16_aggregation_mimc() {
     // Generate parameters for inner product aggregation
     // first generic SRS then specialized to the correct size
     let generic = setup_fake_srs(&mut rng, NUM_PROOFS_TO_AGGREGATE);
-    let (pk, vk) = generic.specialize(NUM_PROOFS_TO_AGGREGATE);
+    let (pk, vk) = generic.specialize(NUM_PROOFS_TO_AGGREGATE).unwrap();
 
     println!("Creating proofs...");
 
@@ -301,3 +301,378 @@ fn test_mimc() {
         );
     }
 }
+
+#[test]
+fn test_mimc_parameters_to_writer() {
+    use bellperson::groth16::{generate_parameters, generate_parameters_to_writer, Parameters};
+    use rand::SeedableRng;
+
+    let rng = &mut rand_chacha::ChaChaRng::seed_from_u64(0);
+
+    let constants = (0..MIMC_ROUNDS)
+        .map(|_| Fr::random(&mut *rng))
+        .collect::<Vec<_>>();
+    let circuit = MimcDemo::<Fr> {
+        xl: None,
+        xr: None,
+        constants: &constants,
+    };
+
+    let g1 = <Bls12 as Engine>::G1::random(&mut *rng);
+    let g2 = <Bls12 as Engine>::G2::random(&mut *rng);
+    let alpha = Fr::random(&mut *rng);
+    let beta = Fr::random(&mut *rng);
+    let gamma = Fr::random(&mut *rng);
+    let delta = Fr::random(&mut *rng);
+    let tau = Fr::random(&mut *rng);
+
+    let params =
+        generate_parameters::<Bls12, _>(circuit.clone(), g1, g2, alpha, beta, gamma, delta, tau)
+            .unwrap();
+    let mut expected = vec![];
+    params.write(&mut expected).unwrap();
+
+    let mut streamed = vec![];
+    assert!(generate_parameters_to_writer::<Bls12, _, _, _>(
+        circuit.clone(),
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        delta,
+        tau,
+        0,
+        &mut streamed,
+        |_, _, _| {},
+    )
+    .is_err());
+    assert!(streamed.is_empty());
+
+    let mut reports = vec![];
+    generate_parameters_to_writer::<Bls12, _, _, _>(
+        circuit,
+        g1,
+        g2,
+        alpha,
+        beta,
+        gamma,
+        delta,
+        tau,
+        100,
+        &mut streamed,
+        |section, written, total| reports.push((section, written, total)),
+    )
+    .unwrap();
+
+    assert_eq!(streamed, expected);
+    assert!(Parameters::<Bls12>::read(&streamed[..], true).unwrap() == params);
+
+    // Every section reports its chunks in order, up to its full length.
+    for section in &["h", "l", "a", "b_g1", "b_g2"] {
+        let chunks: Vec<_> = reports.iter().filter(|r| r.0 == *section).collect();
+        assert!(chunks.len() > 1);
+        assert!(chunks
+            .windows(2)
+            .all(|w| w[0].1 < w[1].1 && w[1].1 <= w[1].2));
+        let last = chunks.last().unwrap();
+        assert_eq!(last.1, last.2);
+    }
+}
+
+#[test]
+fn test_mimc_pread_parameters() {
+    use bellperson::groth16::{create_proof, ParameterSource, PreadParameters};
+    use bellperson::multiexp::SourceBuilder;
+    use rand::SeedableRng;
+    use std::io::Write;
+
+    let rng = &mut rand_chacha::ChaChaRng::seed_from_u64(0);
+
+    let constants = (0..MIMC_ROUNDS)
+        .map(|_| Fr::random(&mut *rng))
+        .collect::<Vec<_>>();
+
+    let params = {
+        let c = MimcDemo::<Fr> {
+            xl: None,
+            xr: None,
+            constants: &constants,
+        };
+
+        generate_random_parameters::<Bls12, _, _>(c, &mut *rng).unwrap()
+    };
+
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    params.write(&mut file).unwrap();
+    file.flush().unwrap();
+
+    let pvk = prepare_verifying_key(&params.vk);
+
+    for &(checked, cache_size, buffer_len) in &[(true, 0, 7), (false, 2, 64), (false, 8, 1 << 14)] {
+        let pread_params = PreadParameters::<Bls12>::build(file.path().to_path_buf(), checked)
+            .unwrap()
+            .with_file_cache_size(cache_size)
+            .with_buffer_len(buffer_len);
+        assert!(pread_params.vk == params.vk);
+
+        let xl = Fr::random(&mut *rng);
+        let xr = Fr::random(&mut *rng);
+        let image = mimc::<Fr>(xl, xr, &constants);
+        let c = MimcDemo {
+            xl: Some(xl),
+            xr: Some(xr),
+            constants: &constants,
+        };
+        let r = Fr::random(&mut *rng);
+        let s = Fr::random(&mut *rng);
+
+        let expected = create_proof(c.clone(), &params, r, s).unwrap();
+        let proof = create_proof(c, &pread_params, r, s).unwrap();
+
+        assert!(proof == expected);
+        assert!(verify_proof(&pvk, &proof, &[image]).unwrap());
+    }
+
+    // A truncated file is rejected up front.
+    let bytes = std::fs::read(file.path()).unwrap();
+    let truncated = tempfile::NamedTempFile::new().unwrap();
+    std::fs::write(truncated.path(), &bytes[..bytes.len() - 1]).unwrap();
+    assert!(PreadParameters::<Bls12>::build(truncated.path().to_path_buf(), false).is_err());
+
+    // A file truncated after it was opened fails to load with an error.
+    let pread_params = PreadParameters::<Bls12>::build(file.path().to_path_buf(), false)
+        .unwrap()
+        .with_file_cache_size(0);
+    file.as_file().set_len(pread_params.h.offset).unwrap();
+    let h = (&pread_params).get_h(0).unwrap();
+    assert!(h.try_get().is_err());
+    assert!(create_proof(
+        MimcDemo {
+            xl: Some(Fr::one()),
+            xr: Some(Fr::one()),
+            constants: &constants,
+        },
+        &pread_params,
+        Fr::one(),
+        Fr::one(),
+    )
+    .is_err());
+}
+
+#[cfg(feature = "snarkjs")]
+#[test]
+fn test_mimc_snarkjs_json() {
+    use bellperson::groth16::{
+        read_snarkjs_public_inputs, write_snarkjs_public_inputs, VerifyingKey,
+    };
+    use rand::SeedableRng;
+
+    let rng = &mut rand_chacha::ChaChaRng::seed_from_u64(0);
+
+    let constants = (0..MIMC_ROUNDS)
+        .map(|_| Fr::random(&mut *rng))
+        .collect::<Vec<_>>();
+    let params = {
+        let c = MimcDemo::<Fr> {
+            xl: None,
+            xr: None,
+            constants: &constants,
+        };
+        generate_random_parameters::<Bls12, _, _>(c, &mut *rng).unwrap()
+    };
+
+    let xl = Fr::random(&mut *rng);
+    let xr = Fr::random(&mut *rng);
+    let image = mimc(xl, xr, &constants);
+    let c = MimcDemo {
+        xl: Some(xl),
+        xr: Some(xr),
+        constants: &constants,
+    };
+    let proof = create_random_proof(c, &params, &mut *rng).unwrap();
+
+    let mut proof_json = vec![];
+    proof.write_snarkjs_json(&mut proof_json).unwrap();
+    let mut vk_json = vec![];
+    params.vk.write_snarkjs_json(&mut vk_json).unwrap();
+    let mut public_json = vec![];
+    write_snarkjs_public_inputs(&[image], &mut public_json).unwrap();
+
+    let proof = Proof::read_snarkjs_json(&proof_json[..]).unwrap();
+    let vk = VerifyingKey::<Bls12>::read_snarkjs_json(&vk_json[..]).unwrap();
+    let inputs = read_snarkjs_public_inputs(&public_json[..]).unwrap();
+    assert!(vk == params.vk);
+    assert_eq!(inputs, vec![image]);
+
+    let pvk = prepare_verifying_key(&vk);
+    assert!(verify_proof(&pvk, &proof, &inputs).unwrap());
+}
+
+#[test]
+fn test_mimc_batch_multi() {
+    use bellperson::groth16::{generate_parameters, verify_proofs_batch_multi};
+    use rand::SeedableRng;
+
+    let rng = &mut rand_chacha::ChaChaRng::seed_from_u64(0);
+
+    let constants: Vec<Vec<Fr>> = (0..2)
+        .map(|_| (0..MIMC_ROUNDS).map(|_| Fr::random(&mut *rng)).collect())
+        .collect();
+    let circuit = |i: usize| MimcDemo::<Fr> {
+        xl: None,
+        xr: None,
+        constants: &constants[i],
+    };
+
+    let random = generate_random_parameters::<Bls12, _, _>(circuit(0), &mut *rng).unwrap();
+
+    // The last two keys share gamma and delta, so their terms are grouped.
+    let g1 = <Bls12 as Engine>::G1::generator();
+    let g2 = <Bls12 as Engine>::G2::generator();
+    let gamma = Fr::random(&mut *rng);
+    let delta = Fr::random(&mut *rng);
+    let mut shared = |c| {
+        let alpha = Fr::random(&mut *rng);
+        let beta = Fr::random(&mut *rng);
+        let tau = Fr::random(&mut *rng);
+        generate_parameters::<Bls12, _>(c, g1, g2, alpha, beta, gamma, delta, tau).unwrap()
+    };
+    let params = [random, shared(circuit(1)), shared(circuit(0))];
+    let circuit_of = [0, 1, 0];
+    let pvks: Vec<_> = params
+        .iter()
+        .map(|p| prepare_verifying_key(&p.vk))
+        .collect();
+
+    let mut proofs = vec![];
+    let mut inputs = vec![];
+    for _ in 0..2 {
+        for (k, p) in params.iter().enumerate() {
+            let xl = Fr::random(&mut *rng);
+            let xr = Fr::random(&mut *rng);
+            let c = MimcDemo {
+                xl: Some(xl),
+                xr: Some(xr),
+                constants: &constants[circuit_of[k]],
+            };
+            proofs.push((k, create_random_proof(c, p, &mut *rng).unwrap()));
+            inputs.push(vec![mimc(xl, xr, &constants[circuit_of[k]])]);
+        }
+    }
+
+    let batch: Vec<_> = proofs
+        .iter()
+        .zip(inputs.iter())
+        .map(|((k, proof), input)| (&pvks[*k], proof, &input[..]))
+        .collect();
+    assert!(verify_proofs_batch_multi(&mut *rng, &batch).unwrap());
+    assert!(verify_proofs_batch_multi::<Bls12, _>(&mut *rng, &[]).unwrap());
+
+    // A wrong input is rejected.
+    let wrong = [inputs[3][0] + Fr::one()];
+    let mut bad = batch.clone();
+    bad[3].2 = &wrong[..];
+    assert!(!verify_proofs_batch_multi(&mut *rng, &bad).unwrap());
+
+    // A proof checked against another key, even one sharing gamma and delta.
+    let mut bad = batch.clone();
+    bad[2].0 = &pvks[1];
+    assert!(!verify_proofs_batch_multi(&mut *rng, &bad).unwrap());
+
+    // Swapping two proofs for different keys.
+    let mut bad = batch.clone();
+    bad[0].1 = batch[1].1;
+    bad[1].1 = batch[0].1;
+    assert!(!verify_proofs_batch_multi(&mut *rng, &bad).unwrap());
+
+    // Input lengths are checked against each key.
+    let mut bad = batch;
+    bad[0].2 = &[];
+    assert!(verify_proofs_batch_multi(&mut *rng, &bad).is_err());
+}
+
+#[test]
+fn test_mimc_batch_verifier() {
+    use bellperson::groth16::BatchVerifier;
+    use group::Curve;
+    use rand::SeedableRng;
+
+    let rng = &mut rand_chacha::ChaChaRng::seed_from_u64(0);
+
+    let constants: Vec<Vec<Fr>> = (0..2)
+        .map(|_| (0..MIMC_ROUNDS).map(|_| Fr::random(&mut *rng)).collect())
+        .collect();
+    let params: Vec<_> = constants
+        .iter()
+        .map(|constants| {
+            let c = MimcDemo::<Fr> {
+                xl: None,
+                xr: None,
+                constants,
+            };
+            generate_random_parameters::<Bls12, _, _>(c, &mut *rng).unwrap()
+        })
+        .collect();
+    let pvks: Vec<_> = params
+        .iter()
+        .map(|p| prepare_verifying_key(&p.vk))
+        .collect();
+
+    let mut proofs = vec![];
+    for i in 0..9 {
+        let k = i % 2;
+        let xl = Fr::random(&mut *rng);
+        let xr = Fr::random(&mut *rng);
+        let c = MimcDemo {
+            xl: Some(xl),
+            xr: Some(xr),
+            constants: &constants[k],
+        };
+        let proof = create_random_proof(c, &params[k], &mut *rng).unwrap();
+        proofs.push((k, proof, vec![mimc(xl, xr, &constants[k])]));
+    }
+
+    let mut verifier = BatchVerifier::new();
+    assert!(verifier.verify(&mut *rng).unwrap());
+    for (k, proof, inputs) in &proofs {
+        verifier
+            .queue(&pvks[*k], proof.clone(), inputs.clone())
+            .unwrap();
+    }
+    assert_eq!(verifier.len(), proofs.len());
+    assert!(verifier.verify(&mut *rng).unwrap());
+    assert!(verifier.find_invalid(&mut *rng).unwrap().is_empty());
+
+    // The number of inputs is checked when queueing.
+    assert!(verifier
+        .queue(&pvks[0], proofs[0].1.clone(), vec![])
+        .is_err());
+    assert_eq!(verifier.len(), proofs.len());
+
+    for bad in [
+        vec![0],
+        vec![8],
+        vec![1, 2],
+        vec![0, 4, 5, 8],
+        (0..9).collect(),
+    ]
+    .iter()
+    {
+        let mut verifier = BatchVerifier::new();
+        for (i, (k, proof, inputs)) in proofs.iter().enumerate() {
+            let mut proof = proof.clone();
+            let mut inputs = inputs.clone();
+            if bad.contains(&i) {
+                if i % 2 == 0 {
+                    inputs[0] += Fr::one();
+                } else {
+                    proof.c = (proof.c + <Bls12 as Engine>::G1::generator()).to_affine();
+                }
+            }
+            verifier.queue(&pvks[*k], proof, inputs).unwrap();
+        }
+        assert!(!verifier.verify(&mut *rng).unwrap());
+        assert_eq!(verifier.find_invalid(&mut *rng).unwrap(), *bad);
+    }
+}
@@ -187,7 +187,9 @@ fn main() {
     let pvk = prepare_verifying_key(&params.vk);
 
     let srs = if opts.aggregate {
-        let x = setup_fake_srs(&mut rng, opts.proofs).specialize(opts.proofs);
+        let x = setup_fake_srs(&mut rng, opts.proofs)
+            .specialize(opts.proofs)
+            .unwrap();
         Some(x)
     } else {
         None